@@ -0,0 +1,52 @@
+//! Autoremove command implementation
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use xpm_core::{db::Database, utils::logger::Logger};
+
+use crate::commands::remove;
+
+/// Run the autoremove command
+pub async fn run(dry_run: bool) -> Result<()> {
+    let db = Database::instance()?;
+    let orphans = db.find_orphans()?;
+
+    if orphans.is_empty() {
+        Logger::info("No unused dependencies to remove");
+        return Ok(());
+    }
+
+    println!("{}", "━━━ Unused Dependencies ━━━".cyan().bold());
+    println!();
+    for pkg in &orphans {
+        println!(
+            "  {} {}",
+            pkg.name.red().bold(),
+            pkg.installed.as_deref().unwrap_or("unknown").dimmed()
+        );
+    }
+    println!();
+
+    if dry_run {
+        Logger::tip(&format!("Remove them with {}", "xpm autoremove".cyan()));
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    for pkg in &orphans {
+        if let Err(e) = remove::run(&pkg.name, false, false).await {
+            Logger::error(&format!("Failed to remove {}: {}", pkg.name, e));
+            failed.push(pkg.name.as_str());
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("Could not remove: {}", failed.join(", "));
+    }
+
+    Logger::success(&format!(
+        "Removed {} unused dependencies",
+        orphans.len().to_string().green()
+    ));
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Files and owns command implementations
+//!
+//! XPM records the files a package script creates in the watched directories
+//! while it installs the package; these commands look them up.
+
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::path::Path;
+use xpm_core::{db::Database, utils::logger::Logger};
+
+use crate::prompt::Exit;
+
+/// Run the files command, listing the files recorded for a package
+pub async fn run(package: &str, json: bool) -> Result<()> {
+    let db = Database::instance()?;
+    db.find_package_by_name(package)?
+        .ok_or_else(|| anyhow::anyhow!("Package '{}' not found in XPM database", package))?;
+
+    let recorded = db.get_package_files(package)?;
+
+    if json {
+        let result = serde_json::json!({
+            "name": package,
+            "recorded_at": recorded.as_ref().map(|r| r.recorded_at.to_rfc3339()),
+            "files": recorded.as_ref().map(|r| r.files.clone()).unwrap_or_default(),
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    let Some(recorded) = recorded else {
+        Logger::warning(&format!("No files recorded for {}", package));
+        Logger::tip("Files are recorded when a package script installs them");
+        return Ok(());
+    };
+
+    println!("{}", format!("━━━ Files of {} ━━━", package).cyan().bold());
+    println!();
+    for file in &recorded.files {
+        // Files can be deleted by hand after they were recorded
+        if Path::new(file).symlink_metadata().is_ok() {
+            println!("  {}", file);
+        } else {
+            println!("  {} {}", file.dimmed(), "(missing)".dimmed());
+        }
+    }
+    println!();
+    println!(
+        "{} file(s), recorded {}",
+        recorded.files.len(),
+        recorded.recorded_at.format("%Y-%m-%d %H:%M")
+    );
+
+    Ok(())
+}
+
+/// Run the owns command, finding the packages that recorded a file
+///
+/// A directory is owned by the packages with files under it. Fails when no
+/// package owns the path.
+pub async fn owns(path: &str, json: bool) -> Result<()> {
+    let path = std::path::absolute(path).with_context(|| format!("Invalid path: {}", path))?;
+    let owners = Database::instance()?.find_file_owners(&path)?;
+
+    if json {
+        let result = serde_json::json!({
+            "path": path.display().to_string(),
+            "owners": owners,
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else if owners.is_empty() {
+        Logger::warning(&format!("No package owns {}", path.display()));
+    } else {
+        for owner in &owners {
+            println!("{}: {}", owner.green().bold(), path.display());
+        }
+    }
+
+    if owners.is_empty() {
+        return Err(Exit(1).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! History command implementation
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use xpm_core::{db::Database, utils::logger::Logger};
+
+/// Run the history command
+pub async fn run(count: usize, package: Option<&str>, json: bool) -> Result<()> {
+    let db = Database::instance()?;
+
+    let transactions: Vec<_> = db
+        .get_transactions()?
+        .into_iter()
+        .filter(|t| t.operation.is_package_change())
+        .filter(|t| package.is_none_or(|p| t.package.as_deref() == Some(p)))
+        .take(count)
+        .collect();
+
+    if json {
+        let result: Vec<_> = transactions
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "id": t.id,
+                    "timestamp": t.timestamp.to_rfc3339(),
+                    "operation": t.operation.as_str(),
+                    "package": t.package,
+                    "old_version": t.old_version,
+                    "new_version": t.new_version,
+                    "old_method": t.old_method,
+                    "method": t.method,
+                    "old_channel": t.old_channel,
+                    "channel": t.channel,
+                    "exit_code": t.exit_code,
+                    "output_path": t.output_path,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    if transactions.is_empty() {
+        Logger::info("No transactions recorded");
+        return Ok(());
+    }
+
+    println!("{}", "━━━ Transaction History ━━━".cyan().bold());
+    println!();
+
+    // Oldest first so the most recent transaction ends up near the prompt
+    for txn in transactions.iter().rev() {
+        let status = if txn.is_success() {
+            format!("{}", "✓".green())
+        } else {
+            format!("{}", "✗".red())
+        };
+        let versions = match (&txn.old_version, &txn.new_version) {
+            (Some(old), Some(new)) => format!("{} -> {}", old.red(), new.green()),
+            (None, Some(new)) => format!("{}", new.green()),
+            (Some(old), None) => format!("{}", old.red()),
+            (None, None) => String::new(),
+        };
+
+        println!(
+            "  {} {} {} {} {} {}",
+            format!("#{}", txn.id).yellow(),
+            txn.timestamp
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+                .dimmed(),
+            txn.operation.as_str().cyan(),
+            txn.package.as_deref().unwrap_or("").bold(),
+            versions,
+            status
+        );
+
+        if let Some(method) = &txn.method {
+            let channel = txn.channel.as_deref().unwrap_or("stable");
+            println!(
+                "    Method: {}  Channel: {}",
+                method.cyan(),
+                channel.yellow()
+            );
+        }
+    }
+
+    println!();
+    Logger::tip(&format!(
+        "Undo a transaction with {}",
+        "xpm rollback <id>".cyan()
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Info command implementation
+
+use anyhow::Result;
+use futures::future::join_all;
+use owo_colors::OwoColorize;
+use xpm_core::{
+    db::{Database, Package},
+    native_pm::{detect_native_pms, NativePackage, NativePackageManager},
+    script::BashScript,
+};
+
+/// Run the info command
+///
+/// Shows the XPM package (if any) and the same name in every detected native
+/// package manager.
+pub async fn run(package: &str, json: bool) -> Result<()> {
+    let db = Database::instance()?;
+    let pkg = db.find_package_by_name(package)?;
+    let pinned = db.pinned_version(package)?;
+
+    let repo = match pkg.as_ref().and_then(|p| p.repo_id) {
+        Some(id) => db.find_repo(id)?.map(|r| r.url),
+        None => None,
+    };
+
+    let script = pkg
+        .as_ref()
+        .and_then(|p| p.script.as_deref())
+        .map(BashScript::new)
+        .filter(|s| s.exists());
+    let (install_methods, remove_methods) = match &script {
+        Some(script) => (
+            script.available_install_methods(),
+            script.available_remove_methods(),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let pms = detect_native_pms().await;
+    let native: Vec<(String, NativePackage)> = join_all(pms.iter().map(|pm| pm.get(package)))
+        .await
+        .into_iter()
+        .zip(&pms)
+        .filter_map(|(found, pm)| Some((pm.name().to_string(), found.ok()??)))
+        .collect();
+
+    if pkg.is_none() && native.is_empty() {
+        anyhow::bail!("Package '{}' not found", package);
+    }
+
+    if json {
+        let xpm = pkg.as_ref().map(|p| {
+            serde_json::json!({
+                "name": p.name,
+                "title": p.title,
+                "description": p.desc,
+                "url": p.url,
+                "version": p.version,
+                "arch": p.arch,
+                "install_methods": install_methods,
+                "remove_methods": remove_methods,
+                "defaults": p.defaults,
+                "provides": p.provides,
+                "depends": p.depends,
+                "recommends": p.recommends,
+                "conflicts": p.conflicts,
+                "repo": repo,
+                "script": p.script,
+                "installed": p.installed,
+                "method": p.method,
+                "channel": p.channel,
+                "reason": p.is_installed().then(|| p.reason.as_str()),
+                "pinned": pinned,
+            })
+        });
+        let result = serde_json::json!({
+            "xpm": xpm,
+            "native": native.iter().map(|(manager, n)| {
+                serde_json::json!({
+                    "manager": manager,
+                    "name": n.name,
+                    "version": n.version,
+                    "description": n.description,
+                    "arch": n.arch,
+                    "repo": n.repo,
+                    "installed": n.installed
+                })
+            }).collect::<Vec<_>>()
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    // Display order: native (top) → XPM (bottom, closest to prompt)
+    for (manager, native_pkg) in &native {
+        print_native(manager, native_pkg);
+    }
+
+    if let Some(pkg) = &pkg {
+        print_xpm(
+            pkg,
+            repo.as_deref(),
+            &install_methods,
+            &remove_methods,
+            pinned.as_deref(),
+        );
+    }
+
+    Ok(())
+}
+
+fn print_native(manager: &str, pkg: &NativePackage) {
+    println!(
+        "{}",
+        format!("━━━ {} ({}) ━━━", pkg.name, manager).cyan().bold()
+    );
+    field("Version", pkg.version.as_deref());
+    field("Description", pkg.description.as_deref());
+    field("Architecture", pkg.arch.as_deref());
+    field("Repository", pkg.repo.as_deref());
+    field("Installed", Some(if pkg.installed { "yes" } else { "no" }));
+    println!();
+}
+
+fn print_xpm(
+    pkg: &Package,
+    repo: Option<&str>,
+    install_methods: &[String],
+    remove_methods: &[String],
+    pinned: Option<&str>,
+) {
+    println!("{}", format!("━━━ {} (xpm) ━━━", pkg.name).cyan().bold());
+    field("Title", pkg.title.as_deref());
+    field("Description", pkg.desc.as_deref());
+    field("URL", pkg.url.as_deref());
+    field("Version", pkg.version.as_deref());
+    list("Architectures", &pkg.arch);
+    list("Install methods", install_methods);
+    list("Remove methods", remove_methods);
+    list("Defaults", &pkg.defaults);
+    list("Provides", &pkg.provides);
+    list("Depends", &pkg.depends);
+    list("Recommends", &pkg.recommends);
+    list("Conflicts", &pkg.conflicts);
+    field("Repository", repo);
+
+    match &pkg.installed {
+        Some(installed) => {
+            let method = pkg.method.as_deref().unwrap_or("unknown");
+            let channel = pkg.channel.as_deref().unwrap_or("stable");
+            println!(
+                "  {} {} via {} ({}, {})",
+                format!("{:16}", "Installed:").bold(),
+                installed.green(),
+                method.cyan(),
+                channel.yellow(),
+                pkg.reason.as_str()
+            );
+        }
+        None => field("Installed", Some("no")),
+    }
+    if let Some(pinned) = pinned {
+        println!(
+            "  {} {}",
+            format!("{:16}", "Pinned:").bold(),
+            pinned.yellow()
+        );
+    }
+    println!();
+}
+
+fn field(label: &str, value: Option<&str>) {
+    if let Some(value) = value.filter(|v| !v.is_empty()) {
+        println!(
+            "  {} {}",
+            format!("{:16}", format!("{}:", label)).bold(),
+            value
+        );
+    }
+}
+
+fn list(label: &str, values: &[String]) {
+    if !values.is_empty() {
+        field(label, Some(&values.join(", ")));
+    }
+}
@@ -1,152 +1,1246 @@
 //! Install command implementation
 
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use anyhow::{Context, Result};
+use futures::{future::join_all, stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use owo_colors::OwoColorize;
-use std::process::Stdio;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
 use xpm_core::{
-    db::{Database, Package},
-    native_pm::{detect_native_pm, NativePackageManager},
-    os::{get_architecture, get_os_info, Executable, OsType, XpmDirs},
-    script::BashScript,
-    utils::logger::Logger,
+    db::{Database, Operation, Package, PackageFiles, Repo, Transaction},
+    manifest::{LockedPackage, Lockfile},
+    native_pm::{
+        detect_native_pm, native_pm_for, NativeMode, NativePM, NativePackage, NativePackageManager,
+    },
+    os::{
+        get_architecture, get_os_info,
+        snapshot::{ChangeKind, FileChange, Snapshot},
+        Executable, OsType, XpmDirs,
+    },
+    repo::Repositories,
+    resolver::{resolve_install, InstallPlan, InstallStep},
+    script::{
+        sandbox::{declared_writes, Sandbox, SandboxReport},
+        BashScript, OutputStream, ScriptError, ScriptRunner,
+    },
+    utils::{
+        checksum::{compute_sha256, Checksum, ChecksumAlgorithm},
+        logger::Logger,
+        version::compare_versions,
+    },
 };
 
+use crate::commands::summary::{self, Outcome};
+use crate::commands::{log, review};
+use crate::prompt;
+
 pub async fn run(
     package: &str,
-    method: &str,
-    force_method: bool,
-    channel: Option<&str>,
-    custom_flags: &[String],
-    native_mode: &str,
+    options: &ScriptOptions<'_>,
+    native_mode: &NativeMode,
 ) -> Result<()> {
     Logger::info(&format!("Installing {}...", package.green().bold()));
 
     let db = Database::instance()?;
+    let parent = install_parent();
+    let requester = match parent.as_deref() {
+        Some(parent) => Requester::Package(parent),
+        None => Requester::User,
+    };
 
-    match native_mode {
-        "only" => {
-            return install_via_native_pm(package).await;
-        }
-        "off" => {
-            if let Some(pkg) = db.find_package_by_name(package)? {
-                if pkg.is_installed() {
-                    Logger::info(&format!("Reinstalling {}...", package.cyan()));
-                }
-                return install_xpm_package(&pkg, method, force_method, channel, custom_flags)
-                    .await;
+    if native_mode.includes_xpm() {
+        if let Some(pkg) = db.find_package_by_name(package)? {
+            if pkg.is_installed() {
+                Logger::info(&format!("Reinstalling {}...", package.cyan()));
             }
+
+            return install_xpm_package(&pkg, options, requester).await;
+        }
+
+        if *native_mode == NativeMode::Off {
             anyhow::bail!("Package '{}' not found in XPM database", package);
         }
-        _ => {}
     }
 
-    if let Some(pkg) = db.find_package_by_name(package)? {
-        if pkg.is_installed() {
-            Logger::info(&format!("Reinstalling {}...", package.cyan()));
+    let pms = native_mode
+        .preferred_pms(&db.get_backend_preference()?)
+        .await;
+    install_via_native_pm(package, &pms, requester).await
+}
+
+/// Install several packages in one go
+///
+/// Packages from native package managers are installed with one call per
+/// package manager. XPM packages get their dependencies installed first, one
+/// at a time, then up to `jobs` of their scripts run side by side. Every
+/// package gets a line in the summary printed at the end.
+pub async fn run_many(
+    packages: &[String],
+    options: &ScriptOptions<'_>,
+    native_mode: &NativeMode,
+    jobs: usize,
+) -> Result<()> {
+    let mut names: Vec<&str> = Vec::new();
+    for package in packages {
+        let name = select_repo(package)?;
+        if !names.contains(&name) {
+            names.push(name);
         }
+    }
 
-        return install_xpm_package(&pkg, method, force_method, channel, custom_flags).await;
+    if let [package] = names[..] {
+        return run(package, options, native_mode).await;
     }
 
-    install_via_native_pm(package).await
+    Logger::info(&format!(
+        "Installing {}...",
+        names.join(", ").green().bold()
+    ));
+
+    let db = Database::instance()?;
+    let parent = install_parent();
+    let requester = match parent.as_deref() {
+        Some(parent) => Requester::Package(parent),
+        None => Requester::User,
+    };
+
+    let mut outcomes = HashMap::new();
+    let mut xpm = Vec::new();
+    let mut native = Vec::new();
+    for &name in &names {
+        match db.find_package_by_name(name)? {
+            Some(pkg) if native_mode.includes_xpm() => xpm.push(pkg),
+            _ if *native_mode == NativeMode::Off => {
+                let error = format!("Package '{}' not found in XPM database", name);
+                outcomes.insert(name.to_string(), Err(error));
+            }
+            _ => native.push(name),
+        }
+    }
+
+    if !native.is_empty() {
+        let pms = native_mode
+            .preferred_pms(&db.get_backend_preference()?)
+            .await;
+        outcomes.extend(install_native_batch(&native, &pms, requester).await);
+    }
+
+    if !xpm.is_empty() {
+        outcomes.extend(install_xpm_batch(&xpm, options, requester, jobs).await);
+    }
+
+    summary::print("install", &names, &outcomes)
+}
+
+/// Install options given on the command line
+///
+/// Only `insecure`, `yes` and the sandbox settings carry over to
+/// dependencies, which otherwise install with the defaults.
+pub struct ScriptOptions<'a> {
+    /// Installation method, or "auto"
+    pub method: &'a str,
+    /// Use the method even when the package does not list it
+    pub force_method: bool,
+    pub channel: Option<&'a str>,
+    /// Flags passed on to the install script
+    pub custom_flags: &'a [String],
+    /// Install scripts that are not vouched for by a signed repository index
+    pub insecure: bool,
+    /// Approve new and changed scripts without asking, when review is enabled
+    pub yes: bool,
+    /// Run scripts in a sandbox, writing only to their own directories
+    pub sandbox: bool,
+    /// Let sandboxed scripts use the network
+    pub network: bool,
+    /// Let sandboxed scripts write to the paths in their `xWRITES` without
+    /// asking; `yes` only covers review
+    pub allow_writes: bool,
+}
+
+impl Default for ScriptOptions<'_> {
+    fn default() -> Self {
+        Self {
+            method: "auto",
+            force_method: false,
+            channel: None,
+            custom_flags: &[],
+            insecure: false,
+            yes: false,
+            sandbox: false,
+            network: false,
+            allow_writes: false,
+        }
+    }
+}
+
+impl ScriptOptions<'_> {
+    /// Options for installing a dependency
+    fn for_dependency(&self) -> ScriptOptions<'static> {
+        ScriptOptions {
+            insecure: self.insecure,
+            yes: self.yes,
+            sandbox: self.sandbox,
+            network: self.network,
+            allow_writes: self.allow_writes,
+            ..Default::default()
+        }
+    }
 }
 
-async fn install_via_native_pm(package: &str) -> Result<()> {
-    if let Some(pm) = detect_native_pm().await {
+/// Install packages from native package managers, one call per package manager
+async fn install_native_batch(
+    packages: &[&str],
+    pms: &[NativePM],
+    requester: Requester<'_>,
+) -> HashMap<String, Outcome> {
+    let mut outcomes = HashMap::new();
+
+    let found = join_all(packages.iter().map(|package| find_native(package, pms))).await;
+
+    let mut batches: Vec<(&NativePM, Vec<NativeInstall>)> = Vec::new();
+    for (&package, found) in packages.iter().zip(found) {
+        let Some((pm, native_pkg)) = found else {
+            let error = format!("Package '{}' not found", package);
+            outcomes.insert(package.to_string(), Err(error));
+            continue;
+        };
+
+        match NativeInstall::prepare(package, pm, native_pkg) {
+            Ok(install) => match batches.iter_mut().find(|(p, _)| p.name() == pm.name()) {
+                Some((_, installs)) => installs.push(install),
+                None => batches.push((pm, vec![install])),
+            },
+            Err(e) => {
+                outcomes.insert(package.to_string(), Err(format!("{:#}", e)));
+            }
+        }
+    }
+
+    for (pm, installs) in batches {
+        let names: Vec<String> = installs.iter().map(|i| i.name.clone()).collect();
         Logger::info(&format!(
-            "Package not in XPM, trying {}...",
+            "Installing {} via {}...",
+            names.join(", ").green().bold(),
             pm.name().cyan()
         ));
 
+        // One call for the whole batch, so it succeeds or fails as a whole
+        let result = pm
+            .install_many(&names)
+            .await
+            .map_err(|e| format!("{:#}", e));
+        for install in installs {
+            let name = install.name.clone();
+            let outcome = install
+                .finish(result.clone().map_err(anyhow::Error::msg), requester)
+                .map_err(|e| format!("{:#}", e));
+            outcomes.insert(name, outcome);
+        }
+    }
+
+    outcomes
+}
+
+/// Find a package in the first native package manager that has it
+async fn find_native<'a>(
+    package: &str,
+    pms: &'a [NativePM],
+) -> Option<(&'a NativePM, NativePackage)> {
+    for pm in pms {
         if let Ok(Some(native_pkg)) = pm.get(package).await {
-            Logger::info(&format!("Installing via {}...", pm.name().cyan()));
-            pm.install(package).await?;
+            return Some((pm, native_pkg));
+        }
+    }
+    None
+}
 
-            // Track native package in XPM database
-            let db = Database::instance()?;
-            let mut pkg = Package::new(package);
-            pkg.version = native_pkg.version.clone();
-            pkg.desc = native_pkg.description.clone();
-            pkg.installed = Some(native_pkg.version.unwrap_or_else(|| "native".to_string()));
-            pkg.is_native = true;
-            pkg.method = Some(pm.name().to_string());
-            db.upsert_package(pkg)?;
+/// Install XPM packages, running up to `jobs` of their scripts at once
+///
+/// Dependencies are installed first and one at a time, so a dependency
+/// shared by several packages is only installed once.
+async fn install_xpm_batch(
+    packages: &[Package],
+    options: &ScriptOptions<'_>,
+    requester: Requester<'_>,
+    jobs: usize,
+) -> HashMap<String, Outcome> {
+    let mut outcomes = HashMap::new();
+    let requested: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+
+    let mut plans = Vec::new();
+    for pkg in packages {
+        if pkg.is_installed() {
+            Logger::info(&format!("Reinstalling {}...", pkg.name.cyan()));
+        }
+        match plan_install(pkg) {
+            Ok(plan) => plans.push((pkg, plan)),
+            Err(e) => {
+                outcomes.insert(pkg.name.clone(), Err(format!("{:#}", e)));
+            }
+        }
+    }
 
-            Logger::success(&format!("{} installed successfully", package.green()));
-            return Ok(());
+    let mut dependencies: HashMap<String, Outcome> = HashMap::new();
+    for (pkg, plan) in &plans {
+        for step in plan.dependencies() {
+            if dependencies.contains_key(step.name()) {
+                continue;
+            }
+
+            let result = match step {
+                // Requested packages keep the user's options and install reason
+                InstallStep::Xpm(dep) if requested.contains(&dep.name.as_str()) => {
+                    Logger::info(&format!("Installing {}...", dep.name.green().bold()));
+                    install_script_package(dep, options, requester).await
+                }
+                InstallStep::Xpm(dep) => {
+                    Logger::info(&format!("Installing {}...", dep.name.green().bold()));
+                    install_script_package(
+                        dep,
+                        &options.for_dependency(),
+                        Requester::Package(&pkg.name),
+                    )
+                    .await
+                }
+                InstallStep::Native(name) => install_native_dependency(name, &pkg.name).await,
+            };
+            dependencies.insert(
+                step.name().to_string(),
+                result.map_err(|e| format!("{:#}", e)),
+            );
         }
     }
 
-    anyhow::bail!("Package '{}' not found", package)
+    let mut installs = Vec::new();
+    for (pkg, plan) in &plans {
+        // Already installed as another requested package's dependency
+        if let Some(outcome) = dependencies.get(&pkg.name) {
+            outcomes.insert(pkg.name.clone(), outcome.clone());
+            continue;
+        }
+
+        let failed = plan
+            .dependencies()
+            .iter()
+            .find(|step| matches!(dependencies.get(step.name()), Some(Err(_))));
+        if let Some(step) = failed {
+            let error = format!("Failed to install dependency {}", step.name());
+            outcomes.insert(pkg.name.clone(), Err(error));
+            continue;
+        }
+
+        match ScriptInstall::prepare(pkg, options) {
+            Ok(install) => installs.push((install, plan)),
+            Err(e) => {
+                outcomes.insert(pkg.name.clone(), Err(format!("{:#}", e)));
+            }
+        }
+    }
+
+    let progress = MultiProgress::new();
+    let mut results: Vec<_> = stream::iter(
+        installs
+            .iter()
+            .map(|(install, _)| install.execute(Some(&progress))),
+    )
+    .buffered(jobs.max(1))
+    .collect()
+    .await;
+    if jobs > 1 {
+        drop_shared_files(&mut results);
+    }
+
+    // Database updates happen one at a time, once every script is done
+    for ((install, plan), execution) in installs.into_iter().zip(results) {
+        let name = install.pkg.name.clone();
+        let outcome = install.finish(execution, requester);
+        if outcome.is_ok() && !plan.recommends.is_empty() {
+            Logger::tip(&format!(
+                "{} also recommends: {}",
+                name,
+                plan.recommends.join(", ").cyan()
+            ));
+        }
+        outcomes.insert(name, outcome.map_err(|e| format!("{:#}", e)));
+    }
+
+    outcomes
 }
 
-async fn install_xpm_package(
-    pkg: &Package,
-    method: &str,
-    force_method: bool,
-    channel: Option<&str>,
+/// Install packages exactly as a lockfile recorded them
+///
+/// The repository of each package is checked out at its locked commit while
+/// the script runs, and the script must still match the recorded SHA-256.
+/// Native packages are installed from the package manager that was locked,
+/// or any available one when none was recorded.
+/// Without package names, every locked package is installed.
+pub async fn run_locked(
+    packages: &[String],
+    lockfile: &str,
     custom_flags: &[String],
+    insecure: bool,
+    yes: bool,
+    jobs: usize,
 ) -> Result<()> {
-    let script_path = pkg
-        .script
-        .as_ref()
-        .ok_or_else(|| anyhow::anyhow!("Package has no installation script"))?;
+    let lock = Lockfile::load(Path::new(lockfile))?;
+    let whole = packages.is_empty();
+    let packages = if whole {
+        lock.packages.iter().map(|p| p.name.clone()).collect()
+    } else {
+        packages.to_vec()
+    };
+
+    // Packages locked to the same commit and options are installed together
+    let mut groups: BTreeMap<_, Vec<String>> = BTreeMap::new();
+    for name in &packages {
+        let entry = lock
+            .find(name)
+            .ok_or_else(|| anyhow::anyhow!("{} is not in {}", name, lockfile))?;
+        if !entry.is_reproducible() {
+            // Installing the whole lockfile goes on with what can be reproduced
+            if whole {
+                Logger::warning(&format!(
+                    "Skipping {}, it has no recorded source in {}",
+                    name, lockfile
+                ));
+                continue;
+            }
+            anyhow::bail!(
+                "{} has no recorded source in {}, reinstall it and run 'xpm lock' again",
+                name,
+                lockfile
+            );
+        }
 
-    let script = BashScript::new(script_path);
-    if !script.exists() {
-        anyhow::bail!("Installation script not found: {}", script_path);
+        let names = groups
+            .entry((
+                entry.native,
+                entry.repo.clone(),
+                entry.commit.clone(),
+                entry.method.clone(),
+                entry.channel.clone(),
+            ))
+            .or_default();
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
     }
 
-    let install_method = if force_method && method != "auto" {
-        if !script.has_function(&format!("install_{}", method)) {
-            anyhow::bail!("Method '{}' not available for this package", method);
+    let mut errors = Vec::new();
+    for ((native, repo, commit, method, channel), names) in groups {
+        let result = match (native, repo, commit) {
+            (true, _, _) => {
+                let native_mode = lock
+                    .find(&names[0])
+                    .map_or(NativeMode::Only, LockedPackage::native_mode);
+                let options = ScriptOptions {
+                    custom_flags,
+                    insecure,
+                    yes,
+                    ..Default::default()
+                };
+                run_many(&names, &options, &native_mode, jobs).await
+            }
+            (false, Some(repo), Some(commit)) => {
+                let options = ScriptOptions {
+                    method: method.as_deref().unwrap_or("auto"),
+                    force_method: method.is_some(),
+                    channel: channel.as_deref(),
+                    custom_flags,
+                    insecure,
+                    yes,
+                    ..Default::default()
+                };
+                install_at_commit(&lock, &repo, &commit, &names, &options, jobs).await
+            }
+            _ => unreachable!("reproducible script entries have a repo and a commit"),
+        };
+
+        if let Err(e) = result {
+            errors.push(format!("{:#}", e));
         }
-        method.to_string()
-    } else {
-        determine_method(method, pkg, &script)?
+    }
+
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Could not install everything from {}: {}",
+            lockfile,
+            errors.join("; ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Resolve `repo/package` to the package of that repository
+///
+/// Returns the plain package name. Names whose prefix is not a repository,
+/// such as Homebrew's `hashicorp/tap/terraform`, are returned unchanged.
+fn select_repo(package: &str) -> Result<&str> {
+    let Some((spec, name)) = package.rsplit_once('/') else {
+        return Ok(package);
     };
+    let Some(repo) = Repositories::find_repo(spec)? else {
+        return Ok(package);
+    };
+
+    Repositories::use_repo_package(&repo, name)?;
+    Ok(name)
+}
+
+/// Install packages with their repository checked out at a locked commit
+///
+/// The clone and the package index go back to where they were afterwards,
+/// so search, upgrade and plain installs keep seeing the current scripts.
+async fn install_at_commit(
+    lock: &Lockfile,
+    url: &str,
+    commit: &str,
+    names: &[String],
+    options: &ScriptOptions<'_>,
+    jobs: usize,
+) -> Result<()> {
+    let db = Database::instance()?;
+    let repo = match db.find_repo_by_url(url)? {
+        Some(repo) => repo,
+        None => {
+            Logger::info(&format!("Adding repository: {}...", url.cyan()));
+            Repositories::add_repo(Repo::new(url)).await?
+        }
+    };
+    let root = repo
+        .local_path
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Repository {} has not been cloned", url))?;
+    let root = Path::new(&root);
+    let head = Repositories::head_ref(root)?;
+
+    Logger::info(&format!(
+        "Checking out {} at {}",
+        Repositories::repo_name(url).cyan(),
+        commit.get(..12).unwrap_or(commit).yellow()
+    ));
+    let result = match Repositories::checkout(root, commit).await {
+        Ok(()) => match verify_locked(lock, &repo, root, names) {
+            Ok(()) => run_many(names, options, &NativeMode::Off, jobs).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    let restored = restore_repo(&repo, root, &head).await;
+    result.and(restored)
+}
+
+/// Reindex a repository at a locked commit and check the locked scripts
+fn verify_locked(lock: &Lockfile, repo: &Repo, root: &Path, names: &[String]) -> Result<()> {
+    Repositories::index_repo(repo)?;
+
+    for name in names {
+        let Some(entry) = lock.find(name) else {
+            continue;
+        };
+        let (Some(script), Some(expected)) = (&entry.script, &entry.sha256) else {
+            continue;
+        };
+
+        let actual = compute_sha256(&root.join(script))
+            .with_context(|| format!("Locked script of {} is missing", name))?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            anyhow::bail!(
+                "Script of {} does not match the lockfile (expected {}, found {})",
+                name,
+                expected,
+                actual
+            );
+        }
 
-    Logger::info(&format!("Using method: {}", install_method.cyan()));
+        // The locked repository wins over higher priority ones
+        Repositories::use_repo_package(repo, name)?;
+    }
 
-    let has_validate = script.has_function("validate");
+    Ok(())
+}
 
-    let install_script = build_install_script(
-        script_path,
-        &install_method,
-        channel,
-        &pkg.name,
-        custom_flags,
-    )?;
+/// Put a repository clone back at its previous HEAD and reindex it
+async fn restore_repo(repo: &Repo, root: &Path, head: &str) -> Result<()> {
+    Repositories::restore_head(root, head).await?;
+    Repositories::index_repo(repo)?;
+    Repositories::resolve_packages()?;
+    Database::instance()?.rebuild_search_index()?;
+    Ok(())
+}
+
+/// Reinstall an installed package at its latest version, keeping its install reason
+pub async fn upgrade(pkg: &Package, insecure: bool, yes: bool) -> Result<()> {
+    Logger::info(&format!("Upgrading {}...", pkg.name.green().bold()));
+
+    if pkg.is_native {
+        return upgrade_via_native_pm(pkg).await;
+    }
 
-    // Run installation
-    run_script(&install_script, &pkg.name).await?;
+    let options = ScriptOptions {
+        method: pkg.method.as_deref().unwrap_or("auto"),
+        channel: pkg.channel.as_deref(),
+        insecure,
+        yes,
+        ..Default::default()
+    };
+    install_xpm_package(pkg, &options, Requester::Upgrade).await
+}
 
-    // Validate installation if possible
-    if has_validate {
-        let validate_script = format!(r#"source "{}" && validate"#, script_path);
+/// Who asked for an install, which decides the install reason recorded for it
+#[derive(Debug, Clone, Copy)]
+enum Requester<'a> {
+    /// The user, directly
+    User,
+    /// Another package, from its dependencies or its own script
+    Package(&'a str),
+    /// An upgrade of an already installed package
+    Upgrade,
+}
 
-        match run_script(&validate_script, "validation").await {
-            Ok(_) => Logger::success("Validation passed"),
-            Err(_) => Logger::warning("Validation failed - package may not be installed correctly"),
+impl Requester<'_> {
+    fn apply(self, pkg: &mut Package) {
+        match self {
+            Requester::User => pkg.set_install_reason(None),
+            Requester::Package(parent) => pkg.set_install_reason(Some(parent)),
+            Requester::Upgrade => {}
         }
     }
+}
+
+/// Get the package whose script invoked this install, if any
+///
+/// Package scripts run with `XPM_PARENT` set to their own name, so a nested
+/// `$XPM install` is recorded as a dependency of that package.
+pub fn install_parent() -> Option<String> {
+    std::env::var("XPM_PARENT").ok().filter(|p| !p.is_empty())
+}
+
+/// Upgrade a package tracked from the native package manager in place
+///
+/// `pkg.version` holds the version the package manager offered.
+async fn upgrade_via_native_pm(pkg: &Package) -> Result<()> {
+    let pm = native_pm_for(pkg.method.as_deref())
+        .await
+        .ok_or_else(|| anyhow::anyhow!("No native package manager found"))?;
 
-    // Update database
     let db = Database::instance()?;
-    let mut updated_pkg = pkg.clone();
-    updated_pkg.installed = Some(pkg.version.clone().unwrap_or_else(|| "unknown".to_string()));
-    updated_pkg.method = Some(install_method);
-    updated_pkg.channel = channel.map(String::from);
+    let new_version = pkg.version.clone().unwrap_or_else(|| "native".to_string());
+
+    let mut txn = Transaction::from_package(Operation::Upgrade, pkg);
+    txn.new_version = Some(new_version.clone());
+    txn.method = Some(pm.name().to_string());
+
+    let result = pm.upgrade(&pkg.name).await;
+    txn.finish(ScriptError::exit_code_of(&result));
+    db.record_transaction(txn)?;
+    result?;
+
+    let mut updated_pkg = db
+        .find_package_by_name(&pkg.name)?
+        .unwrap_or_else(|| pkg.clone());
+    updated_pkg.version = Some(new_version.clone());
+    updated_pkg.installed = Some(new_version);
+    updated_pkg.method = Some(pm.name().to_string());
     db.upsert_package(updated_pkg)?;
 
-    Logger::success(&format!("{} installed successfully", pkg.name.green()));
+    Logger::success(&format!("{} upgraded successfully", pkg.name.green()));
+    Ok(())
+}
+
+/// Install a package through the first native package manager that has it
+async fn install_via_native_pm(
+    package: &str,
+    pms: &[NativePM],
+    requester: Requester<'_>,
+) -> Result<()> {
+    for pm in pms {
+        Logger::info(&format!("Trying {}...", pm.name().cyan()));
+
+        if let Ok(Some(native_pkg)) = pm.get(package).await {
+            Logger::info(&format!("Installing via {}...", pm.name().cyan()));
+
+            let install = NativeInstall::prepare(package, pm, native_pkg)?;
+            let result = pm.install(package).await;
+            return install.finish(result, requester);
+        }
+    }
+
+    anyhow::bail!("Package '{}' not found", package)
+}
+
+/// A package about to be installed through a native package manager
+struct NativeInstall {
+    name: String,
+    native_pkg: NativePackage,
+    previous: Package,
+    txn: Transaction,
+}
+
+impl NativeInstall {
+    /// Check the pin and open the transaction for the install
+    fn prepare(package: &str, pm: &NativePM, native_pkg: NativePackage) -> Result<Self> {
+        let db = Database::instance()?;
+        let previous = db
+            .find_package_by_name(package)?
+            .unwrap_or_else(|| Package::new(package));
+        let new_version = native_pkg
+            .version
+            .clone()
+            .unwrap_or_else(|| "native".to_string());
+
+        check_pin(db, package, native_pkg.version.as_deref())?;
+
+        let mut txn = Transaction::from_package(operation_for(&previous, &new_version), &previous);
+        txn.new_version = Some(new_version);
+        txn.method = Some(pm.name().to_string());
+
+        Ok(Self {
+            name: package.to_string(),
+            native_pkg,
+            previous,
+            txn,
+        })
+    }
+
+    /// Record the outcome and track the package in the XPM database
+    fn finish(self, result: Result<()>, requester: Requester<'_>) -> Result<()> {
+        let db = Database::instance()?;
+        let mut txn = self.txn;
+        txn.finish(ScriptError::exit_code_of(&result));
+        let new_version = txn.new_version.clone();
+        let method = txn.method.clone();
+        db.record_transaction(txn)?;
+        result?;
+
+        // Track native package in XPM database
+        let mut pkg = Package::new(&self.name);
+        pkg.keep_install_state(&self.previous);
+        requester.apply(&mut pkg);
+        pkg.version = self.native_pkg.version;
+        pkg.desc = self.native_pkg.description;
+        pkg.installed = new_version;
+        pkg.is_native = true;
+        pkg.method = method;
+        db.upsert_package(pkg)?;
+
+        Logger::success(&format!("{} installed successfully", self.name.green()));
+        Ok(())
+    }
+}
+
+async fn install_xpm_package(
+    pkg: &Package,
+    options: &ScriptOptions<'_>,
+    requester: Requester<'_>,
+) -> Result<()> {
+    let plan = plan_install(pkg)?;
+
+    let dependencies = plan.dependencies();
+    if !dependencies.is_empty() {
+        let names: Vec<_> = dependencies.iter().map(|s| s.name()).collect();
+        Logger::info(&format!(
+            "Installing dependencies: {}",
+            names.join(", ").cyan()
+        ));
+
+        for step in dependencies {
+            match step {
+                InstallStep::Xpm(dep) => {
+                    Logger::info(&format!("Installing {}...", dep.name.green().bold()));
+                    install_script_package(
+                        dep,
+                        &options.for_dependency(),
+                        Requester::Package(&pkg.name),
+                    )
+                    .await
+                    .with_context(|| format!("Failed to install dependency {}", dep.name))?;
+                }
+                InstallStep::Native(name) => install_native_dependency(name, &pkg.name).await?,
+            }
+        }
+    }
+
+    install_script_package(pkg, options, requester).await?;
+
+    if !plan.recommends.is_empty() {
+        Logger::tip(&format!(
+            "{} also recommends: {}",
+            pkg.name,
+            plan.recommends.join(", ").cyan()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolve the install order for a package, refusing plans that move a pinned package
+fn plan_install(pkg: &Package) -> Result<InstallPlan> {
+    let plan = resolve_install(pkg)?;
+
+    let db = Database::instance()?;
+    for step in &plan.steps {
+        if let InstallStep::Xpm(step) = step {
+            check_pin(db, &step.name, step.version.as_deref())?;
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Refuse to move a pinned package away from the version it is pinned at
+fn check_pin(db: &Database, name: &str, version: Option<&str>) -> Result<()> {
+    if let Some(pinned) = db.pinned_version(name)? {
+        let version = version.unwrap_or("unknown");
+        if compare_versions(version, &pinned) != Ordering::Equal {
+            anyhow::bail!(
+                "{} is pinned at {} but {} would be installed, run 'xpm unpin {}' first",
+                name,
+                pinned,
+                version,
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Install a dependency that is not in XPM through the native package manager
+async fn install_native_dependency(name: &str, parent: &str) -> Result<()> {
+    let pm = detect_native_pm()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Dependency {} requires a native package manager", name))?;
+
+    if pm.is_installed(name).await.unwrap_or(false) {
+        return Ok(());
+    }
+
+    install_via_native_pm(name, &[pm], Requester::Package(parent))
+        .await
+        .with_context(|| format!("Failed to install dependency {}", name))
+}
+
+async fn install_script_package(
+    pkg: &Package,
+    options: &ScriptOptions<'_>,
+    requester: Requester<'_>,
+) -> Result<()> {
+    let install = ScriptInstall::prepare(pkg, options)?;
+    let execution = install.execute(None).await;
+    install.finish(execution, requester)
+}
+
+/// A package script install that has been planned but not run yet
+///
+/// Running the script does not touch the database, so several installs can
+/// run side by side and be finished one at a time afterwards.
+struct ScriptInstall {
+    pkg: Package,
+    method: String,
+    channel: Option<String>,
+    script: String,
+    validate: Option<String>,
+    runner: ScriptRunner,
+    sandbox: Option<Sandbox>,
+    txn: Transaction,
+    new_version: String,
+    script_sha256: Option<String>,
+    repo_commit: Option<String>,
+}
+
+impl ScriptInstall {
+    /// Pick the install method and build the script to run
+    fn prepare(pkg: &Package, options: &ScriptOptions<'_>) -> Result<Self> {
+        let ScriptOptions {
+            method,
+            force_method,
+            channel,
+            insecure,
+            yes,
+            sandbox,
+            network,
+            allow_writes,
+            ..
+        } = *options;
+
+        let script_path = pkg
+            .script
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Package has no installation script"))?;
+
+        let script = BashScript::new(script_path);
+        if !script.exists() {
+            anyhow::bail!("Installation script not found: {}", script_path);
+        }
+
+        // Read once, so the script that runs is the one verified and reviewed
+        let content = review::read_script(pkg)?;
+
+        // Scripts run with root privileges, so their repository must vouch
+        // for them
+        if let Err(e) = Repositories::verify_script(pkg, &content) {
+            if !insecure {
+                anyhow::bail!(
+                    "Refusing to run the script of {}: {:#} (pass --insecure to run it anyway)",
+                    pkg.name,
+                    e
+                );
+            }
+            Logger::warning(&format!(
+                "Running unverified script of {}: {:#}",
+                pkg.name, e
+            ));
+        }
+
+        review::check(pkg, &content, yes)?;
+
+        let install_method = if force_method && method != "auto" {
+            if !script.has_function(&format!("install_{}", method)) {
+                anyhow::bail!("Method '{}' not available for this package", method);
+            }
+            method.to_string()
+        } else {
+            determine_method(method, pkg, &script)?
+        };
+
+        Logger::info(&format!(
+            "Using method for {}: {}",
+            pkg.name,
+            install_method.cyan()
+        ));
+
+        let validate = script
+            .has_function("validate")
+            .then(|| format!("{}\nvalidate", content));
+
+        let sandbox = if sandbox {
+            let declared = declared_writes(&script)
+                .with_context(|| format!("Refusing to sandbox the script of {}", pkg.name))?;
+            confirm_writes(&pkg.name, &declared, allow_writes)?;
+            let sandbox = Sandbox::for_package(&pkg.name, &declared)?.with_network(network);
+            Logger::info(&format!(
+                "Sandboxing {} with {} ({})",
+                pkg.name,
+                sandbox.backend().name().cyan(),
+                if sandbox.allows_network() {
+                    "network allowed"
+                } else {
+                    "no network"
+                }
+            ));
+            Some(sandbox)
+        } else {
+            None
+        };
+
+        let install_script = build_install_script(&content, &install_method, &pkg.name, options)?;
+
+        let new_version = pkg.version.clone().unwrap_or_else(|| "unknown".to_string());
+
+        // Recorded so the install can be locked and reproduced elsewhere
+        let script_sha256 = Some(Checksum::hash_bytes(
+            content.as_bytes(),
+            ChecksumAlgorithm::Sha256,
+        ));
+        let repo_commit = Repositories::package_commit(pkg).ok().flatten();
+
+        let operation = operation_for(pkg, &new_version);
+        let runner = ScriptRunner::for_operation(operation.as_str(), &pkg.name)?;
+
+        let mut txn = Transaction::from_package(operation, pkg);
+        txn.new_version = Some(new_version.clone());
+        txn.method = Some(install_method.clone());
+        txn.channel = channel.map(String::from);
+        txn.output_path = runner.log_path().map(|p| p.display().to_string());
+
+        Ok(Self {
+            pkg: pkg.clone(),
+            method: install_method,
+            channel: channel.map(String::from),
+            script: install_script,
+            validate,
+            runner,
+            sandbox,
+            txn,
+            new_version,
+            script_sha256,
+            repo_commit,
+        })
+    }
+
+    /// Run the install script, then its validation if it has one
+    async fn execute(&self, progress: Option<&MultiProgress>) -> Execution {
+        // A sandboxed run already reports the files it changed
+        let before = match &self.sandbox {
+            Some(_) => None,
+            None => Snapshot::watched().await.ok(),
+        };
+        let (result, mut report) = run_script(
+            &self.script,
+            &self.pkg.name,
+            &self.runner,
+            self.sandbox.as_ref(),
+            progress,
+        )
+        .await;
+        if result.is_err() {
+            return Execution {
+                result,
+                validated: None,
+                report,
+                created: None,
+            };
+        }
+
+        let created = match (&report, before) {
+            // The report also covers xTMP, which is not watched
+            (Some(report), _) => XpmDirs::watched_dirs().ok().map(|watched| {
+                created_files(&report.changes)
+                    .into_iter()
+                    .filter(|path| watched.iter().any(|root| path.starts_with(root)))
+                    .collect()
+            }),
+            (None, Some(before)) => Snapshot::watched()
+                .await
+                .ok()
+                .map(|after| created_files(&before.changes(&after))),
+            (None, None) => None,
+        };
+
+        let validated = match &self.validate {
+            Some(validate) => {
+                let name = match progress {
+                    Some(_) => format!("{} validation", self.pkg.name),
+                    None => "validation".to_string(),
+                };
+                let (validation, validate_report) = run_script(
+                    validate,
+                    &name,
+                    &self.runner,
+                    self.sandbox.as_ref(),
+                    progress,
+                )
+                .await;
+                if let (Some(report), Some(validate_report)) = (&mut report, validate_report) {
+                    report.merge(validate_report);
+                }
+                Some(validation.is_ok())
+            }
+            None => None,
+        };
+
+        Execution {
+            result,
+            validated,
+            report,
+            created,
+        }
+    }
+
+    /// Record the outcome and update the database
+    fn finish(self, execution: Execution, requester: Requester<'_>) -> Result<()> {
+        let db = Database::instance()?;
+        let Execution {
+            result,
+            validated,
+            report,
+            created,
+        } = execution;
+
+        if let Some(report) = &report {
+            print_sandbox_report(&self.pkg.name, report);
+        }
+
+        let mut txn = self.txn;
+        txn.finish(ScriptError::exit_code_of(&result));
+        let txn = db.record_transaction(txn)?;
+        if result.is_err() {
+            log::print_output_tail(&txn);
+        }
+        result?;
+
+        match validated {
+            Some(true) => Logger::success("Validation passed"),
+            Some(false) => {
+                Logger::warning("Validation failed - package may not be installed correctly")
+            }
+            None => {}
+        }
+
+        // Update database (reloaded, nested installs may have touched it)
+        let mut updated_pkg = db
+            .find_package_by_name(&self.pkg.name)?
+            .unwrap_or_else(|| self.pkg.clone());
+        requester.apply(&mut updated_pkg);
+        updated_pkg.installed = Some(self.new_version);
+        updated_pkg.method = Some(self.method);
+        updated_pkg.channel = self.channel;
+        updated_pkg.script_sha256 = self.script_sha256;
+        updated_pkg.repo_commit = self.repo_commit;
+        db.upsert_package(updated_pkg)?;
+
+        if let Some(created) = created {
+            record_files(db, &self.pkg.name, created)?;
+        }
+
+        Logger::success(&format!("{} installed successfully", self.pkg.name.green()));
+        Ok(())
+    }
+}
+
+/// What running a package script gave
+struct Execution {
+    result: Result<()>,
+    /// Whether validation passed, if the script has one
+    validated: Option<bool>,
+    /// What the script did to the filesystem, if it ran in a sandbox
+    report: Option<SandboxReport>,
+    /// Files the install script created in the watched directories, when
+    /// they could be read
+    created: Option<Vec<PathBuf>>,
+}
+
+/// Get the files that were created
+fn created_files(changes: &[FileChange]) -> Vec<PathBuf> {
+    changes
+        .iter()
+        .filter(|change| change.kind == ChangeKind::Created)
+        .map(|change| change.path.clone())
+        .collect()
+}
+
+/// Leave out the files more than one of the scripts run side by side created
+///
+/// Snapshots cannot tell which script wrote a file, and a file recorded for
+/// the wrong package would be deleted with it.
+fn drop_shared_files(executions: &mut [Execution]) {
+    let mut seen: HashMap<PathBuf, usize> = HashMap::new();
+    for created in executions.iter().filter_map(|e| e.created.as_ref()) {
+        for path in created {
+            *seen.entry(path.clone()).or_default() += 1;
+        }
+    }
+
+    let shared = seen.values().filter(|&&count| count > 1).count();
+    if shared == 0 {
+        return;
+    }
+    for created in executions.iter_mut().filter_map(|e| e.created.as_mut()) {
+        created.retain(|path| seen[path] == 1);
+    }
+    Logger::warning(&format!(
+        "Not recording {} file(s) created while several scripts ran",
+        shared
+    ));
+}
+
+/// Add the files an install created to those recorded for the package
+///
+/// A reinstall finds the files of the previous install already there, so
+/// those are kept as long as they still exist.
+fn record_files(db: &Database, name: &str, created: Vec<PathBuf>) -> Result<()> {
+    let mut files: Vec<String> = db
+        .get_package_files(name)?
+        .map(|recorded| recorded.files)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|file| Path::new(file).symlink_metadata().is_ok())
+        .collect();
+    if !created.is_empty() {
+        Logger::info(&format!(
+            "Recorded {} new file(s), see: xpm files {}",
+            created.len(),
+            name
+        ));
+    }
+    files.extend(created.iter().map(|path| path.display().to_string()));
+
+    if files.is_empty() {
+        db.delete_package_files(name)
+    } else {
+        db.record_package_files(PackageFiles::new(name, files))
+    }
+}
+
+/// Changed files listed before the rest are only counted
+const REPORT_LIMIT: usize = 20;
+
+/// Show the paths a sandboxed script declares in `xWRITES` and ask before
+/// making them writable, unless `allowed` by `--allow-writes`
+fn confirm_writes(name: &str, paths: &[PathBuf], allowed: bool) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    Logger::info(&format!("The script of {} asks to write to:", name.cyan()));
+    for path in paths {
+        println!("  {}", path.display());
+    }
+
+    if allowed {
+        return Ok(());
+    }
+    // Nested installs run with their output captured, so they cannot ask
+    if !prompt::is_interactive() || install_parent().is_some() {
+        anyhow::bail!(
+            "The script of {} writes outside its own directories, pass --allow-writes to allow it",
+            name
+        );
+    }
+    if !prompt::confirm("Let it write there?", false)? {
+        anyhow::bail!("The script of {} was not allowed to write there", name);
+    }
     Ok(())
 }
 
+/// Print the files a sandboxed script changed and the writes it was denied
+fn print_sandbox_report(name: &str, report: &SandboxReport) {
+    println!();
+    println!("{}", format!("━━━ Sandbox: {} ━━━", name).cyan().bold());
+    println!();
+
+    if report.changes.is_empty() {
+        println!("  No files changed");
+    }
+    for change in report.changes.iter().take(REPORT_LIMIT) {
+        let kind = format!("{:<8}", change.kind.as_str());
+        let kind = match change.kind {
+            ChangeKind::Created => kind.green().to_string(),
+            ChangeKind::Modified => kind.yellow().to_string(),
+            ChangeKind::Removed => kind.red().to_string(),
+        };
+        println!("  {} {}", kind, change.path.display());
+    }
+    if report.changes.len() > REPORT_LIMIT {
+        println!(
+            "  {}",
+            format!("... and {} more", report.changes.len() - REPORT_LIMIT).dimmed()
+        );
+    }
+    println!();
+
+    for path in &report.denied {
+        Logger::warning(&format!("Blocked write to {}", path.display()));
+    }
+    if !report.denied.is_empty() {
+        Logger::tip("Scripts can declare the paths they write to in xWRITES");
+    }
+}
+
+/// Classify an install as an upgrade when it replaces a different installed version
+fn operation_for(previous: &Package, new_version: &str) -> Operation {
+    match &previous.installed {
+        Some(installed) if compare_versions(installed, new_version) != Ordering::Equal => {
+            Operation::Upgrade
+        }
+        _ => Operation::Install,
+    }
+}
+
 fn determine_method(requested: &str, pkg: &Package, script: &BashScript) -> Result<String> {
     if requested != "auto" {
         // Verify requested method is available
@@ -221,23 +1315,22 @@ fn get_update_command(method: &str, sudo_cmd: &str) -> String {
 }
 
 fn build_install_script(
-    script_path: &str,
+    content: &str,
     method: &str,
-    channel: Option<&str>,
     pkg_name: &str,
-    custom_flags: &[String],
+    options: &ScriptOptions<'_>,
 ) -> Result<String> {
     let os_info = get_os_info();
     let arch = get_architecture();
 
-    // Determine sudo command
-    let sudo_cmd = if os_info.os_type == OsType::Android {
+    // Determine sudo command, there are no privileges to gain in a sandbox
+    let sudo_cmd = if os_info.os_type == OsType::Android || options.sandbox {
         "".to_string()
     } else {
         std::env::var("XPM_SUDO").unwrap_or_else(|_| "sudo".to_string())
     };
 
-    let channel = channel.unwrap_or("stable");
+    let channel = options.channel.unwrap_or("stable");
 
     let xpm_path = std::env::current_exe()
         .map(|p| p.display().to_string())
@@ -267,7 +1360,7 @@ fn build_install_script(
     let has_snap = Executable::new("snap").exists();
     let has_flatpak = Executable::new("flatpak").exists();
 
-    let flags_str = custom_flags.join(" ");
+    let flags_str = options.custom_flags.join(" ");
 
     // Get update command for this method
     let update_command = get_update_command(method, &sudo_cmd);
@@ -283,6 +1376,9 @@ set -e
 
 # XPM environment variables
 export XPM="{xpm_path}"
+export XPM_PARENT="{pkg_name}"
+export XPM_YES="{yes}"
+export XPM_INSECURE="{insecure}"
 export xSUDO="{sudo_cmd}"
 export xCHANNEL="{channel}"
 
@@ -315,14 +1411,17 @@ export XPM_CHANNEL="{channel}"
 # Create temp directory if needed
 mkdir -p "$xTMP"
 
-# Source the package script
-source "{script_path}"
+# The package script
+{content}
 
 {update_section}
 # Run installation
 install_{method} "$xSUDO"
 "#,
         xpm_path = xpm_path,
+        pkg_name = pkg_name,
+        yes = if options.yes { "1" } else { "" },
+        insecure = if options.insecure { "1" } else { "" },
         sudo_cmd = sudo_cmd,
         channel = channel,
         x_os = x_os,
@@ -337,7 +1436,7 @@ install_{method} "$xSUDO"
         has_snap = has_snap,
         has_flatpak = has_flatpak,
         flags_str = flags_str,
-        script_path = script_path,
+        content = content,
         update_section = update_section,
         method = method
     );
@@ -345,64 +1444,44 @@ install_{method} "$xSUDO"
     Ok(script)
 }
 
-async fn run_script(script: &str, name: &str) -> Result<()> {
-    use std::sync::Arc;
-
-    let spinner = Arc::new(ProgressBar::new_spinner());
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.cyan} {msg}")
-            .unwrap(),
-    );
+/// Run a script behind a spinner, added to `progress` when several run at once
+///
+/// Also returns the sandbox report when the script runs in a sandbox.
+async fn run_script(
+    script: &str,
+    name: &str,
+    runner: &ScriptRunner,
+    sandbox: Option<&Sandbox>,
+    progress: Option<&MultiProgress>,
+) -> (Result<()>, Option<SandboxReport>) {
+    let spinner = match progress {
+        Some(progress) => progress.add(ProgressBar::new_spinner()),
+        None => ProgressBar::new_spinner(),
+    };
+    // Side by side spinners need to say which script they belong to
+    let template = if progress.is_some() {
+        "{spinner:.cyan} {prefix:.bold} {msg}"
+    } else {
+        "{spinner:.cyan} {msg}"
+    };
+    spinner.set_style(ProgressStyle::default_spinner().template(template).unwrap());
+    spinner.set_prefix(name.to_string());
     spinner.set_message(format!("Running {}...", name));
     spinner.enable_steady_tick(Duration::from_millis(100));
 
-    let mut child = Command::new("bash")
-        .arg("-c")
-        .arg(script)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
-
-    // Spawn task to read stdout
-    let spinner_stdout = Arc::clone(&spinner);
-    let stdout_task = tokio::spawn(async move {
-        if let Some(stdout) = stdout {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                spinner_stdout.set_message(line);
-            }
-        }
-    });
-
-    // Spawn task to read stderr
-    let spinner_stderr = Arc::clone(&spinner);
-    let stderr_task = tokio::spawn(async move {
-        if let Some(stderr) = stderr {
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                // Display stderr in yellow to differentiate from stdout
-                spinner_stderr.set_message(format!("{}", line.yellow()));
-            }
+    let on_line = |stream: OutputStream, line: &str| match stream {
+        OutputStream::Stdout => spinner.set_message(line.to_string()),
+        // Display stderr in yellow to differentiate from stdout
+        OutputStream::Stderr => spinner.set_message(format!("{}", line.yellow())),
+    };
+    let (result, report) = match sandbox {
+        Some(sandbox) => {
+            let (result, report) = runner.run_sandboxed(script, sandbox, on_line).await;
+            (result, Some(report))
         }
-    });
-
-    // Wait for both streams to complete
-    let _ = tokio::try_join!(stdout_task, stderr_task)?;
+        None => (runner.run(script, on_line).await, None),
+    };
 
-    let status = child.wait().await?;
     spinner.finish_and_clear();
-
-    if !status.success() {
-        anyhow::bail!("Script failed with exit code: {:?}", status.code());
-    }
-
-    Ok(())
+    (result, report)
 }
@@ -0,0 +1,244 @@
+//! Lint command implementation
+
+use crate::prompt::Exit;
+use anyhow::Result;
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+use xpm_core::{
+    repo::Repositories,
+    script::{
+        lint::{lint, Finding, Rule, Severity},
+        BashScript,
+    },
+    utils::logger::Logger,
+    VERSION,
+};
+
+/// Output format of the lint command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LintFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+/// Run the lint command
+///
+/// Each target is a script, a directory of package directories, or a
+/// repository; without targets every added repository is linted. Fails when
+/// any script has an error.
+pub async fn run(targets: &[String], format: LintFormat) -> Result<()> {
+    let scripts = collect_scripts(targets)?;
+    if scripts.is_empty() {
+        Logger::warning("No package scripts to lint");
+        return Ok(());
+    }
+
+    let mut results = Vec::new();
+    for path in scripts {
+        let findings = lint(&BashScript::new(&path))?;
+        results.push((path, findings));
+    }
+
+    match format {
+        LintFormat::Text => print_text(&results),
+        LintFormat::Json => print_json(&results)?,
+        LintFormat::Sarif => print_sarif(&results)?,
+    }
+
+    let failed = results
+        .iter()
+        .flat_map(|(_, findings)| findings)
+        .any(|f| f.severity == Severity::Error);
+    if failed {
+        return Err(Exit(1).into());
+    }
+
+    Ok(())
+}
+
+/// Expand the lint targets into script paths
+fn collect_scripts(targets: &[String]) -> Result<Vec<PathBuf>> {
+    let roots: Vec<PathBuf> = if targets.is_empty() {
+        Repositories::all_repos()?
+            .into_iter()
+            .filter_map(|r| r.local_path.map(PathBuf::from))
+            .collect()
+    } else {
+        targets
+            .iter()
+            .map(|target| {
+                let path = PathBuf::from(target);
+                if path.exists() {
+                    return Ok(path);
+                }
+                match Repositories::find_repo(target)?.and_then(|r| r.local_path) {
+                    Some(local_path) => Ok(PathBuf::from(local_path)),
+                    None => anyhow::bail!("No such script, directory or repository: {}", target),
+                }
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let mut scripts = Vec::new();
+    for root in roots {
+        if root.is_file() {
+            scripts.push(root);
+        } else {
+            scripts.extend(scripts_in(&root)?);
+        }
+    }
+
+    Ok(scripts)
+}
+
+/// Find the scripts of a directory, laid out as `<name>/<name>.bash` like a
+/// repository, or directly inside it
+fn scripts_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut scripts = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            let script = path.join(format!("{}.bash", name));
+            if script.is_file() {
+                scripts.push(script);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "bash") {
+            scripts.push(path);
+        }
+    }
+
+    scripts.sort();
+    Ok(scripts)
+}
+
+fn print_text(results: &[(PathBuf, Vec<Finding>)]) {
+    let mut errors = 0;
+    let mut warnings = 0;
+
+    for (path, findings) in results.iter().filter(|(_, f)| !f.is_empty()) {
+        println!("{}", path.display().to_string().bold());
+        for finding in findings {
+            let line = finding
+                .line
+                .map(|l| l.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let severity = match finding.severity {
+                Severity::Error => {
+                    errors += 1;
+                    format!("{:<7}", finding.severity).red().to_string()
+                }
+                Severity::Warning => {
+                    warnings += 1;
+                    format!("{:<7}", finding.severity).yellow().to_string()
+                }
+            };
+            println!(
+                "  {:>4}  {}  {}  {}",
+                line.dimmed(),
+                severity,
+                finding.message,
+                format!("[{}]", finding.rule).dimmed()
+            );
+        }
+        println!();
+    }
+
+    let summary = format!(
+        "{} error(s), {} warning(s) in {} script(s)",
+        errors,
+        warnings,
+        results.len()
+    );
+    if errors > 0 {
+        Logger::error(&summary);
+    } else if warnings > 0 {
+        Logger::warning(&summary);
+    } else {
+        Logger::success(&format!("No problems found in {} script(s)", results.len()));
+    }
+}
+
+fn print_json(results: &[(PathBuf, Vec<Finding>)]) -> Result<()> {
+    let result: Vec<_> = results
+        .iter()
+        .map(|(path, findings)| {
+            serde_json::json!({
+                "path": path.display().to_string(),
+                "findings": findings,
+            })
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+/// Print the findings as a SARIF 2.1.0 log, for code scanning tools
+fn print_sarif(results: &[(PathBuf, Vec<Finding>)]) -> Result<()> {
+    let rules: Vec<_> = Rule::ALL
+        .iter()
+        .map(|rule| {
+            serde_json::json!({
+                "id": rule.id(),
+                "shortDescription": { "text": rule.description() },
+                "defaultConfiguration": { "level": sarif_level(rule.severity()) },
+            })
+        })
+        .collect();
+
+    let findings: Vec<_> = results
+        .iter()
+        .flat_map(|(path, findings)| findings.iter().map(move |f| (path, f)))
+        .map(|(path, finding)| {
+            let mut location = serde_json::json!({
+                "artifactLocation": { "uri": path.display().to_string() },
+            });
+            if let Some(line) = finding.line {
+                location["region"] = serde_json::json!({ "startLine": line });
+            }
+
+            serde_json::json!({
+                "ruleId": finding.rule.id(),
+                "ruleIndex": Rule::ALL.iter().position(|r| *r == finding.rule),
+                "level": sarif_level(finding.severity),
+                "message": { "text": finding.message },
+                "locations": [{ "physicalLocation": location }],
+            })
+        })
+        .collect();
+
+    let log = serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "xpm",
+                    "version": VERSION,
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "results": findings,
+        }],
+    });
+    println!("{}", serde_json::to_string_pretty(&log)?);
+    Ok(())
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
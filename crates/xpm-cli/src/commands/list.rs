@@ -0,0 +1,166 @@
+//! List command implementation
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use xpm_core::{
+    db::{Database, InstallReason, Package},
+    native_pm::{detect_native_pm, NativePackage, NativePackageManager},
+    utils::logger::Logger,
+};
+
+/// Run the list command
+///
+/// With `native`, everything installed through the native package manager is
+/// listed above the XPM packages.
+pub async fn run(native: bool, json: bool) -> Result<()> {
+    let db = Database::instance()?;
+
+    // Get installed packages
+    let mut installed = db.get_installed_packages()?;
+    installed.sort_by(|a, b| a.name.cmp(&b.name));
+    let pins = db.get_pins()?;
+
+    let native_packages = if native {
+        let pm = detect_native_pm()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("No native package manager found"))?;
+        let mut packages = pm.list_installed().await?;
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Some((pm.name().to_string(), packages))
+    } else {
+        None
+    };
+
+    if json {
+        let xpm: Vec<_> = installed
+            .iter()
+            .map(|p| {
+                serde_json::json!({
+                    "name": p.name,
+                    "version": p.installed,
+                    "method": p.method,
+                    "channel": p.channel,
+                    "native": p.is_native,
+                    "reason": p.reason.as_str(),
+                    "required_by": p.required_by,
+                    "pinned": pins.get(&p.name)
+                })
+            })
+            .collect();
+
+        let result = match &native_packages {
+            Some((manager, packages)) => serde_json::json!({
+                "xpm": xpm,
+                "native": {
+                    "manager": manager,
+                    "packages": packages.iter().map(|p| {
+                        serde_json::json!({
+                            "name": p.name,
+                            "version": p.version,
+                            "arch": p.arch,
+                            "repo": p.repo,
+                            "tracked": is_tracked(&installed, p)
+                        })
+                    }).collect::<Vec<_>>()
+                }
+            }),
+            None => serde_json::json!(xpm),
+        };
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    // Display order: native (top) → XPM (bottom, closest to prompt)
+    if let Some((manager, packages)) = &native_packages {
+        print_native_packages(manager, packages, &installed);
+    }
+
+    if installed.is_empty() {
+        Logger::info("No packages installed via XPM");
+        return Ok(());
+    }
+
+    println!("{}", "━━━ Installed Packages ━━━".cyan().bold());
+    println!();
+
+    for pkg in &installed {
+        let name = format!("{}", pkg.name.green().bold());
+        let version = format!("{}", pkg.installed.as_deref().unwrap_or("unknown").dimmed());
+        let method = pkg.method.as_deref().unwrap_or("unknown");
+        let channel = pkg.channel.as_deref().unwrap_or("stable");
+
+        let reason = match pkg.reason {
+            InstallReason::Explicit => String::new(),
+            InstallReason::Dependency => format!(" {}", "[dependency]".dimmed()),
+        };
+
+        let pinned = if pins.contains_key(&pkg.name) {
+            format!(" {}", "[pinned]".yellow())
+        } else {
+            String::new()
+        };
+
+        println!("  {} {}{}{}", name, version, reason, pinned);
+        println!(
+            "    Method: {}  Channel: {}",
+            method.cyan(),
+            channel.yellow()
+        );
+        println!();
+    }
+
+    println!(
+        "Total: {} packages installed",
+        installed.len().to_string().green().bold()
+    );
+
+    Ok(())
+}
+
+fn print_native_packages(manager: &str, packages: &[NativePackage], installed: &[Package]) {
+    if packages.is_empty() {
+        Logger::info(&format!("No packages installed via {}", manager));
+        return;
+    }
+
+    println!(
+        "{}",
+        format!("━━━ Native Packages ({}) ━━━", manager)
+            .cyan()
+            .bold()
+    );
+
+    // Align versions in a column
+    let width = packages.iter().map(|p| p.name.len()).max().unwrap_or(0);
+
+    for pkg in packages {
+        let tracked = if is_tracked(installed, pkg) {
+            format!(" {}", "[xpm]".green())
+        } else {
+            String::new()
+        };
+
+        println!(
+            "  {:width$} {}{}",
+            pkg.name.bold(),
+            pkg.version.as_deref().unwrap_or("").dimmed(),
+            tracked,
+            width = width
+        );
+    }
+
+    println!();
+    println!(
+        "Total: {} packages installed via {}",
+        packages.len().to_string().green().bold(),
+        manager.cyan()
+    );
+    println!();
+}
+
+/// Check whether a native package is also tracked in the XPM database
+fn is_tracked(installed: &[Package], native: &NativePackage) -> bool {
+    installed
+        .iter()
+        .any(|p| p.is_native && p.name == native.name)
+}
@@ -1,54 +1,323 @@
 //! Log command implementation
 
 use anyhow::Result;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
 use owo_colors::OwoColorize;
-use xpm_core::{db::Database, utils::logger::Logger};
+use xpm_core::{
+    db::{Database, Operation, Transaction},
+    script::tail,
+    utils::logger::Logger,
+};
+
+/// Number of output lines printed when a script fails
+const FAILURE_TAIL_LINES: usize = 20;
 
 /// Run the log command
-pub async fn run(count: usize) -> Result<()> {
+pub async fn run(
+    count: usize,
+    package: Option<&str>,
+    since: Option<&str>,
+    failed: bool,
+    op: Option<&str>,
+    show: Option<u64>,
+    json: bool,
+) -> Result<()> {
+    if let Some(id) = show {
+        return show_output(id, json);
+    }
+
+    let since = since.map(parse_since).transpose()?;
+    let op = op
+        .map(|s| {
+            Operation::parse(s).ok_or_else(|| {
+                let valid: Vec<_> = Operation::ALL.iter().map(|o| o.as_str()).collect();
+                anyhow::anyhow!(
+                    "Unknown operation '{}'. Valid operations: {}",
+                    s,
+                    valid.join(", ")
+                )
+            })
+        })
+        .transpose()?;
+
     let db = Database::instance()?;
+    let pins = db.get_pins()?;
 
-    // Get installed packages
-    let installed = db.get_installed_packages()?;
+    let entries: Vec<_> = db
+        .get_transactions()?
+        .into_iter()
+        .take_while(|t| since.is_none_or(|s| t.timestamp >= s))
+        .filter(|t| package.is_none_or(|p| t.package.as_deref() == Some(p)))
+        .filter(|t| op.is_none_or(|o| t.operation == o))
+        .filter(|t| !failed || !t.is_success())
+        .take(count)
+        .collect();
 
-    if installed.is_empty() {
-        Logger::info("No packages installed via XPM");
+    if json {
+        let result: Vec<_> = entries
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "id": t.id,
+                    "timestamp": t.timestamp.to_rfc3339(),
+                    "duration_ms": t.duration_ms,
+                    "user": t.user,
+                    "operation": t.operation.as_str(),
+                    "package": t.package,
+                    "repo": t.repo,
+                    "old_version": t.old_version,
+                    "new_version": t.new_version,
+                    "method": t.method,
+                    "channel": t.channel,
+                    "success": t.is_success(),
+                    "exit_code": t.exit_code,
+                    "output_path": t.output_path,
+                    "pinned": t.package.as_ref().is_some_and(|p| pins.contains_key(p)),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&result)?);
         return Ok(());
     }
 
-    println!("{}", "━━━ Installed Packages ━━━".cyan().bold());
+    if entries.is_empty() {
+        Logger::info("No matching operations in the log");
+        return Ok(());
+    }
+
+    println!("{}", "━━━ Operation Log ━━━".cyan().bold());
+    println!();
+
+    // Oldest first so the most recent entry ends up near the prompt
+    for entry in entries.iter().rev() {
+        let pinned = entry.package.as_ref().is_some_and(|p| pins.contains_key(p));
+        print_entry(entry, pinned);
+    }
+
     println!();
 
-    let display_count = count.min(installed.len());
+    Ok(())
+}
 
-    for pkg in installed.iter().take(display_count) {
-        let name = format!("{}", pkg.name.green().bold());
-        let version = format!("{}", pkg.installed.as_deref().unwrap_or("unknown").dimmed());
-        let method = pkg.method.as_deref().unwrap_or("unknown");
-        let channel = pkg.channel.as_deref().unwrap_or("stable");
+/// Print a single journal entry, flagging packages that are currently pinned
+fn print_entry(txn: &Transaction, pinned: bool) {
+    let status = if txn.is_success() {
+        format!("{}", "✓".green())
+    } else {
+        format!("{}", "✗".red())
+    };
+    let target = txn.package.as_deref().or(txn.repo.as_deref()).unwrap_or("");
+    let versions = match (&txn.old_version, &txn.new_version) {
+        (Some(old), Some(new)) => format!(" {} -> {}", old.red(), new.green()),
+        (None, Some(new)) => format!(" {}", new.green()),
+        (Some(old), None) => format!(" {}", old.red()),
+        (None, None) => String::new(),
+    };
+    let pinned = if pinned {
+        format!(" {}", "[pinned]".yellow())
+    } else {
+        String::new()
+    };
 
-        println!("  {} {}", name, version);
-        println!(
-            "    Method: {}  Channel: {}",
-            method.cyan(),
-            channel.yellow()
-        );
-        println!();
+    println!(
+        "  {} {} {} {}{}{} {}",
+        format!("#{}", txn.id).yellow(),
+        txn.timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+            .dimmed(),
+        txn.operation.as_str().cyan(),
+        target.bold(),
+        pinned,
+        versions,
+        status
+    );
+
+    let mut details = Vec::new();
+    if let Some(ms) = txn.duration_ms {
+        details.push(format!("Duration: {}", format_duration(ms)));
+    }
+    if let Some(user) = &txn.user {
+        details.push(format!("User: {}", user));
+    }
+    if !txn.is_success() {
+        let code = txn
+            .exit_code
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "none".to_string());
+        details.push(format!("Exit code: {}", code));
+    }
+    if !details.is_empty() {
+        println!("    {}", details.join("  ").dimmed());
     }
+}
 
-    if installed.len() > display_count {
-        println!(
-            "  ... and {} more. Use {} to see all.",
-            (installed.len() - display_count).to_string().yellow(),
-            format!("xpm log -c {}", installed.len()).cyan()
-        );
+/// Replay the captured script output of a transaction
+fn show_output(id: u64, json: bool) -> Result<()> {
+    let db = Database::instance()?;
+    let txn = db
+        .find_transaction(id)?
+        .ok_or_else(|| anyhow::anyhow!("Transaction #{} not found", id))?;
+    let path = txn
+        .output_path
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("No output was captured for transaction #{}", id))?;
+    let lines = tail(path, usize::MAX)?;
+
+    if json {
+        let result = serde_json::json!({
+            "id": txn.id,
+            "output_path": path,
+            "lines": lines,
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
     }
 
+    let pinned = match &txn.package {
+        Some(name) => db.pinned_version(name)?.is_some(),
+        None => false,
+    };
+    print_entry(&txn, pinned);
     println!();
-    println!(
-        "Total: {} packages installed",
-        installed.len().to_string().green().bold()
-    );
+    for line in &lines {
+        println!("  {}", format_output_line(line));
+    }
 
     Ok(())
 }
+
+/// Print the last lines of a failed transaction's output with a replay hint
+pub fn print_output_tail(txn: &Transaction) {
+    let Some(path) = txn.output_path.as_deref() else {
+        return;
+    };
+    let Ok(lines) = tail(path, FAILURE_TAIL_LINES) else {
+        return;
+    };
+
+    if !lines.is_empty() {
+        eprintln!();
+        for line in &lines {
+            eprintln!("  {}", format_output_line(line));
+        }
+        eprintln!();
+    }
+    Logger::tip(&format!(
+        "See the full output with {}",
+        format!("xpm log --show {}", txn.id).cyan()
+    ));
+}
+
+/// Format a captured output line for display, highlighting stderr
+fn format_output_line(line: &str) -> String {
+    // Lines are stored as "<timestamp> [out|err] <text>"
+    let (stream, text) = line
+        .split_once(' ')
+        .and_then(|(_, rest)| rest.split_once(' '))
+        .unwrap_or(("", line));
+    match stream {
+        "[err]" => format!("{}", text.yellow()),
+        "[out]" => text.to_string(),
+        _ => line.to_string(),
+    }
+}
+
+/// Format a duration in milliseconds for display
+fn format_duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m{}s", ms / 60_000, (ms % 60_000) / 1000)
+    }
+}
+
+/// Parse a `--since` value: a date (YYYY-MM-DD), an RFC 3339 timestamp,
+/// or a relative age such as 30m, 12h, 7d or 2w
+fn parse_since(s: &str) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+
+    if let Ok(ts) = DateTime::parse_from_rfc3339(s) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+        if let Some(local) = Local.from_local_datetime(&midnight).earliest() {
+            return Ok(local.with_timezone(&Utc));
+        }
+    }
+
+    if let Some(unit) = s.chars().last() {
+        if let Ok(n) = s[..s.len() - unit.len_utf8()].parse::<i64>() {
+            let age = match unit {
+                'm' => Some(Duration::minutes(n)),
+                'h' => Some(Duration::hours(n)),
+                'd' => Some(Duration::days(n)),
+                'w' => Some(Duration::weeks(n)),
+                _ => None,
+            };
+            if let Some(age) = age {
+                return Ok(Utc::now() - age);
+            }
+        }
+    }
+
+    anyhow::bail!(
+        "Invalid --since value '{}'. Use a date (2024-01-31), an RFC 3339 timestamp, or an age like 30m, 12h, 7d, 2w",
+        s
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since_relative() {
+        let since = parse_since("7d").unwrap();
+        let expected = Utc::now() - Duration::days(7);
+        assert!((since - expected).num_seconds().abs() < 5);
+
+        assert!(parse_since("12h").unwrap() > parse_since("1d").unwrap());
+    }
+
+    #[test]
+    fn test_parse_since_absolute() {
+        let since = parse_since("2024-01-31T12:00:00Z").unwrap();
+        assert_eq!(since.to_rfc3339(), "2024-01-31T12:00:00+00:00");
+
+        let date = parse_since("2024-01-31").unwrap();
+        assert_eq!(
+            date.with_timezone(&Local).date_naive(),
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_since_invalid() {
+        assert!(parse_since("yesterday").is_err());
+        assert!(parse_since("7x").is_err());
+        assert!(parse_since("").is_err());
+    }
+
+    #[test]
+    fn test_format_output_line() {
+        assert_eq!(
+            format_output_line("2024-01-31T12:00:00.000Z [out] Installing..."),
+            "Installing..."
+        );
+        assert_eq!(format_output_line("2024-01-31T12:00:00.000Z [out] "), "");
+        assert_eq!(format_output_line("not a log line"), "not a log line");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(250), "250ms");
+        assert_eq!(format_duration(3200), "3.2s");
+        assert_eq!(format_duration(125_000), "2m5s");
+    }
+}
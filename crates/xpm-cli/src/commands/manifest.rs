@@ -0,0 +1,257 @@
+//! Apply, export and lock command implementations
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use xpm_core::{
+    db::{Database, InstallReason, Repo},
+    manifest::{ApplyPlan, Lockfile, Manifest},
+    native_pm::NativeMode,
+    repo::Repositories,
+    utils::logger::Logger,
+};
+
+use crate::commands::{install, remove, repo};
+use crate::prompt;
+
+/// Run the apply command
+///
+/// Brings the machine in line with the manifest at `path`: adds missing
+/// repositories, updates pins, installs what is missing or set up differently
+/// and removes explicitly installed packages the manifest does not list.
+pub async fn apply(
+    path: &str,
+    dry_run: bool,
+    yes: bool,
+    insecure: bool,
+    jobs: usize,
+) -> Result<()> {
+    let manifest = Manifest::load(Path::new(path))?;
+    for (name, spec) in &manifest.packages {
+        if let Some(from) = &spec.from {
+            from.parse::<NativeMode>()
+                .map_err(|e| anyhow::anyhow!("{} in {}: {}", name, path, e))?;
+        }
+    }
+
+    let db = Database::instance()?;
+    let plan = manifest.plan(
+        &db.get_installed_packages()?,
+        &db.get_pins()?,
+        &Repositories::all_repos()?,
+    );
+
+    if plan.is_empty() {
+        Logger::success(&format!("Already up to date with {}", path.cyan()));
+        return Ok(());
+    }
+
+    print_plan(&plan);
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !yes {
+        if !prompt::is_interactive() {
+            anyhow::bail!("Not running interactively, pass --yes to apply without confirmation");
+        }
+        if !prompt::confirm("Apply these changes?", true)? {
+            Logger::info("Apply cancelled");
+            return Ok(());
+        }
+    }
+
+    let mut errors = Vec::new();
+
+    for url in &plan.add_repos {
+        if let Err(e) = repo::add(Repo::new(url.clone())).await {
+            errors.push(format!("repo {}: {}", url, e));
+        }
+    }
+
+    let db = Database::instance()?;
+    for name in &plan.unpin {
+        db.unpin_package(name)?;
+        Logger::success(&format!("{} unpinned", name.green().bold()));
+    }
+    // Pins go first so installs refuse versions the manifest does not allow
+    for (name, version) in &plan.pin {
+        db.pin_package(name, version)?;
+        Logger::success(&format!(
+            "{} pinned at {}",
+            name.green().bold(),
+            version.yellow()
+        ));
+    }
+
+    // Packages installed the same way share a single batch
+    let mut batches: BTreeMap<_, Vec<String>> = BTreeMap::new();
+    for (name, spec) in &plan.install {
+        batches
+            .entry((spec.method.clone(), spec.channel.clone(), spec.from.clone()))
+            .or_default()
+            .push(name.clone());
+    }
+
+    for ((method, channel, from), names) in batches {
+        let native_mode = match &from {
+            Some(from) => from.parse().map_err(anyhow::Error::msg)?,
+            None => NativeMode::Auto,
+        };
+        let options = install::ScriptOptions {
+            method: method.as_deref().unwrap_or("auto"),
+            channel: channel.as_deref(),
+            insecure,
+            yes,
+            ..Default::default()
+        };
+        let result = install::run_many(&names, &options, &native_mode, jobs).await;
+        if let Err(e) = result {
+            errors.push(e.to_string());
+        }
+    }
+
+    for name in &plan.unmark {
+        if let Some(mut pkg) = db.find_package_by_name(name)? {
+            pkg.reason = InstallReason::Dependency;
+            db.upsert_package(pkg)?;
+            Logger::info(&format!("{} kept as a dependency", name.cyan()));
+        }
+    }
+
+    for name in &plan.mark_explicit {
+        if let Some(mut pkg) = db.find_package_by_name(name)? {
+            pkg.reason = InstallReason::Explicit;
+            db.upsert_package(pkg)?;
+            Logger::info(&format!("{} marked as explicitly installed", name.cyan()));
+        }
+    }
+
+    if !plan.remove.is_empty() {
+        if let Err(e) = remove::run_many(&plan.remove, false, yes, jobs).await {
+            errors.push(e.to_string());
+        }
+    }
+
+    if !errors.is_empty() {
+        anyhow::bail!("Could not fully apply {}: {}", path, errors.join("; "));
+    }
+
+    Logger::success(&format!("Applied {}", path.cyan()));
+    Ok(())
+}
+
+fn print_plan(plan: &ApplyPlan) {
+    println!("{}", "━━━ Changes ━━━".cyan().bold());
+    println!();
+    for url in &plan.add_repos {
+        println!("  {} repo {}", "+".green(), url.cyan());
+    }
+    for name in &plan.unpin {
+        println!("  {} unpin {}", "~".yellow(), name.bold());
+    }
+    for (name, version) in &plan.pin {
+        println!(
+            "  {} pin {} at {}",
+            "~".yellow(),
+            name.bold(),
+            version.yellow()
+        );
+    }
+    for (name, spec) in &plan.install {
+        let details: Vec<String> = [
+            spec.from.as_ref().map(|f| format!("from {}", f)),
+            spec.method.as_ref().map(|m| format!("method {}", m)),
+            spec.channel.as_ref().map(|c| format!("channel {}", c)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let details = if details.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", format!("({})", details.join(", ")).dimmed())
+        };
+        println!(
+            "  {} install {}{}",
+            "+".green(),
+            name.green().bold(),
+            details
+        );
+    }
+    for name in &plan.remove {
+        println!("  {} remove {}", "-".red(), name.red().bold());
+    }
+    for name in &plan.unmark {
+        println!(
+            "  {} keep {} {}",
+            "~".yellow(),
+            name.bold(),
+            "(needed by other packages)".dimmed()
+        );
+    }
+    for name in &plan.mark_explicit {
+        println!(
+            "  {} keep {} {}",
+            "~".yellow(),
+            name.bold(),
+            "(no longer only a dependency)".dimmed()
+        );
+    }
+    println!();
+}
+
+/// Run the export command
+///
+/// Writes a manifest describing this machine to `path`, or to stdout.
+pub async fn export(path: Option<&str>) -> Result<()> {
+    let db = Database::instance()?;
+    let manifest = Manifest::from_installed(
+        &db.get_installed_packages()?,
+        &db.get_pins()?,
+        &Repositories::all_repos()?,
+    );
+    let content = manifest.to_toml()?;
+
+    match path {
+        None | Some("-") => print!("{}", content),
+        Some(path) => {
+            std::fs::write(path, content)?;
+            Logger::success(&format!(
+                "Exported {} packages to {}",
+                manifest.packages.len().to_string().green(),
+                path.cyan()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the lock command
+///
+/// Writes the exact source of every installed package to `path` so
+/// `xpm install --locked` can reproduce it elsewhere.
+pub async fn lock(path: &str) -> Result<()> {
+    let db = Database::instance()?;
+    let lockfile =
+        Lockfile::from_installed(&db.get_installed_packages()?, &Repositories::all_repos()?);
+
+    for entry in lockfile.packages.iter().filter(|p| !p.is_reproducible()) {
+        Logger::warning(&format!(
+            "{} was installed before its source was recorded, reinstall it to lock it",
+            entry.name.yellow()
+        ));
+    }
+
+    std::fs::write(path, lockfile.to_toml()?)?;
+    Logger::success(&format!(
+        "Locked {} packages in {}",
+        lockfile.packages.len().to_string().green(),
+        path.cyan()
+    ));
+
+    Ok(())
+}
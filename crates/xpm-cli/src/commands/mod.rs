@@ -1,15 +1,28 @@
 //! Command implementations for XPM CLI
 
+pub mod autoremove;
 pub mod check;
 pub mod checksum;
 pub mod file;
+pub mod files;
 pub mod get;
+pub mod history;
+pub mod info;
 pub mod install;
+pub mod lint;
+pub mod list;
 pub mod log;
 pub mod make;
+pub mod manifest;
+pub mod pin;
+pub mod prefer;
+pub mod provides;
 pub mod refresh;
 pub mod remove;
 pub mod repo;
+pub mod review;
+pub mod rollback;
 pub mod search;
 pub mod shortcut;
+pub mod summary;
 pub mod upgrade;
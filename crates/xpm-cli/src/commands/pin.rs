@@ -0,0 +1,73 @@
+//! Pin and unpin command implementations
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use xpm_core::{db::Database, utils::logger::Logger};
+
+/// Run the pin command
+///
+/// Without a version the package is held at its installed version. Without a
+/// package the current pins are listed.
+pub async fn run(package: Option<&str>, version: Option<&str>, json: bool) -> Result<()> {
+    let db = Database::instance()?;
+
+    let Some(package) = package else {
+        return list(db, json);
+    };
+
+    let version = match version {
+        Some(v) => v.to_string(),
+        None => db
+            .find_package_by_name(package)?
+            .and_then(|p| p.installed)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Package '{}' is not installed, pass a version to pin it",
+                    package
+                )
+            })?,
+    };
+
+    db.pin_package(package, &version)?;
+    Logger::success(&format!(
+        "{} pinned at {}",
+        package.green().bold(),
+        version.yellow()
+    ));
+    Ok(())
+}
+
+/// Run the unpin command
+pub async fn unpin(package: &str) -> Result<()> {
+    let db = Database::instance()?;
+
+    if !db.unpin_package(package)? {
+        anyhow::bail!("Package '{}' is not pinned", package);
+    }
+
+    Logger::success(&format!("{} unpinned", package.green().bold()));
+    Ok(())
+}
+
+fn list(db: &Database, json: bool) -> Result<()> {
+    let pins = db.get_pins()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&pins)?);
+        return Ok(());
+    }
+
+    if pins.is_empty() {
+        Logger::info("No packages are pinned");
+        return Ok(());
+    }
+
+    println!("{}", "━━━ Pinned Packages ━━━".cyan().bold());
+    println!();
+    for (name, version) in &pins {
+        println!("  {} {}", name.green().bold(), version.yellow());
+    }
+    println!();
+
+    Ok(())
+}
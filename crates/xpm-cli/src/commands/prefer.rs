@@ -0,0 +1,69 @@
+//! Prefer command implementation
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use xpm_core::{db::Database, native_pm::NATIVE_BACKENDS, utils::logger::Logger};
+
+/// Run the prefer command
+///
+/// Without backends the current preference is shown. Backends left out of the
+/// preference are still tried, after the preferred ones.
+pub async fn run(backends: &[String], reset: bool, json: bool) -> Result<()> {
+    let db = Database::instance()?;
+
+    if reset {
+        db.set_backend_preference(&[])?;
+        Logger::success("Backend preference cleared");
+        return Ok(());
+    }
+
+    if backends.is_empty() {
+        return show(db, json);
+    }
+
+    let mut preference: Vec<String> = Vec::new();
+    for backend in backends {
+        let backend = backend.trim().to_lowercase();
+        if backend.is_empty() || preference.contains(&backend) {
+            continue;
+        }
+        if !NATIVE_BACKENDS.contains(&backend.as_str()) {
+            anyhow::bail!(
+                "Unknown backend '{}' (expected one of: {})",
+                backend,
+                NATIVE_BACKENDS.join(", ")
+            );
+        }
+        preference.push(backend);
+    }
+
+    db.set_backend_preference(&preference)?;
+    Logger::success(&format!(
+        "Installs now try {}",
+        preference.join(" → ").cyan()
+    ));
+    Ok(())
+}
+
+fn show(db: &Database, json: bool) -> Result<()> {
+    let preference = db.get_backend_preference()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&preference)?);
+        return Ok(());
+    }
+
+    if preference.is_empty() {
+        Logger::info("No backend preference set, installs use the detected order");
+        return Ok(());
+    }
+
+    println!("{}", "━━━ Backend Preference ━━━".cyan().bold());
+    println!();
+    for (i, backend) in preference.iter().enumerate() {
+        println!("  {}. {}", i + 1, backend.green().bold());
+    }
+    println!();
+
+    Ok(())
+}
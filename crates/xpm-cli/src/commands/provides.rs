@@ -0,0 +1,134 @@
+//! Provides and shell-hook command implementations
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use xpm_core::{
+    db::{Database, Package},
+    utils::logger::Logger,
+};
+
+/// Run the provides command
+///
+/// With `hint`, prints a single line for the command-not-found hook and stays
+/// silent when nothing provides the command or the database is unavailable.
+pub async fn run(command: &str, hint: bool, json: bool) -> Result<()> {
+    if hint {
+        if let Ok(providers) = Database::instance().and_then(|db| db.find_providers(command)) {
+            print_hint(command, &providers);
+        }
+        return Ok(());
+    }
+
+    let db = Database::instance()?;
+    let providers = db.find_providers(command)?;
+
+    if json {
+        let result: Vec<_> = providers
+            .iter()
+            .map(|p| {
+                serde_json::json!({
+                    "name": p.name,
+                    "version": p.version,
+                    "description": p.desc,
+                    "installed": p.is_installed(),
+                    "provides": p.provides
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    if providers.is_empty() {
+        Logger::warning(&format!("No package provides '{}'", command));
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!("━━━ Packages providing {} ━━━", command)
+            .cyan()
+            .bold()
+    );
+    println!();
+    for pkg in &providers {
+        let installed = if pkg.is_installed() {
+            format!("{}", " [installed]".green())
+        } else {
+            String::new()
+        };
+        println!(
+            "  {} {}{}",
+            pkg.name.green().bold(),
+            pkg.version.as_deref().unwrap_or("").dimmed(),
+            installed
+        );
+        if let Some(desc) = &pkg.desc {
+            println!("    {}", desc.dimmed());
+        }
+    }
+    println!();
+
+    if let Some(pkg) = providers.iter().find(|p| !p.is_installed()) {
+        Logger::tip(&format!("Install it with: xpm install {}", pkg.name));
+    }
+
+    Ok(())
+}
+
+fn print_hint(command: &str, providers: &[Package]) {
+    match providers {
+        [] => {}
+        [pkg] => println!(
+            "{} is provided by {}, install it with: {}",
+            command.bold(),
+            pkg.name.green().bold(),
+            format!("xpm install {}", pkg.name).cyan()
+        ),
+        _ => {
+            let names: Vec<_> = providers.iter().map(|p| p.name.as_str()).collect();
+            println!(
+                "{} is provided by {}, install one with: {}",
+                command.bold(),
+                names.join(", ").green().bold(),
+                "xpm install <package>".cyan()
+            );
+        }
+    }
+}
+
+/// Print the command-not-found hook for a shell
+pub fn shell_hook(shell: &str) -> Result<()> {
+    // Call xpm by path so a missing xpm cannot send the hook into a loop
+    let xpm = std::env::current_exe()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "xpm".to_string());
+    let xpm = format!("'{}'", xpm.replace('\'', r"'\''"));
+
+    let hook = match shell {
+        "bash" => format!(
+            r#"command_not_found_handle() {{
+    [ -x {xpm} ] && {xpm} provides --hint "$1" >&2
+    printf 'bash: %s: command not found\n' "$1" >&2
+    return 127
+}}"#
+        ),
+        "zsh" => format!(
+            r#"command_not_found_handler() {{
+    [ -x {xpm} ] && {xpm} provides --hint "$1" >&2
+    printf 'zsh: command not found: %s\n' "$1" >&2
+    return 127
+}}"#
+        ),
+        "fish" => format!(
+            r#"function fish_command_not_found
+    test -x {xpm}; and {xpm} provides --hint $argv[1] >&2
+    __fish_default_command_not_found_handler $argv
+end"#
+        ),
+        other => anyhow::bail!("Unsupported shell '{}' (expected bash, zsh or fish)", other),
+    };
+
+    println!("{}", hook);
+    Ok(())
+}
@@ -1,22 +1,30 @@
 //! Remove command implementation
 
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use anyhow::{Context, Result};
+use futures::{stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use owo_colors::OwoColorize;
-use std::process::Stdio;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
 use xpm_core::{
-    db::Database,
-    native_pm::{detect_native_pm, NativePackageManager},
-    os::{get_os_info, OsType},
-    script::BashScript,
+    db::{Database, Operation, Package, PackageFiles, Transaction},
+    native_pm::{native_pm_for, NativePM, NativePackageManager},
+    os::{get_os_info, OsType, XpmDirs},
+    script::{BashScript, OutputStream, ScriptError, ScriptRunner},
     utils::logger::Logger,
 };
 
+use crate::commands::log;
+use crate::commands::summary::{self, Outcome};
+use crate::prompt;
+
 /// Run the remove command
-pub async fn run(package: &str) -> Result<()> {
+///
+/// Packages without a removal script have their recorded files deleted,
+/// which is confirmed first unless `yes` is set.
+pub async fn run(package: &str, force: bool, yes: bool) -> Result<()> {
     Logger::info(&format!("Removing {}...", package.red().bold()));
 
     let db = Database::instance()?;
@@ -27,58 +35,528 @@ pub async fn run(package: &str) -> Result<()> {
             Logger::warning(&format!("{} is not installed via XPM", package));
 
             // Try native PM
-            return try_native_remove(package).await;
+            return try_native_remove(package, pkg.method.as_deref()).await;
         }
 
-        // Get removal method
-        let method = pkg.method.as_deref().unwrap_or("any");
+        let dependents = db.find_dependents(package)?;
+        if !dependents.is_empty() {
+            let names: Vec<_> = dependents.iter().map(|p| p.name.as_str()).collect();
+            if !force {
+                anyhow::bail!(
+                    "{} is required by: {}. Use --force to remove it anyway",
+                    package,
+                    names.join(", ")
+                );
+            }
+            Logger::warning(&format!(
+                "Removing {} anyway; these packages may stop working: {}",
+                package,
+                names.join(", ").yellow()
+            ));
+        }
 
-        if let Some(script_path) = &pkg.script {
-            let script = BashScript::new(script_path);
+        let all_files = db.get_all_package_files()?;
+        let recorded = RecordedFiles::for_removal(&pkg, &all_files, &[package]);
+        if let Some(recorded) = &recorded {
+            confirm_deletion(&pkg.name, recorded, yes)?;
+        }
+        let runner = ScriptRunner::for_operation(Operation::Remove.as_str(), &pkg.name)?;
+        let result = remove_installed(&pkg, recorded.as_ref(), &runner, None).await;
+        return finish_removal(pkg, &runner, result);
+    }
 
-            if script.has_function(&format!("remove_{}", method)) {
-                let remove_script = build_remove_script(script_path, method)?;
-                run_script(&remove_script, &pkg.name).await?;
-            } else if script.has_function("remove_any") {
-                let remove_script = build_remove_script(script_path, "any")?;
-                run_script(&remove_script, &pkg.name).await?;
-            } else {
-                Logger::warning("No removal script found, trying native package manager...");
-                try_native_remove(package).await?;
+    // Try native package manager
+    try_native_remove(package, None).await
+}
+
+/// Remove several packages in one go
+///
+/// Package scripts run up to `jobs` at a time and native packages are removed
+/// with one call per package manager. Every package gets a line in the
+/// summary printed at the end.
+pub async fn run_many(packages: &[String], force: bool, yes: bool, jobs: usize) -> Result<()> {
+    let mut names: Vec<&str> = Vec::new();
+    for package in packages {
+        if !names.contains(&package.as_str()) {
+            names.push(package);
+        }
+    }
+
+    if let [package] = names[..] {
+        return run(package, force, yes).await;
+    }
+
+    Logger::info(&format!("Removing {}...", names.join(", ").red().bold()));
+
+    let db = Database::instance()?;
+    let mut outcomes = HashMap::new();
+    let mut scripts = Vec::new();
+    let mut native = Vec::new();
+
+    for &name in &names {
+        let pkg = match db.find_package_by_name(name)? {
+            Some(pkg) if pkg.is_installed() => pkg,
+            // Not installed through XPM, may still be a native package
+            pkg => {
+                native.push(NativeRemoval {
+                    name,
+                    method: pkg.and_then(|p| p.method),
+                    tracked: None,
+                });
+                continue;
+            }
+        };
+
+        // Packages removed together don't hold each other back
+        let dependents: Vec<_> = db
+            .find_dependents(name)?
+            .into_iter()
+            .filter(|p| !names.contains(&p.name.as_str()))
+            .map(|p| p.name)
+            .collect();
+        if !dependents.is_empty() {
+            if !force {
+                let error = format!(
+                    "{} is required by: {}. Use --force to remove it anyway",
+                    name,
+                    dependents.join(", ")
+                );
+                outcomes.insert(name.to_string(), Err(error));
+                continue;
+            }
+            Logger::warning(&format!(
+                "Removing {} anyway; these packages may stop working: {}",
+                name,
+                dependents.join(", ").yellow()
+            ));
+        }
+
+        if pkg.script.is_none() && pkg.is_native {
+            native.push(NativeRemoval {
+                name,
+                method: pkg.method.clone(),
+                tracked: Some(pkg),
+            });
+            continue;
+        }
+
+        match ScriptRunner::for_operation(Operation::Remove.as_str(), &pkg.name) {
+            Ok(runner) => scripts.push((pkg, runner)),
+            Err(e) => {
+                outcomes.insert(name.to_string(), Err(format!("{:#}", e)));
+            }
+        }
+    }
+
+    // Recorded files are read and confirmed here, the scripts run side by
+    // side, cannot prompt and must leave the database to nested installs
+    let all_files = db.get_all_package_files()?;
+    let removed: Vec<String> = scripts.iter().map(|(pkg, _)| pkg.name.clone()).collect();
+    let removed: Vec<&str> = removed.iter().map(String::as_str).collect();
+    let mut confirmed = Vec::new();
+    for (pkg, runner) in scripts {
+        let recorded = RecordedFiles::for_removal(&pkg, &all_files, &removed);
+        match recorded.map_or(Ok(()), |recorded| {
+            confirm_deletion(&pkg.name, &recorded, yes)
+        }) {
+            Ok(()) => confirmed.push((pkg, runner)),
+            Err(e) => {
+                outcomes.insert(pkg.name.clone(), Err(format!("{:#}", e)));
+            }
+        }
+    }
+
+    // Split again without the declined packages, which only keeps more files
+    let removed: Vec<&str> = confirmed.iter().map(|(pkg, _)| pkg.name.as_str()).collect();
+    let scripts: Vec<_> = confirmed
+        .iter()
+        .map(|(pkg, runner)| {
+            let recorded = RecordedFiles::for_removal(pkg, &all_files, &removed);
+            (pkg, runner, recorded)
+        })
+        .collect();
+
+    if !native.is_empty() {
+        outcomes.extend(remove_native_batch(native).await);
+    }
+
+    let progress = MultiProgress::new();
+    let results: Vec<_> = stream::iter(scripts.iter().map(|(pkg, runner, recorded)| {
+        remove_installed(pkg, recorded.as_ref(), runner, Some(&progress))
+    }))
+    .buffered(jobs.max(1))
+    .collect()
+    .await;
+
+    // Database updates happen one at a time, once every script is done
+    for ((pkg, runner), result) in confirmed.into_iter().zip(results) {
+        let name = pkg.name.clone();
+        let outcome = finish_removal(pkg, &runner, result).map_err(|e| format!("{:#}", e));
+        outcomes.insert(name, outcome);
+    }
+
+    summary::print("remove", &names, &outcomes)
+}
+
+/// A package to remove through a native package manager
+struct NativeRemoval<'a> {
+    name: &'a str,
+    /// Package manager it was installed with, if known
+    method: Option<String>,
+    /// XPM record, when the package is tracked
+    tracked: Option<Package>,
+}
+
+/// Remove native packages, one call per package manager
+async fn remove_native_batch(packages: Vec<NativeRemoval<'_>>) -> HashMap<String, Outcome> {
+    let mut outcomes = HashMap::new();
+
+    let mut batches: Vec<(NativePM, Vec<NativeRemoval>)> = Vec::new();
+    for removal in packages {
+        let name = removal.name;
+        let pm = match native_pm_for(removal.method.as_deref()).await {
+            Some(pm) if pm.is_installed(name).await.unwrap_or(false) => pm,
+            _ => {
+                let error = format!("Package '{}' is not installed", name);
+                outcomes.insert(name.to_string(), Err(error));
+                continue;
             }
+        };
+
+        match batches.iter_mut().find(|(p, _)| p.name() == pm.name()) {
+            Some((_, removals)) => removals.push(removal),
+            None => batches.push((pm, vec![removal])),
+        }
+    }
+
+    for (pm, removals) in batches {
+        let names: Vec<String> = removals.iter().map(|r| r.name.to_string()).collect();
+        Logger::info(&format!(
+            "Removing {} via {}...",
+            names.join(", ").red().bold(),
+            pm.name().cyan()
+        ));
+
+        // One call for the whole batch, so it succeeds or fails as a whole
+        let result = pm.remove_many(&names).await.map_err(|e| format!("{:#}", e));
+        for NativeRemoval { name, tracked, .. } in removals {
+            let outcome = match tracked {
+                Some(pkg) => record_native_removal(pkg, &result),
+                None => result.clone(),
+            };
+            if outcome.is_ok() {
+                Logger::success(&format!("{} removed successfully", name.green()));
+            }
+            outcomes.insert(name.to_string(), outcome);
+        }
+    }
+
+    outcomes
+}
+
+/// Record the removal of a tracked native package and clear its install state
+fn record_native_removal(pkg: Package, result: &Outcome) -> Outcome {
+    let record = || -> Result<()> {
+        let db = Database::instance()?;
+
+        let mut txn = Transaction::from_package(Operation::Remove, &pkg);
+        txn.method = pkg.method.clone();
+        txn.channel = pkg.channel.clone();
+        txn.finish(if result.is_ok() { Some(0) } else { None });
+        db.record_transaction(txn)?;
+
+        if result.is_ok() {
+            let mut updated_pkg = pkg.clone();
+            updated_pkg.clear_install_state();
+            db.upsert_package(updated_pkg)?;
+        }
+        Ok(())
+    };
+
+    record().map_err(|e| format!("{:#}", e))?;
+    result.clone()
+}
+
+/// Record a removal and clear the install state of the package
+fn finish_removal(pkg: Package, runner: &ScriptRunner, result: Result<()>) -> Result<()> {
+    let db = Database::instance()?;
+
+    let mut txn = Transaction::from_package(Operation::Remove, &pkg);
+    txn.method = pkg.method.clone();
+    txn.channel = pkg.channel.clone();
+    // Native removals don't go through the runner and leave no log behind
+    txn.output_path = runner
+        .log_path()
+        .filter(|p| p.exists())
+        .map(|p| p.display().to_string());
+    txn.finish(ScriptError::exit_code_of(&result));
+    let txn = db.record_transaction(txn)?;
+    if result.is_err() {
+        log::print_output_tail(&txn);
+    }
+    result?;
+
+    // Update database
+    let name = pkg.name.clone();
+    let mut updated_pkg = pkg;
+    updated_pkg.clear_install_state();
+    db.upsert_package(updated_pkg)?;
+    db.delete_package_files(&name)?;
+
+    Logger::success(&format!("{} removed successfully", name.green()));
+    Ok(())
+}
+
+/// Run the removal script of an installed package, without touching the database
+///
+/// `recorded` holds the files to delete when the script has no removal
+/// function for the install.
+async fn remove_installed(
+    pkg: &Package,
+    recorded: Option<&RecordedFiles>,
+    runner: &ScriptRunner,
+    progress: Option<&MultiProgress>,
+) -> Result<()> {
+    // Get removal method
+    let method = pkg.method.as_deref().unwrap_or("any");
+
+    if let Some(script_path) = &pkg.script {
+        let script = BashScript::new(script_path);
+
+        if let Some(method) = removal_method(&script, method) {
+            let remove_script = build_remove_script(script_path, method)?;
+            run_script(&remove_script, &pkg.name, runner, progress).await?;
+        } else if let Some(recorded) = recorded {
+            remove_recorded_files(&pkg.name, recorded, runner, progress).await?;
+        } else {
+            Logger::warning("No removal script found, trying native package manager...");
+            try_native_remove(&pkg.name, pkg.method.as_deref()).await?;
+        }
 
-            // Validate removal: validation should FAIL after successful removal
-            if script.has_function("validate") {
-                let validate_script = format!(r#"source "{}" && validate"#, script_path);
-                match run_script(&validate_script, "validation").await {
-                    Ok(_) => {
-                        // Validation passed = package still exists = warning
-                        Logger::warning("Package may not have been fully removed");
-                    }
-                    Err(_) => {
-                        // Validation failed = package was removed = success
-                    }
+        // Validate removal: validation should FAIL after successful removal
+        if script.has_function("validate") {
+            let validate_script = format!(r#"source "{}" && validate"#, script_path);
+            let name = match progress {
+                Some(_) => format!("{} validation", pkg.name),
+                None => "validation".to_string(),
+            };
+            match run_script(&validate_script, &name, runner, progress).await {
+                Ok(_) => {
+                    // Validation passed = package still exists = warning
+                    Logger::warning("Package may not have been fully removed");
                 }
+                Err(_) => {
+                    // Validation failed = package was removed = success
+                }
+            }
+        }
+    } else if pkg.is_native {
+        try_native_remove(&pkg.name, pkg.method.as_deref()).await?;
+    }
+
+    Ok(())
+}
+
+/// Get the function of a package script that removes the package
+fn removal_method<'a>(script: &BashScript, method: &'a str) -> Option<&'a str> {
+    if script.has_function(&format!("remove_{}", method)) {
+        Some(method)
+    } else if script.has_function("remove_any") {
+        Some("any")
+    } else {
+        None
+    }
+}
+
+/// Recorded files of a package without a removal script, deleted instead
+#[derive(Debug, Clone, Default, PartialEq)]
+struct RecordedFiles {
+    /// Files to delete
+    owned: Vec<String>,
+    /// Files left to the other packages that recorded them too
+    kept: Vec<String>,
+}
+
+impl RecordedFiles {
+    /// Get the recorded files to delete when removing a package, if its
+    /// script has no removal function for the install
+    ///
+    /// `removed` lists the packages removed in the same run. Files another
+    /// package recorded too are kept, unless it is removed as well; those go
+    /// to the first of the packages in `removed`, so only one deletes them.
+    fn for_removal(pkg: &Package, all: &[PackageFiles], removed: &[&str]) -> Option<Self> {
+        let script = BashScript::new(pkg.script.as_ref()?);
+        let method = pkg.method.as_deref().unwrap_or("any");
+        if removal_method(&script, method).is_some() {
+            return None;
+        }
+        let recorded = all.iter().find(|recorded| recorded.name == pkg.name)?;
+        Some(Self::split(&pkg.name, &recorded.files, all, removed))
+    }
+
+    fn split(name: &str, files: &[String], all: &[PackageFiles], removed: &[&str]) -> Self {
+        let mut split = Self::default();
+        for file in files {
+            let owners: Vec<&str> = all
+                .iter()
+                .filter(|recorded| recorded.files.contains(file))
+                .map(|recorded| recorded.name.as_str())
+                .collect();
+            let owner = removed.iter().find(|removed| owners.contains(removed));
+            let all_removed = owners
+                .iter()
+                .all(|owner| *owner == name || removed.contains(owner));
+            if all_removed && owner.is_none_or(|owner| *owner == name) {
+                split.owned.push(file.clone());
+            } else {
+                split.kept.push(file.clone());
             }
         }
+        split
+    }
+}
 
-        // Update database
-        let mut updated_pkg = pkg.clone();
-        updated_pkg.installed = None;
-        updated_pkg.method = None;
-        updated_pkg.channel = None;
-        db.upsert_package(updated_pkg)?;
+/// Show the recorded files a removal deletes and ask before deleting them
+fn confirm_deletion(name: &str, recorded: &RecordedFiles, yes: bool) -> Result<()> {
+    let existing: Vec<&String> = recorded
+        .owned
+        .iter()
+        .filter(|file| Path::new(file).symlink_metadata().is_ok())
+        .collect();
+    if existing.is_empty() {
+        return Ok(());
+    }
 
-        Logger::success(&format!("{} removed successfully", package.green()));
+    Logger::info(&format!(
+        "{} has no removal script, these recorded files will be deleted:",
+        name.cyan()
+    ));
+    for file in &existing {
+        println!("  {}", file);
+    }
+
+    if yes {
         return Ok(());
     }
+    if !prompt::is_interactive() {
+        anyhow::bail!(
+            "{} has no removal script, pass --yes to delete its recorded files",
+            name
+        );
+    }
+    if !prompt::confirm("Delete them?", false)? {
+        anyhow::bail!("Kept the files of {}", name);
+    }
+    Ok(())
+}
 
-    // Try native package manager
-    try_native_remove(package).await
+/// Check whether a path is in a system directory files may be deleted from
+/// with sudo
+fn is_system_path(path: &Path) -> bool {
+    cfg!(unix) && (path.starts_with("/opt") || path.starts_with("/usr/local"))
+}
+
+/// Delete the files recorded when a package was installed
+///
+/// Files also recorded for other packages are kept. Files the user cannot
+/// delete are deleted with sudo when they are in `/opt` or `/usr/local`, and
+/// left in place elsewhere. Then the directories left empty are removed, up
+/// to the watched directories themselves.
+async fn remove_recorded_files(
+    name: &str,
+    recorded: &RecordedFiles,
+    runner: &ScriptRunner,
+    progress: Option<&MultiProgress>,
+) -> Result<()> {
+    let RecordedFiles { owned, kept } = recorded;
+
+    Logger::info(&format!(
+        "No removal script found, deleting the {} file(s) recorded for {}...",
+        owned.len(),
+        name
+    ));
+    if !kept.is_empty() {
+        Logger::info(&format!(
+            "Keeping {} file(s) also recorded for other packages",
+            kept.len()
+        ));
+    }
+
+    let mut denied = Vec::new();
+    for file in owned {
+        match std::fs::remove_file(file) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => denied.push(file.as_str()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to delete {}", file)),
+        }
+    }
+
+    let dirs = emptied_dirs(owned.iter().map(Path::new), &XpmDirs::watched_dirs()?);
+    for dir in &dirs {
+        let _ = std::fs::remove_dir(dir);
+    }
+
+    let (denied, skipped): (Vec<&str>, Vec<&str>) = denied
+        .into_iter()
+        .partition(|file| is_system_path(Path::new(file)));
+    for file in &skipped {
+        Logger::warning(&format!("Not allowed to delete {}, left in place", file));
+    }
+
+    if !denied.is_empty() {
+        let mut script = format!("set -e\n{} rm -f --", sudo_command());
+        for file in &denied {
+            script.push(' ');
+            script.push_str(&shell_quote(file));
+        }
+        // Directories that are not empty, or already gone, are left alone
+        for dir in dirs
+            .iter()
+            .filter(|dir| dir.exists() && is_system_path(dir))
+        {
+            script.push_str(&format!(
+                "\n{} rmdir -- {} 2>/dev/null || true",
+                sudo_command(),
+                shell_quote(&dir.display().to_string())
+            ));
+        }
+        run_script(&script, name, runner, progress).await?;
+    }
+
+    Ok(())
+}
+
+/// Get the directories of some files that may be left empty, deepest first
+///
+/// Only directories inside the watched ones count, never those themselves.
+fn emptied_dirs<'a>(files: impl Iterator<Item = &'a Path>, watched: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for file in files {
+        for dir in file.ancestors().skip(1) {
+            let inside = watched
+                .iter()
+                .any(|root| dir.starts_with(root) && dir != root);
+            if !inside {
+                break;
+            }
+            if !dirs.iter().any(|d| d == dir) {
+                dirs.push(dir.to_path_buf());
+            }
+        }
+    }
+
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    dirs
+}
+
+/// Quote a string for bash
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
 }
 
-async fn try_native_remove(package: &str) -> Result<()> {
-    if let Some(pm) = detect_native_pm().await {
+async fn try_native_remove(package: &str, method: Option<&str>) -> Result<()> {
+    if let Some(pm) = native_pm_for(method).await {
         if pm.is_installed(package).await? {
             Logger::info(&format!("Removing via {}...", pm.name().cyan()));
             pm.remove(package).await?;
@@ -90,14 +568,17 @@ async fn try_native_remove(package: &str) -> Result<()> {
     anyhow::bail!("Package '{}' is not installed", package)
 }
 
-fn build_remove_script(script_path: &str, method: &str) -> Result<String> {
-    let os_info = get_os_info();
-
-    let sudo_cmd = if os_info.os_type == OsType::Android {
+/// Get the command scripts use to gain root privileges
+fn sudo_command() -> String {
+    if get_os_info().os_type == OsType::Android {
         "".to_string()
     } else {
         std::env::var("XPM_SUDO").unwrap_or_else(|_| "sudo".to_string())
-    };
+    }
+}
+
+fn build_remove_script(script_path: &str, method: &str) -> Result<String> {
+    let sudo_cmd = sudo_command();
 
     let script = format!(
         r#"#!/bin/bash
@@ -119,64 +600,127 @@ remove_{method} "$XPM_SUDO"
     Ok(script)
 }
 
-async fn run_script(script: &str, name: &str) -> Result<()> {
-    use std::sync::Arc;
-
-    let spinner = Arc::new(ProgressBar::new_spinner());
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.red} {msg}")
-            .unwrap(),
-    );
+/// Run a script behind a spinner, added to `progress` when several run at once
+async fn run_script(
+    script: &str,
+    name: &str,
+    runner: &ScriptRunner,
+    progress: Option<&MultiProgress>,
+) -> Result<()> {
+    let spinner = match progress {
+        Some(progress) => progress.add(ProgressBar::new_spinner()),
+        None => ProgressBar::new_spinner(),
+    };
+    // Side by side spinners need to say which script they belong to
+    let template = if progress.is_some() {
+        "{spinner:.red} {prefix:.bold} {msg}"
+    } else {
+        "{spinner:.red} {msg}"
+    };
+    spinner.set_style(ProgressStyle::default_spinner().template(template).unwrap());
+    spinner.set_prefix(name.to_string());
     spinner.set_message(format!("Removing {}...", name));
     spinner.enable_steady_tick(Duration::from_millis(100));
 
-    let mut child = Command::new("bash")
-        .arg("-c")
-        .arg(script)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
-
-    // Spawn task to read stdout
-    let spinner_stdout = Arc::clone(&spinner);
-    let stdout_task = tokio::spawn(async move {
-        if let Some(stdout) = stdout {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                spinner_stdout.set_message(line);
-            }
-        }
-    });
+    let result = runner
+        .run(script, |stream, line| match stream {
+            OutputStream::Stdout => spinner.set_message(line.to_string()),
+            // Display stderr in yellow to differentiate from stdout
+            OutputStream::Stderr => spinner.set_message(format!("{}", line.yellow())),
+        })
+        .await;
 
-    // Spawn task to read stderr
-    let spinner_stderr = Arc::clone(&spinner);
-    let stderr_task = tokio::spawn(async move {
-        if let Some(stderr) = stderr {
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
+    spinner.finish_and_clear();
+    result
+}
 
-            while let Ok(Some(line)) = lines.next_line().await {
-                // Display stderr in yellow to differentiate from stdout
-                spinner_stderr.set_message(format!("{}", line.yellow()));
-            }
-        }
-    });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emptied_dirs() {
+        let watched = [PathBuf::from("/opt"), PathBuf::from("/home/user/.config")];
+        let files = [
+            Path::new("/opt/app/lib/libapp.so"),
+            Path::new("/opt/app/bin/app"),
+            Path::new("/home/user/.config/app.conf"),
+            Path::new("/usr/bin/app"),
+        ];
+
+        assert_eq!(
+            emptied_dirs(files.into_iter(), &watched),
+            vec![
+                PathBuf::from("/opt/app/lib"),
+                PathBuf::from("/opt/app/bin"),
+                PathBuf::from("/opt/app"),
+            ]
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
 
-    // Wait for both streams to complete
-    let _ = tokio::try_join!(stdout_task, stderr_task)?;
+    #[test]
+    fn test_is_system_path() {
+        assert!(is_system_path(Path::new("/usr/local/bin/app")));
+        assert!(is_system_path(Path::new("/opt/app")));
+        assert!(!is_system_path(Path::new("/home/user/.config/app")));
+        assert!(!is_system_path(Path::new("/optional/app")));
+    }
 
-    let status = child.wait().await?;
-    spinner.finish_and_clear();
+    // The only test of the crate using the database, which it points at a
+    // temporary directory through the environment
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_remove_shared_files_in_parallel() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        std::env::set_var("HOME", root.join("home"));
+        std::env::set_var("XDG_DATA_HOME", root.join("data"));
+        std::env::set_var("XDG_CONFIG_HOME", root.join("config"));
+        std::env::set_var("XDG_CACHE_HOME", root.join("cache"));
+
+        let app_dir = root.join("home/.local/share/app");
+        std::fs::create_dir_all(&app_dir).unwrap();
+        let file = |name: &str| {
+            let path = app_dir.join(name);
+            std::fs::write(&path, name).unwrap();
+            path.display().to_string()
+        };
+        let (a, b, shared, kept) = (file("a"), file("b"), file("shared"), file("kept"));
+
+        let script = root.join("pkg.bash");
+        std::fs::write(&script, "install_any() { :; }\n").unwrap();
+        let db = Database::instance().unwrap();
+        for (name, files) in [
+            ("a", vec![a.clone(), shared.clone()]),
+            ("b", vec![b.clone(), shared.clone(), kept.clone()]),
+            ("c", vec![kept.clone()]),
+        ] {
+            let mut pkg = Package::new(name);
+            pkg.script = Some(script.display().to_string());
+            pkg.installed = Some("1.0".to_string());
+            pkg.method = Some("any".to_string());
+            db.upsert_package(pkg).unwrap();
+            db.record_package_files(PackageFiles::new(name, files))
+                .unwrap();
+        }
 
-    if !status.success() {
-        anyhow::bail!("Remove script failed with exit code: {:?}", status.code());
-    }
+        let packages = ["a".to_string(), "b".to_string()];
+        run_many(&packages, false, true, 2).await.unwrap();
 
-    Ok(())
+        for deleted in [&a, &b, &shared] {
+            assert!(!Path::new(deleted).exists(), "{}", deleted);
+        }
+        // Still recorded for c, which stays installed
+        assert!(Path::new(&kept).exists());
+
+        let db = Database::instance().unwrap();
+        assert!(db.get_package_files("a").unwrap().is_none());
+        assert!(db.get_package_files("b").unwrap().is_none());
+        assert!(db.get_package_files("c").unwrap().is_some());
+        assert!(!db
+            .find_package_by_name("a")
+            .unwrap()
+            .unwrap()
+            .is_installed());
+    }
 }
@@ -4,20 +4,40 @@ use crate::RepoAction;
 use anyhow::Result;
 use indicatif::{ProgressBar, ProgressStyle};
 use owo_colors::OwoColorize;
+use std::path::Path;
 use std::time::Duration;
-use xpm_core::{repo::Repositories, utils::logger::Logger};
+use xpm_core::{
+    db::{Database, Repo, RepoRef},
+    repo::{signing::SignedIndex, Repositories},
+    utils::logger::Logger,
+};
 
 /// Run the repo command
 pub async fn run(action: RepoAction) -> Result<()> {
     match action {
-        RepoAction::Add { url } => add(&url).await,
+        RepoAction::Add {
+            url,
+            git_ref,
+            branch,
+            commit,
+            priority,
+        } => {
+            let git_ref = match (git_ref, branch, commit) {
+                (Some(name), _, _) => Some(Repositories::resolve_ref(&url, &name).await?),
+                (_, Some(branch), _) => Some(RepoRef::Branch(branch)),
+                (_, _, Some(commit)) => Some(RepoRef::Commit(commit)),
+                _ => None,
+            };
+            add(Repo::new(url).with_ref(git_ref).with_priority(priority)).await
+        }
         RepoAction::Remove { url } => remove(&url).await,
+        RepoAction::Trust { url, pubkey } => trust(&url, &pubkey),
         RepoAction::List => list().await,
     }
 }
 
-async fn add(url: &str) -> Result<()> {
-    Logger::info(&format!("Adding repository: {}...", url.cyan()));
+pub async fn add(repo: Repo) -> Result<()> {
+    Logger::info(&format!("Adding repository: {}...", repo.url.cyan()));
 
     let spinner = ProgressBar::new_spinner();
     spinner.set_style(
@@ -28,12 +48,19 @@ async fn add(url: &str) -> Result<()> {
     spinner.set_message("Cloning repository...");
     spinner.enable_steady_tick(Duration::from_millis(100));
 
-    let repo = Repositories::add_repo(url).await?;
+    let repo = Repositories::add_repo(repo).await?;
 
     spinner.finish_and_clear();
 
     let repo_name = Repositories::repo_name(&repo.url);
-    Logger::success(&format!("Added repository: {}", repo_name.green()));
+    match &repo.git_ref {
+        Some(git_ref) => Logger::success(&format!(
+            "Added repository: {} at {}",
+            repo_name.green(),
+            git_ref.to_string().yellow()
+        )),
+        None => Logger::success(&format!("Added repository: {}", repo_name.green())),
+    }
 
     // Index the repository
     Logger::info("Indexing packages...");
@@ -57,6 +84,43 @@ async fn remove(url: &str) -> Result<()> {
     Ok(())
 }
 
+fn trust(spec: &str, pubkey: &str) -> Result<()> {
+    let repo = Repositories::find_repo(spec)?
+        .ok_or_else(|| anyhow::anyhow!("Repository '{}' not found, see 'xpm repo list'", spec))?;
+
+    // Accept the path of a minisign.pub file as well as the key itself
+    let key = match std::fs::read_to_string(pubkey) {
+        Ok(content) => content,
+        Err(_) => pubkey.to_string(),
+    };
+
+    let repo = Repositories::trust(&repo, &key)?;
+    let name = Repositories::repo_name(&repo.url);
+    Logger::success(&format!(
+        "Trusting {} for {}",
+        key_id(&repo).yellow(),
+        name.green()
+    ));
+
+    // The key is trusted anyway, the repository may not be signed yet
+    if let (Some(root), Some(key)) = (&repo.local_path, &repo.trusted_key) {
+        match SignedIndex::load(Path::new(root), key, &name) {
+            Ok(_) => Logger::success("Signed index verified"),
+            Err(e) => Logger::warning(&format!("{:#}", e)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Short form of the trusted key of a repository, for display
+fn key_id(repo: &Repo) -> String {
+    match &repo.trusted_key {
+        Some(key) => key.chars().take(16).collect::<String>() + "...",
+        None => "none".to_string(),
+    }
+}
+
 async fn list() -> Result<()> {
     let repos = Repositories::all_repos()?;
 
@@ -79,11 +143,62 @@ async fn list() -> Result<()> {
             .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
             .unwrap_or_else(|| "never".to_string());
 
+        let git_ref = repo
+            .git_ref
+            .as_ref()
+            .map(|r| r.to_string())
+            .unwrap_or_else(|| "default branch".to_string());
+        let head = repo
+            .local_path
+            .as_deref()
+            .and_then(|path| Repositories::head_commit(Path::new(path)).ok())
+            .map(|id| format!(" at {}", &id[..12]))
+            .unwrap_or_default();
+
         println!("  📦 {}", name.green().bold());
         println!("     URL: {}", repo.url.dimmed());
+        println!("     Ref: {}{}", git_ref.yellow(), head.dimmed());
+        println!("     Priority: {}", repo.priority);
+        println!("     Trusted key: {}", key_id(&repo).yellow());
         println!("     Last sync: {}", last_sync.dimmed());
         println!();
     }
 
+    print_conflicts()
+}
+
+/// List packages shipped by several repositories and which one is used
+fn print_conflicts() -> Result<()> {
+    let conflicts = Repositories::conflicts()?;
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    let db = Database::instance()?;
+    println!("{}", "━━━ Conflicts ━━━".cyan().bold());
+    println!();
+
+    for (name, repos) in &conflicts {
+        let used = db.find_package_by_name(name)?.and_then(|p| p.repo_id);
+        let shipped: Vec<String> = repos
+            .iter()
+            .map(|repo| {
+                let repo_name = Repositories::repo_name(&repo.url);
+                if Some(repo.id) == used {
+                    format!("{}", repo_name.green().bold())
+                } else {
+                    format!("{}", repo_name.dimmed())
+                }
+            })
+            .collect();
+        println!("  {} {}", name.bold(), shipped.join(" > "));
+    }
+
+    println!();
+    Logger::tip(&format!(
+        "The highlighted repository is used, pick another with {}",
+        "xpm install <repo>/<package>".cyan()
+    ));
+
     Ok(())
 }
@@ -0,0 +1,199 @@
+//! Script review command implementation
+//!
+//! With review enabled, a package script only runs once the user approved its
+//! exact content. A new script is shown in full, a changed one as a diff
+//! against the version approved last.
+
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use xpm_core::{
+    db::{ApprovedScript, Database, Package},
+    utils::{
+        checksum::{Checksum, ChecksumAlgorithm},
+        diff::{unified_diff, DiffLine},
+        logger::Logger,
+    },
+};
+
+use crate::commands::install::install_parent;
+use crate::prompt;
+
+/// Lines of context shown around each change
+const DIFF_CONTEXT: usize = 3;
+
+/// Run the review command
+///
+/// Enables or disables review, reviews the scripts of the given packages, or
+/// shows the review status and the approved scripts.
+pub async fn run(packages: &[String], enable: bool, disable: bool, yes: bool) -> Result<()> {
+    let db = Database::instance()?;
+
+    if enable || disable {
+        db.set_script_review(enable)?;
+        if enable {
+            Logger::success("Script review enabled");
+            Logger::tip("New and changed scripts are now shown for approval before they run");
+        } else {
+            Logger::success("Script review disabled");
+        }
+    }
+
+    for name in packages {
+        let pkg = db
+            .find_package_by_name(name)?
+            .ok_or_else(|| anyhow::anyhow!("Package '{}' not found in XPM database", name))?;
+        let content = read_script(&pkg)?;
+        if !review(&pkg, &content, yes)? {
+            Logger::info(&format!(
+                "The script of {} is already approved",
+                name.green()
+            ));
+        }
+    }
+
+    if packages.is_empty() && !enable && !disable {
+        status(db)?;
+    }
+
+    Ok(())
+}
+
+/// Ask for approval of a package script before it runs, if review is enabled
+///
+/// `content` is the script as it will run.
+pub fn check(pkg: &Package, content: &str, yes: bool) -> Result<()> {
+    if Database::instance()?.script_review_enabled()? {
+        review(pkg, content, yes)?;
+    }
+    Ok(())
+}
+
+/// Read the script of a package
+pub fn read_script(pkg: &Package) -> Result<String> {
+    let path = pkg
+        .script
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Package has no installation script"))?;
+    std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the script of {}", pkg.name))
+}
+
+/// Show a script that is new or changed since its last approval and ask for approval
+///
+/// Returns whether the script needed approval, and an error when it was not
+/// approved.
+fn review(pkg: &Package, content: &str, yes: bool) -> Result<bool> {
+    let db = Database::instance()?;
+
+    let path = pkg.script.as_deref().unwrap_or_default();
+    let sha256 = Checksum::hash_bytes(content.as_bytes(), ChecksumAlgorithm::Sha256);
+
+    let approved = db.get_approved_script(&pkg.name)?;
+    if approved.as_ref().is_some_and(|a| a.sha256 == sha256) {
+        return Ok(false);
+    }
+
+    // Nested installs run with their output captured, so they cannot ask
+    if !yes && (!prompt::is_interactive() || install_parent().is_some()) {
+        anyhow::bail!(
+            "The script of {} is {} and has not been reviewed, run 'xpm review {}' or pass --yes",
+            pkg.name,
+            if approved.is_some() { "changed" } else { "new" },
+            pkg.name
+        );
+    }
+
+    show(pkg, path, content, approved.as_ref());
+
+    if !yes && !prompt::confirm(&format!("Run the script of {}?", pkg.name), false)? {
+        anyhow::bail!("The script of {} was not approved", pkg.name);
+    }
+
+    db.approve_script(ApprovedScript::new(&pkg.name, sha256, content))?;
+    Logger::success(&format!("Approved the script of {}", pkg.name.green()));
+    Ok(true)
+}
+
+/// Print a script in full, or as a diff against its approved version
+fn show(pkg: &Package, path: &str, content: &str, approved: Option<&ApprovedScript>) {
+    println!("{}", format!("━━━ Review: {} ━━━", pkg.name).cyan().bold());
+    println!();
+
+    let Some(approved) = approved else {
+        println!("  New script {}", path.dimmed());
+        println!();
+        for (i, line) in content.lines().enumerate() {
+            println!("{:>4}  {}", (i + 1).to_string().dimmed(), line);
+        }
+        println!();
+        return;
+    };
+
+    println!(
+        "  Changed since approved on {} {}",
+        approved
+            .approved_at
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+            .yellow(),
+        path.dimmed()
+    );
+    println!();
+    for hunk in unified_diff(&approved.content, content, DIFF_CONTEXT) {
+        println!("{}", hunk.to_string().cyan());
+        for line in &hunk.lines {
+            match line {
+                DiffLine::Context(_) => println!("{}", line.dimmed()),
+                DiffLine::Removed(_) => println!("{}", line.red()),
+                DiffLine::Added(_) => println!("{}", line.green()),
+            }
+        }
+    }
+    println!();
+}
+
+/// Print whether review is enabled and which scripts are approved
+fn status(db: &Database) -> Result<()> {
+    let enabled = db.script_review_enabled()?;
+    println!(
+        "Script review: {}",
+        if enabled {
+            "enabled".green().to_string()
+        } else {
+            "disabled".yellow().to_string()
+        }
+    );
+
+    let approved = db.get_approved_scripts()?;
+    if !approved.is_empty() {
+        println!();
+        println!("{}", "━━━ Approved Scripts ━━━".cyan().bold());
+        println!();
+        for script in &approved {
+            // Flag scripts that would need a new review
+            let current = db
+                .find_package_by_name(&script.name)?
+                .and_then(|p| p.script)
+                .and_then(|path| std::fs::read(path).ok())
+                .map(|bytes| Checksum::hash_bytes(&bytes, ChecksumAlgorithm::Sha256));
+            let state = match current {
+                Some(sha256) if sha256 == script.sha256 => String::new(),
+                Some(_) => format!(" {}", "(changed)".yellow()),
+                None => format!(" {}", "(missing)".dimmed()),
+            };
+            println!(
+                "  {} {} {}{}",
+                script.name.green().bold(),
+                script.sha256.get(..12).unwrap_or(&script.sha256).dimmed(),
+                script.approved_at.format("%Y-%m-%d %H:%M"),
+                state
+            );
+        }
+        println!();
+    }
+
+    if !enabled {
+        Logger::tip("Enable it with: xpm review --enable");
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Rollback command implementation
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use xpm_core::{db::Database, native_pm::NativeMode, utils::logger::Logger};
+
+use crate::commands::{install, remove};
+
+/// Run the rollback command
+pub async fn run(id: u64, insecure: bool, yes: bool) -> Result<()> {
+    let db = Database::instance()?;
+
+    let txn = db
+        .find_transaction(id)?
+        .ok_or_else(|| anyhow::anyhow!("Transaction #{} not found", id))?;
+
+    let package = match &txn.package {
+        Some(package) if txn.operation.is_package_change() => package.as_str(),
+        _ => anyhow::bail!(
+            "Transaction #{} ({}) is not a package change and cannot be rolled back",
+            id,
+            txn.operation
+        ),
+    };
+
+    if !txn.is_success() {
+        anyhow::bail!(
+            "Transaction #{} did not complete successfully, nothing to roll back",
+            id
+        );
+    }
+
+    Logger::info(&format!(
+        "Rolling back transaction #{} ({} {})...",
+        id,
+        txn.operation,
+        package.cyan()
+    ));
+
+    // Restore the state recorded before the transaction
+    let Some(old_version) = &txn.old_version else {
+        return remove::run(package, false, yes).await;
+    };
+
+    let method = txn.old_method.as_deref().unwrap_or("auto");
+    // Reinstall from the same native package manager when it is still known
+    let native_mode = if txn.old_native {
+        match txn.old_method.as_deref().map(str::parse) {
+            Some(Ok(mode @ NativeMode::Sources(_))) => mode,
+            _ => NativeMode::Only,
+        }
+    } else {
+        NativeMode::Off
+    };
+
+    let options = install::ScriptOptions {
+        method,
+        channel: txn.old_channel.as_deref(),
+        insecure,
+        yes,
+        ..Default::default()
+    };
+    install::run(package, &options, &native_mode).await?;
+
+    // Scripts and native package managers install whatever version they currently provide
+    let restored = db.find_package_by_name(package)?.and_then(|p| p.installed);
+    if restored.as_deref() != Some(old_version.as_str()) {
+        Logger::warning(&format!(
+            "Restored {} with method {}, but version {} is no longer available (installed {})",
+            package.cyan(),
+            method.cyan(),
+            old_version.yellow(),
+            restored.as_deref().unwrap_or("unknown").yellow()
+        ));
+    }
+
+    Ok(())
+}
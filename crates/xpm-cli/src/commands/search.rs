@@ -3,50 +3,41 @@
 use anyhow::Result;
 use owo_colors::OwoColorize;
 use xpm_core::{
-    db::{Database, Package},
-    native_pm::{detect_native_pm, NativePackage, NativePackageManager},
+    db::Database,
+    native_pm::{search_native, NativeMode, NativeSearchHit},
     utils::logger::Logger,
 };
 
-/// Calculate relevance score for a package based on search terms
-fn calculate_relevance(pkg: &Package, terms: &[String]) -> i32 {
-    let name_lower = pkg.name.to_lowercase();
-    let mut score = 0;
-
-    for term in terms {
-        let term_lower = term.to_lowercase();
-        if name_lower == term_lower {
-            score += 1000;
-        } else if name_lower.starts_with(&term_lower) {
-            score += 100;
-        } else if name_lower.contains(&term_lower) {
-            score += 30;
-        }
-        if let Some(title) = &pkg.title {
-            if title.to_lowercase().contains(&term_lower) {
-                score += 10;
-            }
-        }
-    }
-    score
-}
-
 /// Run the search command
+///
+/// With `provides`, only XPM packages providing that command are listed,
+/// narrowed down by the search terms if any are given.
 pub async fn run(
     terms: &[String],
     limit: usize,
     exact: bool,
     all: bool,
-    native_mode: &str,
+    provides: Option<&str>,
+    native_mode: &NativeMode,
     json: bool,
 ) -> Result<()> {
-    if terms.is_empty() && !all {
+    if terms.is_empty() && !all && provides.is_none() {
         anyhow::bail!("No search terms provided. Use --all to list all packages.");
     }
 
     let db = Database::instance()?;
-
-    let mut xpm_packages = if native_mode != "only" {
+    let pins = db.get_pins()?;
+
+    let mut suggestion = None;
+    let xpm_packages = if let Some(command) = provides {
+        let mut providers = db.find_providers(command)?;
+        if !terms.is_empty() {
+            let matches = db.search_packages(terms, usize::MAX)?.packages;
+            providers.retain(|p| matches.iter().any(|m| m.name == p.name));
+        }
+        providers.truncate(limit);
+        providers
+    } else if native_mode.includes_xpm() {
         if all {
             db.get_packages_limited(limit)?
         } else if exact {
@@ -58,32 +49,27 @@ pub async fn run(
             }
             results
         } else {
-            db.search_packages(terms, limit)?
+            let results = db.search_packages(terms, limit)?;
+            suggestion = results.suggestion;
+            // Ascending relevance: most relevant at bottom
+            results.packages.into_iter().rev().collect()
         }
     } else {
         Vec::new()
     };
 
-    // Sort XPM by relevance (ascending: most relevant at bottom)
-    if !all && !exact && !terms.is_empty() {
-        xpm_packages.sort_by_key(|pkg| calculate_relevance(pkg, terms));
-    }
+    // In auto mode native package managers are only asked when XPM has few results
+    let search_native_pms = match native_mode {
+        NativeMode::Auto => xpm_packages.len() < 6,
+        NativeMode::Off => false,
+        NativeMode::Only | NativeMode::Sources(_) => true,
+    };
 
-    let (native_packages, pm_pre_sorted) = if native_mode != "off" && !all {
-        if let Some(pm) = detect_native_pm().await {
-            if native_mode == "only" || xpm_packages.len() < 6 {
-                let query = terms.join(" ");
-                let packages = pm.search(&query, Some(limit)).await.unwrap_or_default();
-                let pre_sorted = pm.results_pre_sorted();
-                (packages, pre_sorted)
-            } else {
-                (Vec::new(), false)
-            }
-        } else {
-            (Vec::new(), false)
-        }
+    let native_hits = if search_native_pms && !all && provides.is_none() {
+        let pms = native_mode.native_pms().await;
+        search_native(&pms, &terms.join(" "), Some(limit)).await
     } else {
-        (Vec::new(), false)
+        Vec::new()
     };
 
     if json {
@@ -93,53 +79,55 @@ pub async fn run(
                     "name": p.name,
                     "version": p.version,
                     "description": p.desc,
-                    "installed": p.is_installed()
+                    "installed": p.is_installed(),
+                    "pinned": pins.get(&p.name),
+                    "provides": p.provides
                 })
             }).collect::<Vec<_>>(),
-            "native": native_packages.iter().map(|p| {
+            "native": native_hits.iter().map(|h| {
                 serde_json::json!({
-                    "name": p.name,
-                    "version": p.version,
-                    "description": p.description,
-                    "repo": p.repo,
-                    "installed": p.installed
+                    "name": h.package.name,
+                    "version": h.package.version,
+                    "description": h.package.description,
+                    "repo": h.package.repo,
+                    "sources": h.sources,
+                    "installed": h.package.installed
                 })
-            }).collect::<Vec<_>>()
+            }).collect::<Vec<_>>(),
+            "suggestion": suggestion
         });
         println!("{}", serde_json::to_string_pretty(&result)?);
         return Ok(());
     }
 
-    let has_results = !xpm_packages.is_empty() || !native_packages.is_empty();
+    let has_results = !xpm_packages.is_empty() || !native_hits.is_empty();
 
     if !has_results {
         Logger::warning("No packages found");
+        if let Some(suggestion) = &suggestion {
+            Logger::tip(&format!("Did you mean: xpm search {}", suggestion));
+        }
         return Ok(());
     }
 
-    let total = xpm_packages.len() + native_packages.len();
+    let total = xpm_packages.len() + native_hits.len();
     println!("{}", format!("Found {} packages:", total).cyan().bold());
     println!();
 
-    // Group native packages
-    let mut official: Vec<_> = native_packages.iter().filter(|p| !p.is_aur()).collect();
-    let mut aur: Vec<_> = native_packages.iter().filter(|p| p.is_aur()).collect();
+    // Group native packages (already ordered most relevant last)
+    let official: Vec<_> = native_hits.iter().filter(|h| !h.package.is_aur()).collect();
+    let mut aur: Vec<_> = native_hits.iter().filter(|h| h.package.is_aur()).collect();
 
     // Sort AUR by votes ascending (most voted at bottom, near prompt)
-    aur.sort_by(|a, b| a.popularity.cmp(&b.popularity));
-
-    // Invert official if not pre-sorted
-    if !pm_pre_sorted && !official.is_empty() {
-        official.reverse();
-    }
+    aur.sort_by_key(|h| h.package.popularity);
 
     // Display order: AUR (top) → Native (middle) → XPM (bottom, closest to prompt)
 
     // Display AUR packages first (top, least important)
     if !aur.is_empty() {
         println!("{}", "━━━ AUR Packages ━━━".cyan().bold());
-        for pkg in &aur {
-            print_native_package(pkg, true);
+        for hit in &aur {
+            print_native_package(hit, true);
         }
         println!();
     }
@@ -147,8 +135,8 @@ pub async fn run(
     // Display official/native packages (middle)
     if !official.is_empty() {
         println!("{}", "━━━ Native Packages ━━━".cyan().bold());
-        for pkg in &official {
-            print_native_package(pkg, false);
+        for hit in &official {
+            print_native_package(hit, false);
         }
         println!();
     }
@@ -164,8 +152,13 @@ pub async fn run(
             } else {
                 String::new()
             };
+            let pinned_str = if pins.contains_key(&pkg.name) {
+                format!("{}", " [pinned]".yellow())
+            } else {
+                String::new()
+            };
 
-            println!("  {} {} {}", name, version, installed_str);
+            println!("  {} {} {}{}", name, version, installed_str, pinned_str);
 
             if let Some(desc) = &pkg.desc {
                 println!("    {}", desc.dimmed());
@@ -174,10 +167,15 @@ pub async fn run(
         println!();
     }
 
+    if let Some(suggestion) = &suggestion {
+        Logger::tip(&format!("Did you mean: xpm search {}", suggestion));
+    }
+
     Ok(())
 }
 
-fn print_native_package(pkg: &NativePackage, is_aur: bool) {
+fn print_native_package(hit: &NativeSearchHit, is_aur: bool) {
+    let pkg = &hit.package;
     let name = if is_aur {
         format!("{}", pkg.name.magenta().bold())
     } else {
@@ -192,9 +190,11 @@ fn print_native_package(pkg: &NativePackage, is_aur: bool) {
     } else {
         pkg.repo
             .as_deref()
+            .filter(|r| !hit.sources.iter().any(|s| s == r))
             .map(|r| format!("[{}]", r))
             .unwrap_or_default()
     };
+    let sources = format!("({})", hit.sources.join(", "));
 
     let installed_str = if pkg.installed {
         format!("{}", " [installed]".green())
@@ -204,18 +204,20 @@ fn print_native_package(pkg: &NativePackage, is_aur: bool) {
 
     if is_aur {
         println!(
-            "  {} {} {} {}",
+            "  {} {} {} {}{}",
             name,
             version,
             extra.yellow(),
+            sources.cyan(),
             installed_str
         );
     } else {
         println!(
-            "  {} {} {} {}",
+            "  {} {} {} {}{}",
             name,
             version,
             extra.dimmed(),
+            sources.cyan(),
             installed_str
         );
     }
@@ -224,68 +226,3 @@ fn print_native_package(pkg: &NativePackage, is_aur: bool) {
         println!("    {}", desc.dimmed());
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn create_test_package(name: &str, title: Option<&str>) -> Package {
-        let mut pkg = Package::new(name);
-        pkg.title = title.map(|t| t.to_string());
-        pkg
-    }
-
-    #[test]
-    fn test_calculate_relevance_exact_match() {
-        let pkg = create_test_package("vim", None);
-        let terms = vec!["vim".to_string()];
-        assert_eq!(calculate_relevance(&pkg, &terms), 1000);
-    }
-
-    #[test]
-    fn test_calculate_relevance_starts_with() {
-        let pkg = create_test_package("vim-airline", None);
-        let terms = vec!["vim".to_string()];
-        assert_eq!(calculate_relevance(&pkg, &terms), 100);
-    }
-
-    #[test]
-    fn test_calculate_relevance_contains() {
-        let pkg = create_test_package("neovim", None);
-        let terms = vec!["vim".to_string()];
-        assert_eq!(calculate_relevance(&pkg, &terms), 30);
-    }
-
-    #[test]
-    fn test_calculate_relevance_title_match() {
-        let pkg = create_test_package("something-else", Some("Vim-fork editor"));
-        let terms = vec!["vim".to_string()];
-        assert_eq!(calculate_relevance(&pkg, &terms), 10);
-    }
-
-    #[test]
-    fn test_calculate_relevance_multiple_terms() {
-        let pkg = create_test_package("vim", Some("Text editor"));
-        let terms = vec!["vim".to_string(), "editor".to_string()];
-        // vim exact match (1000) + editor in title (10)
-        assert_eq!(calculate_relevance(&pkg, &terms), 1010);
-    }
-
-    #[test]
-    fn test_calculate_relevance_no_match() {
-        let pkg = create_test_package("emacs", None);
-        let terms = vec!["vim".to_string()];
-        assert_eq!(calculate_relevance(&pkg, &terms), 0);
-    }
-
-    #[test]
-    fn test_calculate_relevance_case_insensitive() {
-        let pkg = create_test_package("VIM", None);
-        let terms = vec!["vim".to_string()];
-        assert_eq!(calculate_relevance(&pkg, &terms), 1000);
-
-        let pkg2 = create_test_package("vim", None);
-        let terms2 = vec!["VIM".to_string()];
-        assert_eq!(calculate_relevance(&pkg2, &terms2), 1000);
-    }
-}
@@ -0,0 +1,49 @@
+//! Per-package summary for commands that act on several packages
+
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use std::collections::HashMap;
+
+/// What happened to one package, with the error already formatted for display
+pub type Outcome = Result<(), String>;
+
+/// Print one line per package and fail when any of them failed
+///
+/// `action` is the verb used in the failure message, e.g. "install".
+pub fn print(action: &str, packages: &[&str], outcomes: &HashMap<String, Outcome>) -> Result<()> {
+    println!();
+    println!("{}", "━━━ Summary ━━━".cyan().bold());
+
+    let mut failed = Vec::new();
+    for &name in packages {
+        match outcomes.get(name) {
+            Some(Ok(())) => println!("  {} {}", "✓".green(), name.green().bold()),
+            Some(Err(e)) => {
+                println!("  {} {} {}", "✗".red(), name.red().bold(), e.dimmed());
+                failed.push(name);
+            }
+            None => {
+                println!(
+                    "  {} {} {}",
+                    "✗".red(),
+                    name.red().bold(),
+                    "skipped".dimmed()
+                );
+                failed.push(name);
+            }
+        }
+    }
+    println!();
+
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "{} of {} packages failed to {}: {}",
+            failed.len(),
+            packages.len(),
+            action,
+            failed.join(", ")
+        );
+    }
+
+    Ok(())
+}
@@ -1,49 +1,141 @@
+//! Upgrade command implementation
+
 use anyhow::Result;
 use owo_colors::OwoColorize;
-use xpm_core::{db::Database, utils::logger::Logger};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use xpm_core::{
+    db::{Database, Package},
+    native_pm::{native_pm_for, NativePackageManager, NativeUpgrade},
+    utils::{logger::Logger, version::compare_versions},
+};
 
 use crate::commands::install;
-
-pub async fn run() -> Result<()> {
+use crate::prompt::{self, Exit};
+
+/// Exit code when a named package is not installed
+pub const EXIT_NOT_INSTALLED: u8 = 2;
+
+/// Exit code when every selected package is already up to date
+pub const EXIT_NOTHING_TO_DO: u8 = 100;
+
+/// Exit code when a dry run found upgrades to make
+pub const EXIT_PENDING: u8 = 101;
+
+/// Exit code when the upgrade was not confirmed
+pub const EXIT_CANCELLED: u8 = 102;
+
+/// Run the upgrade command
+///
+/// Exits with 0 when everything was upgraded, 1 when some upgrades failed,
+/// [`EXIT_NOTHING_TO_DO`] when there was nothing to upgrade, [`EXIT_PENDING`]
+/// when a dry run found upgrades, [`EXIT_CANCELLED`] when the upgrade was not
+/// confirmed and [`EXIT_NOT_INSTALLED`] for a named package that is not
+/// installed.
+pub async fn run(
+    packages: &[String],
+    exclude: &[String],
+    dry_run: bool,
+    yes: bool,
+    allow_downgrade: bool,
+    insecure: bool,
+) -> Result<()> {
     let db = Database::instance()?;
 
     let installed = db.get_installed_packages()?;
+    let pins = db.get_pins()?;
 
-    if installed.is_empty() {
-        Logger::info("No packages installed via XPM");
-        return Ok(());
+    for name in packages {
+        if !installed.iter().any(|p| &p.name == name) {
+            Logger::error(&format!("Package '{}' is not installed", name));
+            return Err(Exit(EXIT_NOT_INSTALLED).into());
+        }
+    }
+
+    let selected: Vec<&Package> = installed
+        .iter()
+        .filter(|p| packages.is_empty() || packages.contains(&p.name))
+        .filter(|p| !exclude.contains(&p.name))
+        .collect();
+
+    if selected.is_empty() {
+        Logger::info("No packages to check");
+        return Err(Exit(EXIT_NOTHING_TO_DO).into());
     }
 
     Logger::info(&format!(
         "Checking {} installed packages...",
-        installed.len()
+        selected.len()
     ));
 
+    let native_upgrades = list_native_upgrades(&selected).await;
+
     let mut upgradable = Vec::new();
+    let mut held = Vec::new();
 
-    for pkg in &installed {
+    for pkg in selected {
         let installed_version = match &pkg.installed {
             Some(v) => v,
             None => continue,
         };
 
-        let latest_version = match &pkg.version {
-            Some(v) => v,
-            None => continue,
+        let latest_version = if pkg.is_native {
+            match native_upgrades.get(&pkg.name) {
+                Some(upgrade) => upgrade
+                    .available
+                    .clone()
+                    .unwrap_or_else(|| "latest".to_string()),
+                None => continue,
+            }
+        } else {
+            match &pkg.version {
+                Some(v) => v.clone(),
+                None => continue,
+            }
         };
 
-        if installed_version != latest_version {
-            upgradable.push((
-                pkg.clone(),
-                installed_version.clone(),
-                latest_version.clone(),
-            ));
+        // Pinned packages may only move to the version they are pinned at
+        if let Some(pinned) = pins.get(&pkg.name) {
+            if compare_versions(&latest_version, pinned) != Ordering::Equal {
+                held.push(pkg.name.as_str());
+                continue;
+            }
         }
+
+        // The native package manager already decided what is newer for its packages
+        if !pkg.is_native {
+            match compare_versions(&latest_version, installed_version) {
+                Ordering::Greater => {}
+                Ordering::Less if allow_downgrade => {}
+                Ordering::Less => {
+                    Logger::warning(&format!(
+                        "{} {} is older than the installed {}, skipping (use --allow-downgrade)",
+                        pkg.name.cyan(),
+                        latest_version.yellow(),
+                        installed_version.yellow()
+                    ));
+                    continue;
+                }
+                Ordering::Equal => continue,
+            }
+        }
+
+        let mut pkg = pkg.clone();
+        pkg.version = Some(latest_version.clone());
+        upgradable.push((pkg, installed_version.clone(), latest_version));
+    }
+
+    if !held.is_empty() {
+        Logger::info(&format!(
+            "Holding pinned packages: {} (see {})",
+            held.join(", ").yellow(),
+            "xpm pin".cyan()
+        ));
     }
 
     if upgradable.is_empty() {
         Logger::success("All packages are up to date");
-        return Ok(());
+        return Err(Exit(EXIT_NOTHING_TO_DO).into());
     }
 
     Logger::info(&format!(
@@ -52,26 +144,97 @@ pub async fn run() -> Result<()> {
     ));
 
     for (pkg, old_ver, new_ver) in &upgradable {
+        let downgrade = if compare_versions(new_ver, old_ver) == Ordering::Less {
+            format!(" {}", "(downgrade)".yellow())
+        } else {
+            String::new()
+        };
         println!(
-            "  {} {} -> {}",
+            "  {} {} -> {}{}",
             pkg.name.cyan(),
             old_ver.red(),
-            new_ver.green()
+            new_ver.green(),
+            downgrade
         );
     }
 
     println!();
+
+    if dry_run {
+        return Err(Exit(EXIT_PENDING).into());
+    }
+
+    if !yes {
+        if !prompt::is_interactive() {
+            Logger::error("Not running interactively, pass --yes to upgrade without confirmation");
+            return Err(Exit(EXIT_CANCELLED).into());
+        }
+        if !prompt::confirm("Proceed with the upgrade?", true)? {
+            Logger::info("Upgrade cancelled");
+            return Err(Exit(EXIT_CANCELLED).into());
+        }
+    }
+
     Logger::info("Upgrading packages...");
 
+    let mut failed = Vec::new();
     for (pkg, _, _) in &upgradable {
-        let method = pkg.method.as_deref().unwrap_or("auto");
-        let channel = pkg.channel.as_deref();
-
-        if let Err(e) = install::run(&pkg.name, method, false, channel, &[], "auto").await {
+        if let Err(e) = install::upgrade(pkg, insecure, yes).await {
             Logger::error(&format!("Failed to upgrade {}: {}", pkg.name, e));
+            failed.push(pkg.name.as_str());
         }
     }
 
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "{} of {} packages failed to upgrade: {}",
+            failed.len(),
+            upgradable.len(),
+            failed.join(", ")
+        );
+    }
+
     Logger::success("Upgrade complete");
     Ok(())
 }
+
+/// Ask each native package manager in use which of its packages can be upgraded
+async fn list_native_upgrades(packages: &[&Package]) -> HashMap<String, NativeUpgrade> {
+    let mut methods: Vec<Option<&str>> = packages
+        .iter()
+        .filter(|p| p.is_native)
+        .map(|p| p.method.as_deref())
+        .collect();
+    methods.sort();
+    methods.dedup();
+
+    let mut upgrades = HashMap::new();
+    for method in methods {
+        let Some(pm) = native_pm_for(method).await else {
+            continue;
+        };
+
+        match pm.list_upgradable().await {
+            Ok(list) => {
+                // Only packages tracked with this package manager
+                let tracked = |u: &NativeUpgrade| {
+                    packages
+                        .iter()
+                        .any(|p| p.is_native && p.name == u.name && p.method.as_deref() == method)
+                };
+                upgrades.extend(
+                    list.into_iter()
+                        .filter(tracked)
+                        .map(|u| (u.name.clone(), u)),
+                );
+            }
+            Err(e) => Logger::warning(&format!(
+                "Could not check {} for upgrades: {}",
+                pm.name(),
+                e
+            )),
+        }
+    }
+
+    upgrades
+}
@@ -3,11 +3,13 @@
 //! Command-line interface for XPM.
 
 mod commands;
+mod prompt;
 
-use clap::{Parser, Subcommand};
+use clap::{builder::FalseyValueParser, Parser, Subcommand};
+use commands::lint::LintFormat;
 use std::process::ExitCode;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use xpm_core::{utils::logger::Logger, StartupChecks, VERSION};
+use xpm_core::{native_pm::NativeMode, utils::logger::Logger, StartupChecks, VERSION};
 
 #[derive(Parser)]
 #[command(
@@ -34,6 +36,10 @@ struct Cli {
     #[arg(short, long, global = true)]
     quiet: bool,
 
+    /// Run package scripts that no trusted key vouches for (see `xpm repo trust`)
+    #[arg(long, global = true, env = "XPM_INSECURE", value_parser = FalseyValueParser::new())]
+    insecure: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -58,16 +64,45 @@ enum Commands {
         #[arg(short, long)]
         all: bool,
 
-        /// Control native package manager integration (auto, only, off)
+        /// Only list XPM packages that provide this command
+        #[arg(long, value_name = "COMMAND")]
+        provides: Option<String>,
+
+        /// Package sources: auto, only, off, or a list such as apt,flatpak (xpm for the XPM index)
         #[arg(short, long, default_value = "auto")]
-        native: String,
+        native: NativeMode,
     },
 
-    /// Install a package
-    #[command(alias = "i")]
-    Install {
+    /// Show details about a package
+    Info {
         /// Package name
         package: String,
+    },
+
+    /// Find the packages that provide a command
+    Provides {
+        /// Command name (e.g. rg)
+        command: String,
+
+        /// Print a one-line install hint, for the command-not-found hook
+        #[arg(long, hide = true)]
+        hint: bool,
+    },
+
+    /// Print a shell hook suggesting `xpm install` for unknown commands
+    ///
+    /// Add `eval "$(xpm shell-hook bash)"` to ~/.bashrc (or the zsh/fish equivalent).
+    ShellHook {
+        /// Shell to print the hook for (bash, zsh, fish)
+        shell: String,
+    },
+
+    /// Install one or more packages
+    #[command(alias = "i")]
+    Install {
+        /// Package names, or repo/package to take a package from a given repository
+        #[arg(required_unless_present = "locked")]
+        packages: Vec<String>,
 
         /// Installation method (auto, any, apt, pacman, brew, etc.)
         #[arg(short, long, default_value = "auto")]
@@ -85,24 +120,169 @@ enum Commands {
         #[arg(short = 'e', long = "flags")]
         custom_flags: Vec<String>,
 
-        /// Native package manager mode (auto, only, off)
-        #[arg(short, long, default_value = "auto")]
-        native: String,
+        /// Package sources: auto, only, off, or a list such as flatpak,snap tried in order
+        ///
+        /// Overrides the preference set with `xpm prefer` for this install.
+        #[arg(short, long, visible_alias = "from", default_value = "auto")]
+        native: NativeMode,
+
+        /// Number of package scripts to run at the same time
+        #[arg(short, long, default_value = "4")]
+        jobs: usize,
+
+        /// Install the exact scripts recorded in the lockfile, every locked package without names
+        #[arg(long, conflicts_with_all = ["method", "force_method", "channel", "native"])]
+        locked: bool,
+
+        /// Lockfile used with --locked
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "xpm.lock",
+            requires = "locked"
+        )]
+        lockfile: String,
+
+        /// Run new or changed scripts without asking for their review
+        #[arg(short, long, env = "XPM_YES", value_parser = FalseyValueParser::new())]
+        yes: bool,
+
+        /// Run scripts in a sandbox, where only xBIN, xTMP and the paths in xWRITES are writable
+        #[arg(long, conflicts_with = "locked")]
+        sandbox: bool,
+
+        /// Let sandboxed scripts use the network
+        #[arg(long, requires = "sandbox")]
+        allow_network: bool,
+
+        /// Let sandboxed scripts write to the paths in their xWRITES without asking
+        #[arg(long, requires = "sandbox")]
+        allow_writes: bool,
     },
 
-    /// Remove a package
+    /// Remove one or more packages
     #[command(alias = "rm")]
     Remove {
-        /// Package name
-        package: String,
+        /// Package names
+        #[arg(required = true)]
+        packages: Vec<String>,
+
+        /// Remove even if other installed packages depend on it
+        #[arg(short, long)]
+        force: bool,
+
+        /// Delete the recorded files of packages without a removal script without asking
+        #[arg(short, long, env = "XPM_YES", value_parser = FalseyValueParser::new())]
+        yes: bool,
+
+        /// Number of package scripts to run at the same time
+        #[arg(short, long, default_value = "4")]
+        jobs: usize,
+    },
+
+    /// Remove automatically installed dependencies that are no longer needed
+    Autoremove {
+        /// Only list what would be removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
     },
 
     /// Refresh package database
     #[command(visible_alias = "ref")]
     Refresh,
 
-    /// Upgrade installed packages (stub)
-    Upgrade,
+    /// Upgrade installed packages
+    ///
+    /// Exit codes: 0 when everything was upgraded, 1 when some upgrades
+    /// failed, 2 when a named package is not installed, 100 when there was
+    /// nothing to upgrade, 101 when a dry run found upgrades and 102 when the
+    /// upgrade was not confirmed.
+    Upgrade {
+        /// Packages to upgrade (default: all installed packages)
+        packages: Vec<String>,
+
+        /// Skip these packages
+        #[arg(short, long)]
+        exclude: Vec<String>,
+
+        /// Only list the upgrades that would be made
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Upgrade without asking for confirmation or script review
+        #[arg(short, long, env = "XPM_YES", value_parser = FalseyValueParser::new())]
+        yes: bool,
+
+        /// Also reinstall packages whose repository version is older than the installed one
+        #[arg(long)]
+        allow_downgrade: bool,
+    },
+
+    /// Make this machine match a manifest of packages, pins and repositories
+    ///
+    /// Installs what is missing, reinstalls packages set up differently, removes
+    /// explicitly installed packages the manifest leaves out and adds missing
+    /// repositories.
+    Apply {
+        /// Manifest file
+        #[arg(default_value = "xpm.toml")]
+        file: String,
+
+        /// Only show the changes that would be made
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Apply without asking for confirmation or script review
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Number of package scripts to run at the same time
+        #[arg(short, long, default_value = "4")]
+        jobs: usize,
+    },
+
+    /// Write a manifest describing the installed packages, pins and repositories
+    Export {
+        /// Output file (default: stdout)
+        file: Option<String>,
+    },
+
+    /// Record the exact source of every installed package in a lockfile
+    Lock {
+        /// Lockfile to write
+        #[arg(default_value = "xpm.lock")]
+        file: String,
+    },
+
+    /// Hold a package at a version so upgrades and installs leave it alone
+    ///
+    /// Lists the pinned packages when called without arguments.
+    Pin {
+        /// Package name
+        package: Option<String>,
+
+        /// Version to hold (default: the installed version)
+        version: Option<String>,
+    },
+
+    /// Release a pinned package
+    Unpin {
+        /// Package name
+        package: String,
+    },
+
+    /// Set the order native backends are tried in when installing
+    ///
+    /// Shows the current preference when called without arguments.
+    Prefer {
+        /// Backends, most preferred first (e.g. flatpak,apt)
+        #[arg(value_delimiter = ',')]
+        backends: Vec<String>,
+
+        /// Forget the preference and use the detected order again
+        #[arg(long, conflicts_with = "backends")]
+        reset: bool,
+    },
 
     /// Download a file
     Get {
@@ -219,16 +399,108 @@ enum Commands {
         remove: bool,
     },
 
-    /// Show log
+    /// List installed packages
+    List {
+        /// Also list everything installed through the native package manager
+        #[arg(long)]
+        native: bool,
+    },
+
+    /// List the files a package script created when installing a package
+    Files {
+        /// Package name
+        package: String,
+    },
+
+    /// Find the packages that installed a file, or files under a directory
+    Owns {
+        /// File or directory path
+        path: String,
+    },
+
+    /// Show the operation log
     Log {
         /// Number of entries to show
         #[arg(short, long, default_value = "20")]
         count: usize,
+
+        /// Only show operations on this package
+        #[arg(short, long)]
+        package: Option<String>,
+
+        /// Only show operations since a date (YYYY-MM-DD, RFC 3339) or age (30m, 12h, 7d, 2w)
+        #[arg(short, long)]
+        since: Option<String>,
+
+        /// Only show failed operations
+        #[arg(short, long)]
+        failed: bool,
+
+        /// Only show operations of this kind (install, upgrade, remove, refresh, repo-add, repo-remove)
+        #[arg(short, long)]
+        op: Option<String>,
+
+        /// Replay the captured script output of a transaction
+        #[arg(long, value_name = "ID")]
+        show: Option<u64>,
+    },
+
+    /// Show install/remove/upgrade transaction history
+    History {
+        /// Number of transactions to show
+        #[arg(short, long, default_value = "20")]
+        count: usize,
+
+        /// Only show transactions for this package
+        #[arg(short, long)]
+        package: Option<String>,
+    },
+
+    /// Undo a transaction by restoring the previous version/method
+    Rollback {
+        /// Transaction ID (see `xpm history`)
+        id: u64,
+
+        /// Run new or changed scripts without asking for their review
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Review package scripts before they run
+    ///
+    /// With review enabled, new scripts and scripts changed since they were
+    /// last approved are shown, as a diff when changed, and only run once
+    /// approved. Shows the review status without arguments.
+    Review {
+        /// Packages whose scripts to review now
+        packages: Vec<String>,
+
+        /// Ask for approval of new and changed scripts before running them
+        #[arg(long, conflicts_with = "disable")]
+        enable: bool,
+
+        /// Run scripts without review
+        #[arg(long)]
+        disable: bool,
+
+        /// Approve the scripts without asking
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Check system configuration
     Check,
 
+    /// Check package scripts for missing metadata and dangerous patterns
+    Lint {
+        /// Scripts, directories of package scripts or repositories (default: all repositories)
+        targets: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = LintFormat::Text)]
+        format: LintFormat,
+    },
+
     /// Make a package (stub)
     Make {
         /// Package name
@@ -267,15 +539,45 @@ pub(crate) enum FileAction {
 #[derive(Subcommand)]
 pub(crate) enum RepoAction {
     /// Add a repository
+    ///
+    /// Without a ref the repository follows its default branch. Adding an
+    /// existing repository again updates its ref.
     Add {
         /// Repository URL
         url: String,
+
+        /// Branch, tag or commit to keep the repository at
+        #[arg(long = "ref", value_name = "REF", conflicts_with_all = ["branch", "commit"])]
+        git_ref: Option<String>,
+
+        /// Branch to follow
+        #[arg(long, conflicts_with = "commit")]
+        branch: Option<String>,
+
+        /// Commit to stay at
+        #[arg(long)]
+        commit: Option<String>,
+
+        /// Repositories with a higher priority win when several ship a package
+        #[arg(short, long, default_value = "0", allow_negative_numbers = true)]
+        priority: i32,
     },
     /// Remove a repository
     Remove {
         /// Repository URL
         url: String,
     },
+    /// Trust a minisign public key for a repository
+    ///
+    /// Scripts from the repository are then installed only when its signed
+    /// index (xpm.sum and xpm.sum.minisig) verifies with this key.
+    Trust {
+        /// Repository URL or name
+        url: String,
+
+        /// Public key, or the path to a minisign.pub file
+        pubkey: String,
+    },
     /// List repositories
     List,
 }
@@ -307,23 +609,34 @@ async fn main() -> ExitCode {
 
     init_logging(cli.verbose);
 
-    let result = run(cli.verbose, cli.quiet, cli.json, command).await;
+    let result = run(cli.verbose, cli.quiet, cli.json, cli.insecure, command).await;
 
     match result {
         Ok(()) => ExitCode::SUCCESS,
-        Err(e) => {
-            Logger::error(&format!("{}", e));
-            ExitCode::FAILURE
-        }
+        Err(e) => match e.downcast_ref::<prompt::Exit>() {
+            // The command already reported its outcome
+            Some(exit) => ExitCode::from(exit.0),
+            None => {
+                Logger::error(&format!("{}", e));
+                ExitCode::FAILURE
+            }
+        },
     }
 }
 
-async fn run(verbose: bool, quiet: bool, json: bool, command: Commands) -> anyhow::Result<()> {
+async fn run(
+    verbose: bool,
+    quiet: bool,
+    json: bool,
+    insecure: bool,
+    command: Commands,
+) -> anyhow::Result<()> {
     let dominated_by_startup = matches!(
         command,
         Commands::Search { .. }
             | Commands::Install { .. }
             | Commands::Remove { .. }
+            | Commands::Apply { .. }
             | Commands::External(_)
     );
 
@@ -341,29 +654,94 @@ async fn run(verbose: bool, quiet: bool, json: bool, command: Commands) -> anyho
             limit,
             exact,
             all,
+            provides,
             native,
-        } => commands::search::run(&terms, limit, exact, all, &native, json).await,
+        } => {
+            commands::search::run(
+                &terms,
+                limit,
+                exact,
+                all,
+                provides.as_deref(),
+                &native,
+                json,
+            )
+            .await
+        }
+        Commands::Info { package } => commands::info::run(&package, json).await,
+        Commands::Provides { command, hint } => commands::provides::run(&command, hint, json).await,
+        Commands::ShellHook { shell } => commands::provides::shell_hook(&shell),
         Commands::Install {
-            package,
+            packages,
+            custom_flags,
+            jobs,
+            locked: true,
+            lockfile,
+            yes,
+            ..
+        } => {
+            commands::install::run_locked(&packages, &lockfile, &custom_flags, insecure, yes, jobs)
+                .await
+        }
+        Commands::Install {
+            packages,
             method,
             force_method,
             channel,
             custom_flags,
             native,
+            jobs,
+            locked: false,
+            lockfile: _,
+            yes,
+            sandbox,
+            allow_network,
+            allow_writes,
         } => {
-            commands::install::run(
-                &package,
-                &method,
+            let options = commands::install::ScriptOptions {
+                method: &method,
                 force_method,
-                channel.as_deref(),
-                &custom_flags,
-                &native,
-            )
-            .await
+                channel: channel.as_deref(),
+                custom_flags: &custom_flags,
+                insecure,
+                yes,
+                sandbox,
+                network: allow_network,
+                allow_writes,
+            };
+            commands::install::run_many(&packages, &options, &native, jobs).await
         }
-        Commands::Remove { package } => commands::remove::run(&package).await,
+        Commands::Remove {
+            packages,
+            force,
+            yes,
+            jobs,
+        } => commands::remove::run_many(&packages, force, yes, jobs).await,
+        Commands::Autoremove { dry_run } => commands::autoremove::run(dry_run).await,
+        Commands::Apply {
+            file,
+            dry_run,
+            yes,
+            jobs,
+        } => commands::manifest::apply(&file, dry_run, yes, insecure, jobs).await,
+        Commands::Export { file } => commands::manifest::export(file.as_deref()).await,
+        Commands::Lock { file } => commands::manifest::lock(&file).await,
         Commands::Refresh => commands::refresh::run().await,
-        Commands::Upgrade => commands::upgrade::run().await,
+        Commands::Upgrade {
+            packages,
+            exclude,
+            dry_run,
+            yes,
+            allow_downgrade,
+        } => {
+            commands::upgrade::run(&packages, &exclude, dry_run, yes, allow_downgrade, insecure)
+                .await
+        }
+        Commands::Pin { package, version } => {
+            commands::pin::run(package.as_deref(), version.as_deref(), json).await
+        }
+        Commands::Unpin { package } => commands::pin::unpin(&package).await,
+        Commands::Prefer { backends, reset } => commands::prefer::run(&backends, reset, json).await,
         Commands::Get {
             url,
             output,
@@ -423,12 +801,47 @@ async fn run(verbose: bool, quiet: bool, json: bool, command: Commands) -> anyho
             )
             .await
         }
-        Commands::Log { count } => commands::log::run(count).await,
+        Commands::List { native } => commands::list::run(native, json).await,
+        Commands::Files { package } => commands::files::run(&package, json).await,
+        Commands::Owns { path } => commands::files::owns(&path, json).await,
+        Commands::Log {
+            count,
+            package,
+            since,
+            failed,
+            op,
+            show,
+        } => {
+            commands::log::run(
+                count,
+                package.as_deref(),
+                since.as_deref(),
+                failed,
+                op.as_deref(),
+                show,
+                json,
+            )
+            .await
+        }
+        Commands::History { count, package } => {
+            commands::history::run(count, package.as_deref(), json).await
+        }
+        Commands::Rollback { id, yes } => commands::rollback::run(id, insecure, yes).await,
+        Commands::Review {
+            packages,
+            enable,
+            disable,
+            yes,
+        } => commands::review::run(&packages, enable, disable, yes).await,
         Commands::Check => commands::check::run().await,
+        Commands::Lint { targets, format } => {
+            let format = if json { LintFormat::Json } else { format };
+            commands::lint::run(&targets, format).await
+        }
         Commands::Make { name } => commands::make::run(&name).await,
         Commands::External(args) => {
             // Treat unknown commands as search terms
-            commands::search::run(&args, 30, false, false, "auto", json).await
+            commands::search::run(&args, 30, false, false, None, &NativeMode::Auto, json).await
         }
     }
 }
@@ -0,0 +1,33 @@
+//! Interactive prompts and exit codes
+
+use anyhow::Result;
+use std::io::{self, IsTerminal, Write};
+
+/// Error carrying a specific process exit code
+///
+/// Returned by commands whose outcome is not a plain success or failure; the
+/// command reports the outcome itself, so nothing else is printed.
+#[derive(Debug, thiserror::Error)]
+#[error("exit code {0}")]
+pub struct Exit(pub u8);
+
+/// Check if the user can answer prompts
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+/// Ask a yes/no question, returning `default` on an empty answer
+pub fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    eprint!("{} {} ", question, hint);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        "y" | "yes" => true,
+        _ => false,
+    })
+}
@@ -0,0 +1,423 @@
+//! Full-text search index over package names, provided commands, titles and
+//! descriptions
+//!
+//! Packages are tokenized into an inverted index at refresh time and ranked
+//! with BM25. Query terms that are not in the index are expanded to indexed
+//! terms they prefix, are contained in, or are a typo or two away from.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::models::{Package, Posting, SearchTerm};
+
+/// BM25 term frequency saturation
+const K1: f32 = 1.2;
+
+/// BM25 document length normalization
+const B: f32 = 0.75;
+
+/// Weight of a token found in the package name
+const NAME_WEIGHT: f32 = 3.0;
+
+/// Weight of a command the package provides
+const PROVIDES_WEIGHT: f32 = 3.0;
+
+/// Weight of a token found in the package title
+const TITLE_WEIGHT: f32 = 2.0;
+
+/// Weight of a token found in the package description
+const DESC_WEIGHT: f32 = 1.0;
+
+/// Extra score for a query term equal to the whole package name or a provided command
+const EXACT_NAME_BONUS: f32 = 10.0;
+
+/// Collection statistics stored alongside the index terms
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct IndexStats {
+    /// Number of indexed packages
+    pub documents: usize,
+    /// Average number of tokens per package
+    pub avg_length: f32,
+}
+
+/// Inverted index used to rank packages for a query
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    terms: HashMap<String, Vec<Posting>>,
+    stats: IndexStats,
+}
+
+/// Ranked matches for a query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchMatches {
+    /// Package IDs with their scores, best first
+    pub scores: Vec<(u64, f32)>,
+    /// Corrected query when some terms were only matched by typo tolerance
+    pub suggestion: Option<String>,
+}
+
+/// Packages found for a query
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    /// Matching packages, most relevant first
+    pub packages: Vec<Package>,
+    /// Corrected query to offer as "did you mean"
+    pub suggestion: Option<String>,
+}
+
+impl SearchIndex {
+    /// Build the index for a set of packages
+    pub fn build(packages: &[Package]) -> Self {
+        let mut terms: HashMap<String, Vec<Posting>> = HashMap::new();
+        let mut total_length = 0usize;
+
+        for pkg in packages {
+            let mut frequencies: HashMap<String, f32> = HashMap::new();
+            let mut length = 0usize;
+
+            let name = pkg.name.to_lowercase();
+            let mut name_tokens = tokenize(&name);
+            if !name_tokens.contains(&name) {
+                name_tokens.push(name.clone());
+            }
+
+            let provides: Vec<String> = pkg.provides.iter().map(|p| p.to_lowercase()).collect();
+
+            let fields = [
+                (name_tokens, NAME_WEIGHT),
+                (provides.clone(), PROVIDES_WEIGHT),
+                (tokenize(pkg.title.as_deref().unwrap_or("")), TITLE_WEIGHT),
+                (tokenize(pkg.desc.as_deref().unwrap_or("")), DESC_WEIGHT),
+            ];
+
+            for (tokens, weight) in fields {
+                length += tokens.len();
+                for token in tokens {
+                    *frequencies.entry(token).or_default() += weight;
+                }
+            }
+
+            total_length += length;
+            for (term, frequency) in frequencies {
+                terms.entry(term.clone()).or_default().push(Posting {
+                    package_id: pkg.id,
+                    frequency,
+                    length: length as u32,
+                    exact_name: term == name || provides.contains(&term),
+                });
+            }
+        }
+
+        let stats = IndexStats {
+            documents: packages.len(),
+            avg_length: if packages.is_empty() {
+                0.0
+            } else {
+                total_length as f32 / packages.len() as f32
+            },
+        };
+
+        Self { terms, stats }
+    }
+
+    /// Rebuild an index from its stored terms and statistics
+    pub fn from_parts(terms: Vec<SearchTerm>, stats: IndexStats) -> Self {
+        Self {
+            terms: terms.into_iter().map(|t| (t.term, t.postings)).collect(),
+            stats,
+        }
+    }
+
+    /// Split the index into storable terms and statistics
+    pub fn into_parts(self) -> (Vec<SearchTerm>, IndexStats) {
+        let terms = self
+            .terms
+            .into_iter()
+            .map(|(term, postings)| SearchTerm { term, postings })
+            .collect();
+        (terms, self.stats)
+    }
+
+    /// Get the collection statistics
+    pub fn stats(&self) -> IndexStats {
+        self.stats
+    }
+
+    /// Rank packages for the query terms, best first
+    ///
+    /// A package matches if any query term matches it. Each query term
+    /// contributes the score of its best expansion for that package.
+    pub fn search(&self, terms: &[String]) -> SearchMatches {
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        let mut corrected = Vec::new();
+        let mut corrections = false;
+
+        for query in self.query_tokens(terms) {
+            let expansions = self.expand(&query);
+
+            // Only typo matches means the user probably misspelled the term
+            let suggested = expansions
+                .iter()
+                .filter(|e| e.typos > 0)
+                .min_by(|a, b| {
+                    a.typos.cmp(&b.typos).then_with(|| {
+                        self.postings(&b.term)
+                            .len()
+                            .cmp(&self.postings(&a.term).len())
+                            .then_with(|| a.term.cmp(&b.term))
+                    })
+                })
+                .filter(|_| expansions.iter().all(|e| e.typos > 0));
+            match suggested {
+                Some(e) => {
+                    corrected.push(e.term.clone());
+                    corrections = true;
+                }
+                None => corrected.push(query.clone()),
+            }
+
+            let mut best: HashMap<u64, f32> = HashMap::new();
+            for expansion in &expansions {
+                let postings = self.postings(&expansion.term);
+                let idf = self.idf(postings.len());
+                for posting in postings {
+                    let mut score = idf * self.saturate(posting) * expansion.factor;
+                    if posting.exact_name && expansion.term == query {
+                        score += EXACT_NAME_BONUS;
+                    }
+                    let entry = best.entry(posting.package_id).or_default();
+                    *entry = entry.max(score);
+                }
+            }
+
+            for (id, score) in best {
+                *scores.entry(id).or_default() += score;
+            }
+        }
+
+        let mut scores: Vec<(u64, f32)> = scores.into_iter().collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        SearchMatches {
+            scores,
+            suggestion: corrections.then(|| corrected.join(" ")),
+        }
+    }
+
+    /// Tokenize query terms, keeping whole terms that are indexed (like `vim-airline`)
+    fn query_tokens(&self, terms: &[String]) -> Vec<String> {
+        terms
+            .iter()
+            .map(|t| t.to_lowercase())
+            .flat_map(|t| {
+                if self.terms.contains_key(&t) {
+                    vec![t]
+                } else {
+                    tokenize(&t)
+                }
+            })
+            .collect()
+    }
+
+    fn postings(&self, term: &str) -> &[Posting] {
+        self.terms.get(term).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn idf(&self, document_frequency: usize) -> f32 {
+        let n = self.stats.documents as f32;
+        let df = document_frequency as f32;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    fn saturate(&self, posting: &Posting) -> f32 {
+        let avg = self.stats.avg_length.max(1.0);
+        let norm = 1.0 - B + B * posting.length as f32 / avg;
+        posting.frequency * (K1 + 1.0) / (posting.frequency + K1 * norm)
+    }
+
+    /// Find the indexed terms a query term may stand for
+    fn expand(&self, query: &str) -> Vec<Expansion> {
+        let max_typos = match query.chars().count() {
+            0..=3 => 0,
+            4..=7 => 1,
+            _ => 2,
+        };
+
+        self.terms
+            .keys()
+            .filter_map(|term| {
+                if term == query {
+                    Some(Expansion::new(term, 1.0, 0))
+                } else if term.starts_with(query) {
+                    Some(Expansion::new(term, 0.6, 0))
+                } else if term.contains(query) {
+                    Some(Expansion::new(term, 0.4, 0))
+                } else if max_typos > 0 {
+                    let typos = edit_distance(query, term);
+                    (typos <= max_typos).then(|| Expansion::new(term, 0.5 / typos as f32, typos))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// An indexed term matched by a query term
+struct Expansion {
+    term: String,
+    factor: f32,
+    typos: usize,
+}
+
+impl Expansion {
+    fn new(term: &str, factor: f32, typos: usize) -> Self {
+        Self {
+            term: term.to_string(),
+            factor,
+            typos,
+        }
+    }
+}
+
+/// Split text into lowercase alphanumeric tokens of two or more characters
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() >= 2)
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Damerau-Levenshtein distance (with adjacent transpositions)
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, title: Option<&str>, desc: Option<&str>) -> Package {
+        let mut pkg = Package::new(name);
+        pkg.title = title.map(String::from);
+        pkg.desc = desc.map(String::from);
+        pkg
+    }
+
+    fn ranked(index: &SearchIndex, packages: &[Package], query: &str) -> Vec<String> {
+        let terms: Vec<String> = query.split_whitespace().map(String::from).collect();
+        index
+            .search(&terms)
+            .scores
+            .iter()
+            .map(|(id, _)| packages.iter().find(|p| p.id == *id).unwrap().name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Vim-based text editor (v2)"),
+            vec!["vim", "based", "text", "editor", "v2"]
+        );
+        assert!(tokenize("a b").is_empty());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("vim", "vim"), 0);
+        assert_eq!(edit_distance("neovim", "neovin"), 1);
+        assert_eq!(edit_distance("firefox", "firfeox"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_ranking() {
+        let packages = vec![
+            package("vim-airline", None, Some("Status bar for vim")),
+            package(
+                "neovim",
+                None,
+                Some("Hyperextensible Vim-based text editor"),
+            ),
+            package("vim", Some("Vi Improved"), Some("Text editor")),
+            package("emacs", None, Some("GNU Emacs text editor")),
+        ];
+        let index = SearchIndex::build(&packages);
+
+        let results = ranked(&index, &packages, "vim");
+        assert_eq!(results[0], "vim");
+        assert_eq!(results.len(), 3);
+        assert!(!results.contains(&"emacs".to_string()));
+
+        // Packages matching every term come first
+        let results = ranked(&index, &packages, "gnu editor");
+        assert_eq!(results[0], "emacs");
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn test_provides() {
+        let mut ripgrep = package("ripgrep", None, Some("Recursively search directories"));
+        ripgrep.provides = vec!["rg".to_string()];
+        let packages = vec![ripgrep, package("rgb", None, Some("Color picker"))];
+        let index = SearchIndex::build(&packages);
+
+        assert_eq!(ranked(&index, &packages, "rg")[0], "ripgrep");
+    }
+
+    #[test]
+    fn test_fuzzy_and_suggestion() {
+        let packages = vec![
+            package("firefox", Some("Firefox"), Some("Web browser")),
+            package("neovim", None, Some("Text editor")),
+        ];
+        let index = SearchIndex::build(&packages);
+
+        let matches = index.search(&["firfox".to_string()]);
+        assert_eq!(matches.scores.len(), 1);
+        assert_eq!(matches.suggestion.as_deref(), Some("firefox"));
+
+        // Substring matches need no correction
+        let matches = index.search(&["fox".to_string()]);
+        assert_eq!(matches.scores.len(), 1);
+        assert!(matches.suggestion.is_none());
+
+        assert!(index.search(&["zzzzzz".to_string()]).scores.is_empty());
+    }
+
+    #[test]
+    fn test_parts_roundtrip() {
+        let packages = vec![package("vim", None, Some("Text editor"))];
+        let index = SearchIndex::build(&packages);
+        let expected = index.search(&["editor".to_string()]);
+
+        let (terms, stats) = index.into_parts();
+        assert_eq!(stats.documents, 1);
+        let index = SearchIndex::from_parts(terms, stats);
+        assert_eq!(index.search(&["editor".to_string()]), expected);
+    }
+}
@@ -0,0 +1,490 @@
+//! Legacy model versions kept so existing databases can be migrated
+//!
+//! When a stored model gains fields, its previous layout is frozen here and the
+//! current model declares `from = ...` so `Database::new` can migrate old rows.
+
+use super::models::{Package, Repo};
+
+/// Models as they were stored before schema version 2
+pub mod v1 {
+    use native_db::*;
+    use native_model::{native_model, Model};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[native_model(id = 1, version = 1)]
+    #[native_db]
+    pub struct Package {
+        #[primary_key]
+        pub id: u64,
+        #[secondary_key(unique)]
+        pub name: String,
+        pub script: Option<String>,
+        #[secondary_key]
+        pub desc: Option<String>,
+        pub version: Option<String>,
+        #[secondary_key]
+        pub title: Option<String>,
+        pub url: Option<String>,
+        pub arch: Vec<String>,
+        pub methods: Vec<String>,
+        pub defaults: Vec<String>,
+        #[secondary_key]
+        pub installed: Option<String>,
+        #[secondary_key]
+        pub method: Option<String>,
+        #[secondary_key]
+        pub channel: Option<String>,
+        pub is_native: bool,
+        #[secondary_key]
+        pub repo_id: Option<u64>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[native_model(id = 2, version = 1)]
+    #[native_db]
+    pub struct Repo {
+        #[primary_key]
+        pub id: u64,
+        #[secondary_key(unique)]
+        pub url: String,
+        pub local_path: Option<String>,
+        pub last_sync: Option<chrono::DateTime<chrono::Utc>>,
+    }
+}
+
+/// Models as they were stored before schema version 3
+pub mod v2 {
+    use super::super::models::{InstallReason, RepoRef};
+    use super::v1;
+    use native_db::*;
+    use native_model::{native_model, Model};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[native_model(id = 1, version = 2, from = v1::Package)]
+    #[native_db]
+    pub struct Package {
+        #[primary_key]
+        pub id: u64,
+        #[secondary_key(unique)]
+        pub name: String,
+        pub script: Option<String>,
+        #[secondary_key]
+        pub desc: Option<String>,
+        pub version: Option<String>,
+        #[secondary_key]
+        pub title: Option<String>,
+        pub url: Option<String>,
+        pub arch: Vec<String>,
+        pub methods: Vec<String>,
+        pub defaults: Vec<String>,
+        #[secondary_key]
+        pub installed: Option<String>,
+        #[secondary_key]
+        pub method: Option<String>,
+        #[secondary_key]
+        pub channel: Option<String>,
+        pub is_native: bool,
+        #[secondary_key]
+        pub repo_id: Option<u64>,
+        pub depends: Vec<String>,
+        pub recommends: Vec<String>,
+        pub conflicts: Vec<String>,
+        pub reason: InstallReason,
+        pub required_by: Vec<String>,
+    }
+
+    impl From<v1::Package> for Package {
+        fn from(old: v1::Package) -> Self {
+            Self {
+                id: old.id,
+                name: old.name,
+                script: old.script,
+                desc: old.desc,
+                version: old.version,
+                title: old.title,
+                url: old.url,
+                arch: old.arch,
+                methods: old.methods,
+                defaults: old.defaults,
+                installed: old.installed,
+                method: old.method,
+                channel: old.channel,
+                is_native: old.is_native,
+                repo_id: old.repo_id,
+                depends: Vec::new(),
+                recommends: Vec::new(),
+                conflicts: Vec::new(),
+                reason: InstallReason::Explicit,
+                required_by: Vec::new(),
+            }
+        }
+    }
+
+    impl From<Package> for v1::Package {
+        fn from(pkg: Package) -> Self {
+            Self {
+                id: pkg.id,
+                name: pkg.name,
+                script: pkg.script,
+                desc: pkg.desc,
+                version: pkg.version,
+                title: pkg.title,
+                url: pkg.url,
+                arch: pkg.arch,
+                methods: pkg.methods,
+                defaults: pkg.defaults,
+                installed: pkg.installed,
+                method: pkg.method,
+                channel: pkg.channel,
+                is_native: pkg.is_native,
+                repo_id: pkg.repo_id,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[native_model(id = 2, version = 2, from = v1::Repo)]
+    #[native_db]
+    pub struct Repo {
+        #[primary_key]
+        pub id: u64,
+        #[secondary_key(unique)]
+        pub url: String,
+        pub local_path: Option<String>,
+        pub last_sync: Option<chrono::DateTime<chrono::Utc>>,
+        pub git_ref: Option<RepoRef>,
+    }
+
+    impl From<v1::Repo> for Repo {
+        fn from(old: v1::Repo) -> Self {
+            Self {
+                id: old.id,
+                url: old.url,
+                local_path: old.local_path,
+                last_sync: old.last_sync,
+                git_ref: None,
+            }
+        }
+    }
+
+    impl From<Repo> for v1::Repo {
+        fn from(repo: Repo) -> Self {
+            Self {
+                id: repo.id,
+                url: repo.url,
+                local_path: repo.local_path,
+                last_sync: repo.last_sync,
+            }
+        }
+    }
+}
+
+/// Models as they were stored before schema version 4
+pub mod v3 {
+    use super::super::models::{InstallReason, RepoRef};
+    use super::v2;
+    use native_db::*;
+    use native_model::{native_model, Model};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[native_model(id = 1, version = 3, from = v2::Package)]
+    #[native_db]
+    pub struct Package {
+        #[primary_key]
+        pub id: u64,
+        #[secondary_key(unique)]
+        pub name: String,
+        pub script: Option<String>,
+        #[secondary_key]
+        pub desc: Option<String>,
+        pub version: Option<String>,
+        #[secondary_key]
+        pub title: Option<String>,
+        pub url: Option<String>,
+        pub arch: Vec<String>,
+        pub methods: Vec<String>,
+        pub defaults: Vec<String>,
+        #[secondary_key]
+        pub installed: Option<String>,
+        #[secondary_key]
+        pub method: Option<String>,
+        #[secondary_key]
+        pub channel: Option<String>,
+        pub is_native: bool,
+        #[secondary_key]
+        pub repo_id: Option<u64>,
+        pub depends: Vec<String>,
+        pub recommends: Vec<String>,
+        pub conflicts: Vec<String>,
+        pub reason: InstallReason,
+        pub required_by: Vec<String>,
+        pub provides: Vec<String>,
+    }
+
+    impl From<v2::Package> for Package {
+        fn from(old: v2::Package) -> Self {
+            Self {
+                id: old.id,
+                name: old.name,
+                script: old.script,
+                desc: old.desc,
+                version: old.version,
+                title: old.title,
+                url: old.url,
+                arch: old.arch,
+                methods: old.methods,
+                defaults: old.defaults,
+                installed: old.installed,
+                method: old.method,
+                channel: old.channel,
+                is_native: old.is_native,
+                repo_id: old.repo_id,
+                depends: old.depends,
+                recommends: old.recommends,
+                conflicts: old.conflicts,
+                reason: old.reason,
+                required_by: old.required_by,
+                provides: Vec::new(),
+            }
+        }
+    }
+
+    impl From<Package> for v2::Package {
+        fn from(pkg: Package) -> Self {
+            Self {
+                id: pkg.id,
+                name: pkg.name,
+                script: pkg.script,
+                desc: pkg.desc,
+                version: pkg.version,
+                title: pkg.title,
+                url: pkg.url,
+                arch: pkg.arch,
+                methods: pkg.methods,
+                defaults: pkg.defaults,
+                installed: pkg.installed,
+                method: pkg.method,
+                channel: pkg.channel,
+                is_native: pkg.is_native,
+                repo_id: pkg.repo_id,
+                depends: pkg.depends,
+                recommends: pkg.recommends,
+                conflicts: pkg.conflicts,
+                reason: pkg.reason,
+                required_by: pkg.required_by,
+            }
+        }
+    }
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[native_model(id = 2, version = 3, from = v2::Repo)]
+    #[native_db]
+    pub struct Repo {
+        #[primary_key]
+        pub id: u64,
+        #[secondary_key(unique)]
+        pub url: String,
+        pub local_path: Option<String>,
+        pub last_sync: Option<chrono::DateTime<chrono::Utc>>,
+        pub git_ref: Option<RepoRef>,
+        pub priority: i32,
+    }
+
+    impl From<v2::Repo> for Repo {
+        fn from(old: v2::Repo) -> Self {
+            Self {
+                id: old.id,
+                url: old.url,
+                local_path: old.local_path,
+                last_sync: old.last_sync,
+                git_ref: old.git_ref,
+                priority: 0,
+            }
+        }
+    }
+
+    impl From<Repo> for v2::Repo {
+        fn from(repo: Repo) -> Self {
+            Self {
+                id: repo.id,
+                url: repo.url,
+                local_path: repo.local_path,
+                last_sync: repo.last_sync,
+                git_ref: repo.git_ref,
+            }
+        }
+    }
+}
+
+impl From<v3::Package> for Package {
+    fn from(old: v3::Package) -> Self {
+        Self {
+            id: old.id,
+            name: old.name,
+            script: old.script,
+            desc: old.desc,
+            version: old.version,
+            title: old.title,
+            url: old.url,
+            arch: old.arch,
+            methods: old.methods,
+            defaults: old.defaults,
+            installed: old.installed,
+            method: old.method,
+            channel: old.channel,
+            is_native: old.is_native,
+            repo_id: old.repo_id,
+            depends: old.depends,
+            recommends: old.recommends,
+            conflicts: old.conflicts,
+            reason: old.reason,
+            required_by: old.required_by,
+            provides: old.provides,
+            script_sha256: None,
+            repo_commit: None,
+        }
+    }
+}
+
+impl From<Package> for v3::Package {
+    fn from(pkg: Package) -> Self {
+        Self {
+            id: pkg.id,
+            name: pkg.name,
+            script: pkg.script,
+            desc: pkg.desc,
+            version: pkg.version,
+            title: pkg.title,
+            url: pkg.url,
+            arch: pkg.arch,
+            methods: pkg.methods,
+            defaults: pkg.defaults,
+            installed: pkg.installed,
+            method: pkg.method,
+            channel: pkg.channel,
+            is_native: pkg.is_native,
+            repo_id: pkg.repo_id,
+            depends: pkg.depends,
+            recommends: pkg.recommends,
+            conflicts: pkg.conflicts,
+            reason: pkg.reason,
+            required_by: pkg.required_by,
+            provides: pkg.provides,
+        }
+    }
+}
+
+impl From<v3::Repo> for Repo {
+    fn from(old: v3::Repo) -> Self {
+        Self {
+            id: old.id,
+            url: old.url,
+            local_path: old.local_path,
+            last_sync: old.last_sync,
+            git_ref: old.git_ref,
+            priority: old.priority,
+            trusted_key: None,
+        }
+    }
+}
+
+impl From<Repo> for v3::Repo {
+    fn from(repo: Repo) -> Self {
+        Self {
+            id: repo.id,
+            url: repo.url,
+            local_path: repo.local_path,
+            last_sync: repo.last_sync,
+            git_ref: repo.git_ref,
+            priority: repo.priority,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RepoRef;
+
+    #[test]
+    fn test_package_from_v1() {
+        let mut pkg = Package::new("test");
+        pkg.installed = Some("1.0.0".to_string());
+        pkg.depends = vec!["dep".to_string()];
+
+        let old: v1::Package = v2::Package::from(v3::Package::from(pkg.clone())).into();
+        let migrated: Package = v3::Package::from(v2::Package::from(old)).into();
+
+        assert_eq!(migrated.id, pkg.id);
+        assert_eq!(migrated.installed, Some("1.0.0".to_string()));
+        assert!(migrated.depends.is_empty());
+    }
+
+    #[test]
+    fn test_package_from_v2() {
+        let mut pkg = Package::new("test");
+        pkg.depends = vec!["dep".to_string()];
+        pkg.provides = vec!["test-cli".to_string()];
+
+        let old: v2::Package = v3::Package::from(pkg.clone()).into();
+        let migrated: Package = v3::Package::from(old).into();
+
+        assert_eq!(migrated.id, pkg.id);
+        assert_eq!(migrated.depends, vec!["dep".to_string()]);
+        assert!(migrated.provides.is_empty());
+    }
+
+    #[test]
+    fn test_package_from_v3() {
+        let mut pkg = Package::new("test");
+        pkg.provides = vec!["test-cli".to_string()];
+        pkg.script_sha256 = Some("abc".to_string());
+
+        let old: v3::Package = pkg.clone().into();
+        let migrated: Package = old.into();
+
+        assert_eq!(migrated.id, pkg.id);
+        assert_eq!(migrated.provides, vec!["test-cli".to_string()]);
+        assert!(migrated.script_sha256.is_none());
+    }
+
+    #[test]
+    fn test_repo_from_v1() {
+        let repo = Repo::new("https://example.com/repo.git")
+            .with_ref(Some(RepoRef::Tag("v2.0".to_string())));
+
+        let old: v1::Repo = v2::Repo::from(v3::Repo::from(repo.clone())).into();
+        let migrated: Repo = v3::Repo::from(v2::Repo::from(old)).into();
+
+        assert_eq!(migrated.url, repo.url);
+        assert!(migrated.git_ref.is_none());
+    }
+
+    #[test]
+    fn test_repo_from_v2() {
+        let repo = Repo::new("https://example.com/repo.git")
+            .with_ref(Some(RepoRef::Tag("v2.0".to_string())))
+            .with_priority(10);
+
+        let old: v2::Repo = v3::Repo::from(repo.clone()).into();
+        let migrated: Repo = v3::Repo::from(old).into();
+
+        assert_eq!(migrated.git_ref, repo.git_ref);
+        assert_eq!(migrated.priority, 0);
+    }
+
+    #[test]
+    fn test_repo_from_v3() {
+        let mut repo = Repo::new("https://example.com/repo.git").with_priority(10);
+        repo.trusted_key =
+            Some("RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3".to_string());
+
+        let old: v3::Repo = repo.clone().into();
+        let migrated: Repo = old.into();
+
+        assert_eq!(migrated.priority, 10);
+        assert!(migrated.trusted_key.is_none());
+    }
+}
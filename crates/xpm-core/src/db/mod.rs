@@ -2,8 +2,15 @@
 //!
 //! Uses native_db (powered by redb) for embedded database operations.
 
+mod index;
+mod migrations;
 mod models;
 mod operations;
 
-pub use models::{Package, Repo, Setting};
+pub use index::{edit_distance, tokenize, SearchIndex, SearchResults};
+pub use models::{
+    ApprovedScript, InstallReason, Operation, Package, PackageFiles, Posting, Repo, RepoPackage,
+    RepoRef, SearchTerm, Setting, Transaction,
+};
+pub(crate) use operations::depends_on;
 pub use operations::Database;
@@ -5,9 +5,11 @@ use native_db::*;
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
 
+use super::migrations::v3;
+
 /// Package model representing a package in the XPM database
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[native_model(id = 1, version = 1)]
+#[native_model(id = 1, version = 4, from = v3::Package)]
 #[native_db]
 pub struct Package {
     /// Unique identifier
@@ -60,6 +62,30 @@ pub struct Package {
     /// Repository ID this package belongs to
     #[secondary_key]
     pub repo_id: Option<u64>,
+
+    /// Packages (xpm or native) that must be installed first
+    pub depends: Vec<String>,
+
+    /// Packages suggested alongside this one
+    pub recommends: Vec<String>,
+
+    /// Packages that cannot be installed at the same time
+    pub conflicts: Vec<String>,
+
+    /// Why the package was installed
+    pub reason: InstallReason,
+
+    /// Installed packages that pulled this one in
+    pub required_by: Vec<String>,
+
+    /// Commands the package puts on the PATH
+    pub provides: Vec<String>,
+
+    /// SHA-256 of the script the installed version was installed from
+    pub script_sha256: Option<String>,
+
+    /// Repository commit the installed version was installed from
+    pub repo_commit: Option<String>,
 }
 
 impl Package {
@@ -82,6 +108,14 @@ impl Package {
             channel: None,
             is_native: false,
             repo_id: None,
+            depends: Vec::new(),
+            recommends: Vec::new(),
+            conflicts: Vec::new(),
+            reason: InstallReason::Explicit,
+            required_by: Vec::new(),
+            provides: Vec::new(),
+            script_sha256: None,
+            repo_commit: None,
         }
     }
 
@@ -98,6 +132,52 @@ impl Package {
         hasher.finish()
     }
 
+    /// Record why the package is being installed
+    ///
+    /// Installs requested by another package mark it as a dependency unless it was
+    /// already installed explicitly; direct installs always make it explicit.
+    pub fn set_install_reason(&mut self, parent: Option<&str>) {
+        match parent.filter(|p| *p != self.name) {
+            Some(parent) => {
+                if !self.is_installed() {
+                    self.reason = InstallReason::Dependency;
+                }
+                if !self.required_by.iter().any(|r| r == parent) {
+                    self.required_by.push(parent.to_string());
+                }
+            }
+            None => self.reason = InstallReason::Explicit,
+        }
+    }
+
+    /// Copy installation state from a previously stored copy of this package
+    pub fn keep_install_state(&mut self, existing: &Package) {
+        self.installed = existing.installed.clone();
+        self.method = existing.method.clone();
+        self.channel = existing.channel.clone();
+        self.is_native = existing.is_native;
+        self.reason = existing.reason;
+        self.required_by = existing.required_by.clone();
+        self.script_sha256 = existing.script_sha256.clone();
+        self.repo_commit = existing.repo_commit.clone();
+    }
+
+    /// Clear installation state after removal
+    pub fn clear_install_state(&mut self) {
+        self.installed = None;
+        self.method = None;
+        self.channel = None;
+        self.reason = InstallReason::Explicit;
+        self.required_by.clear();
+        self.script_sha256 = None;
+        self.repo_commit = None;
+    }
+
+    /// Check whether the package provides a command
+    pub fn provides_command(&self, command: &str) -> bool {
+        self.provides.iter().any(|p| p == command)
+    }
+
     /// Check if package supports a specific architecture
     pub fn supports_arch(&self, arch: &str) -> bool {
         self.arch.is_empty()
@@ -111,9 +191,59 @@ impl Package {
     }
 }
 
+/// Why a package was installed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum InstallReason {
+    /// Requested directly by the user
+    #[default]
+    Explicit,
+    /// Pulled in by another package
+    Dependency,
+}
+
+impl InstallReason {
+    /// Get reason name for display
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstallReason::Explicit => "explicit",
+            InstallReason::Dependency => "dependency",
+        }
+    }
+}
+
+/// Git ref a repository clone is kept at
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RepoRef {
+    /// Follow a branch
+    Branch(String),
+    /// Stay at a tag
+    Tag(String),
+    /// Stay at a commit
+    Commit(String),
+}
+
+impl RepoRef {
+    /// Get the branch, tag or commit name
+    pub fn name(&self) -> &str {
+        match self {
+            RepoRef::Branch(name) | RepoRef::Tag(name) | RepoRef::Commit(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for RepoRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepoRef::Branch(name) => write!(f, "branch {}", name),
+            RepoRef::Tag(name) => write!(f, "tag {}", name),
+            RepoRef::Commit(id) => write!(f, "commit {}", id.get(..12).unwrap_or(id)),
+        }
+    }
+}
+
 /// Repository model representing a package source
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[native_model(id = 2, version = 1)]
+#[native_model(id = 2, version = 4, from = v3::Repo)]
 #[native_db]
 pub struct Repo {
     /// Unique identifier
@@ -129,6 +259,15 @@ pub struct Repo {
 
     /// Last sync timestamp
     pub last_sync: Option<DateTime<Utc>>,
+
+    /// Branch, tag or commit to keep the clone at (None follows the default branch)
+    pub git_ref: Option<RepoRef>,
+
+    /// Repositories with a higher priority win when several ship a package
+    pub priority: i32,
+
+    /// Minisign public key the repository index must be signed with
+    pub trusted_key: Option<String>,
 }
 
 impl Repo {
@@ -139,10 +278,162 @@ impl Repo {
             url: url.into(),
             local_path: None,
             last_sync: None,
+            git_ref: None,
+            priority: 0,
+            trusted_key: None,
+        }
+    }
+
+    /// Set the ref to keep the clone at
+    pub fn with_ref(mut self, git_ref: Option<RepoRef>) -> Self {
+        self.git_ref = git_ref;
+        self
+    }
+
+    /// Set the priority
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Order repositories by precedence, the preferred one first
+    ///
+    /// Ties go to the repository that was added first.
+    pub fn precedence(a: &Repo, b: &Repo) -> std::cmp::Ordering {
+        b.priority.cmp(&a.priority).then(a.id.cmp(&b.id))
+    }
+}
+
+/// A package script as shipped by one repository
+///
+/// Every repository is indexed separately so packages shipped by several of
+/// them can be resolved by priority, or picked explicitly.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[native_model(id = 6, version = 1)]
+#[native_db]
+pub struct RepoPackage {
+    /// Repository ID and package name, as `<repo_id>/<name>`
+    #[primary_key]
+    pub key: String,
+
+    /// Package name
+    #[secondary_key]
+    pub name: String,
+
+    /// Repository the script comes from
+    #[secondary_key]
+    pub repo_id: u64,
+
+    /// Package metadata read from the script
+    pub package: Package,
+}
+
+impl RepoPackage {
+    /// Record a package read from a repository
+    pub fn new(repo_id: u64, package: Package) -> Self {
+        Self {
+            key: format!("{}/{}", repo_id, package.name),
+            name: package.name.clone(),
+            repo_id,
+            package,
+        }
+    }
+}
+
+/// The version of a package script the user last approved to run
+///
+/// Only recorded while script review is enabled; the content is kept so a
+/// changed script can be shown as a diff against it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[native_model(id = 7, version = 1)]
+#[native_db]
+pub struct ApprovedScript {
+    /// Package name
+    #[primary_key]
+    pub name: String,
+
+    /// SHA-256 of the approved script
+    pub sha256: String,
+
+    /// Approved script content
+    pub content: String,
+
+    pub approved_at: DateTime<Utc>,
+}
+
+impl ApprovedScript {
+    /// Approve a script now
+    pub fn new(
+        name: impl Into<String>,
+        sha256: impl Into<String>,
+        content: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            sha256: sha256.into(),
+            content: content.into(),
+            approved_at: Utc::now(),
+        }
+    }
+}
+
+/// Files a package script created when it installed the package
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[native_model(id = 8, version = 1)]
+#[native_db]
+pub struct PackageFiles {
+    /// Package name
+    #[primary_key]
+    pub name: String,
+
+    /// Absolute paths, sorted
+    pub files: Vec<String>,
+
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl PackageFiles {
+    /// Record the files of a package now
+    pub fn new(name: impl Into<String>, mut files: Vec<String>) -> Self {
+        files.sort();
+        files.dedup();
+        Self {
+            name: name.into(),
+            files,
+            recorded_at: Utc::now(),
         }
     }
 }
 
+/// Inverted index entry: the packages a search term appears in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[native_model(id = 5, version = 1)]
+#[native_db]
+pub struct SearchTerm {
+    /// Lowercase token
+    #[primary_key]
+    pub term: String,
+
+    /// Packages containing the token
+    pub postings: Vec<Posting>,
+}
+
+/// Occurrence of a search term in one package
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Posting {
+    /// Package ID
+    pub package_id: u64,
+
+    /// Field-weighted number of occurrences
+    pub frequency: f32,
+
+    /// Number of tokens in the package
+    pub length: u32,
+
+    /// Whether the term is the whole package name or a command it provides
+    pub exact_name: bool,
+}
+
 /// Key-Value setting with optional expiration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[native_model(id = 3, version = 1)]
@@ -206,10 +497,233 @@ impl Setting {
     }
 }
 
+/// Kind of operation recorded in the journal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Operation {
+    Install,
+    Upgrade,
+    Remove,
+    Refresh,
+    RepoAdd,
+    RepoRemove,
+}
+
+impl Operation {
+    /// All operations, in display order
+    pub const ALL: &'static [Operation] = &[
+        Operation::Install,
+        Operation::Upgrade,
+        Operation::Remove,
+        Operation::Refresh,
+        Operation::RepoAdd,
+        Operation::RepoRemove,
+    ];
+
+    /// Get operation name for display
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Install => "install",
+            Operation::Upgrade => "upgrade",
+            Operation::Remove => "remove",
+            Operation::Refresh => "refresh",
+            Operation::RepoAdd => "repo-add",
+            Operation::RepoRemove => "repo-remove",
+        }
+    }
+
+    /// Parse operation from string
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.to_lowercase().replace('_', "-");
+        Self::ALL.iter().copied().find(|op| op.as_str() == s)
+    }
+
+    /// Check if the operation changes an installed package
+    pub fn is_package_change(&self) -> bool {
+        matches!(
+            self,
+            Operation::Install | Operation::Upgrade | Operation::Remove
+        )
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Append-only journal entry recording an operation
+///
+/// Package changes (install/upgrade/remove) also carry the previous state
+/// so they can be rolled back.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[native_model(id = 4, version = 1)]
+#[native_db]
+pub struct Transaction {
+    /// Sequential identifier (assigned when recorded)
+    #[primary_key]
+    pub id: u64,
+
+    /// When the transaction started
+    pub timestamp: DateTime<Utc>,
+
+    /// How long the operation took
+    pub duration_ms: Option<u64>,
+
+    /// User who ran the operation
+    pub user: Option<String>,
+
+    /// Kind of operation
+    pub operation: Operation,
+
+    /// Package name (package operations only)
+    #[secondary_key]
+    pub package: Option<String>,
+
+    /// Repository URL (repository operations only)
+    pub repo: Option<String>,
+
+    /// Installed version before the transaction
+    pub old_version: Option<String>,
+
+    /// Installed version after the transaction
+    pub new_version: Option<String>,
+
+    /// Method used before the transaction
+    pub old_method: Option<String>,
+
+    /// Method used by the transaction
+    pub method: Option<String>,
+
+    /// Channel used before the transaction
+    pub old_channel: Option<String>,
+
+    /// Channel used by the transaction
+    pub channel: Option<String>,
+
+    /// Whether the package was managed by the native package manager before the transaction
+    pub old_native: bool,
+
+    /// Exit code (None if the operation failed without one)
+    pub exit_code: Option<i32>,
+
+    /// Path to the captured script output
+    pub output_path: Option<String>,
+}
+
+impl Transaction {
+    /// Create a new transaction started now by the current user
+    pub fn new(operation: Operation) -> Self {
+        Self {
+            id: 0,
+            timestamp: Utc::now(),
+            duration_ms: None,
+            user: current_user(),
+            operation,
+            package: None,
+            repo: None,
+            old_version: None,
+            new_version: None,
+            old_method: None,
+            method: None,
+            old_channel: None,
+            channel: None,
+            old_native: false,
+            exit_code: None,
+            output_path: None,
+        }
+    }
+
+    /// Create a transaction capturing the current state of a package as the old state
+    pub fn from_package(operation: Operation, package: &Package) -> Self {
+        let mut txn = Self::new(operation).with_package(&package.name);
+        txn.old_version = package.installed.clone();
+        txn.old_method = package.method.clone();
+        txn.old_channel = package.channel.clone();
+        txn.old_native = package.is_native;
+        txn
+    }
+
+    /// Builder pattern for package
+    pub fn with_package(mut self, package: impl Into<String>) -> Self {
+        self.package = Some(package.into());
+        self
+    }
+
+    /// Builder pattern for repository
+    pub fn with_repo(mut self, repo: impl Into<String>) -> Self {
+        self.repo = Some(repo.into());
+        self
+    }
+
+    /// Mark the transaction as finished with an exit code, recording its duration
+    pub fn finish(&mut self, exit_code: Option<i32>) {
+        let elapsed = Utc::now() - self.timestamp;
+        self.duration_ms = Some(elapsed.num_milliseconds().max(0) as u64);
+        self.exit_code = exit_code;
+    }
+
+    /// Check if the transaction completed successfully
+    pub fn is_success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Get the name of the user running xpm (the invoking user under sudo)
+fn current_user() -> Option<String> {
+    ["SUDO_USER", "USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_transaction_from_package() {
+        let mut pkg = Package::new("test");
+        pkg.installed = Some("1.0.0".to_string());
+        pkg.method = Some("any".to_string());
+
+        let txn = Transaction::from_package(Operation::Upgrade, &pkg);
+        assert_eq!(txn.package, Some("test".to_string()));
+        assert_eq!(txn.old_version, Some("1.0.0".to_string()));
+        assert_eq!(txn.old_method, Some("any".to_string()));
+        assert_eq!(txn.new_version, None);
+        assert!(!txn.is_success());
+    }
+
+    #[test]
+    fn test_transaction_finish() {
+        let mut txn = Transaction::new(Operation::Refresh);
+        assert!(txn.duration_ms.is_none());
+
+        txn.finish(Some(0));
+        assert!(txn.is_success());
+        assert!(txn.duration_ms.is_some());
+    }
+
+    #[test]
+    fn test_operation_parse() {
+        assert_eq!(Operation::parse("install"), Some(Operation::Install));
+        assert_eq!(Operation::parse("REPO_ADD"), Some(Operation::RepoAdd));
+        assert_eq!(Operation::parse("repo-remove"), Some(Operation::RepoRemove));
+        assert_eq!(Operation::parse("unknown"), None);
+        assert!(Operation::Upgrade.is_package_change());
+        assert!(!Operation::Refresh.is_package_change());
+    }
+
+    #[test]
+    fn test_repo_ref_display() {
+        let tag = RepoRef::Tag("v2.0".to_string());
+        assert_eq!(tag.to_string(), "tag v2.0");
+
+        let commit = RepoRef::Commit("0123456789abcdef0123".to_string());
+        assert_eq!(commit.to_string(), "commit 0123456789ab");
+        assert_eq!(commit.name(), "0123456789abcdef0123");
+    }
+
     #[test]
     fn test_package_new() {
         let pkg = Package::new("test-package");
@@ -217,6 +731,26 @@ mod tests {
         assert!(!pkg.is_installed());
     }
 
+    #[test]
+    fn test_set_install_reason() {
+        let mut pkg = Package::new("lib");
+        pkg.set_install_reason(Some("app"));
+        assert_eq!(pkg.reason, InstallReason::Dependency);
+        assert_eq!(pkg.required_by, vec!["app"]);
+
+        // Already installed explicitly: stays explicit but remembers the dependent
+        pkg.reason = InstallReason::Explicit;
+        pkg.installed = Some("1.0".to_string());
+        pkg.set_install_reason(Some("tool"));
+        assert_eq!(pkg.reason, InstallReason::Explicit);
+        assert_eq!(pkg.required_by, vec!["app", "tool"]);
+
+        pkg.clear_install_state();
+        pkg.set_install_reason(Some("lib"));
+        assert_eq!(pkg.reason, InstallReason::Explicit);
+        assert!(pkg.required_by.is_empty());
+    }
+
     #[test]
     fn test_package_supports_arch() {
         let mut pkg = Package::new("test");
@@ -5,20 +5,46 @@ use anyhow::{Context, Result};
 use chrono::{Duration, Utc};
 use native_db::*;
 use once_cell::sync::Lazy;
-use parking_lot::RwLock;
+use parking_lot::{
+    MappedRwLockReadGuard, RwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard,
+};
 use serde::{de::DeserializeOwned, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
+use std::time::Instant;
 
-use super::models::{Package, PackageKey, Repo, RepoKey, Setting, SettingKey};
+use super::index::{IndexStats, SearchIndex, SearchResults};
+use super::migrations;
+use super::models::{
+    ApprovedScript, InstallReason, Package, PackageFiles, PackageKey, Repo, RepoKey, RepoPackage,
+    SearchTerm, Setting, SettingKey, Transaction,
+};
 
 /// Static models definition - must live for 'static
 static MODELS: Lazy<Models> = Lazy::new(|| {
     let mut models = Models::new();
+    models
+        .define::<migrations::v1::Package>()
+        .expect("Failed to define legacy Package model");
+    models
+        .define::<migrations::v2::Package>()
+        .expect("Failed to define legacy Package model");
+    models
+        .define::<migrations::v3::Package>()
+        .expect("Failed to define legacy Package model");
     models
         .define::<Package>()
         .expect("Failed to define Package model");
+    models
+        .define::<migrations::v1::Repo>()
+        .expect("Failed to define legacy Repo model");
+    models
+        .define::<migrations::v2::Repo>()
+        .expect("Failed to define legacy Repo model");
+    models
+        .define::<migrations::v3::Repo>()
+        .expect("Failed to define legacy Repo model");
     models
         .define::<Repo>()
         .expect("Failed to define Repo model");
@@ -26,14 +52,47 @@ static MODELS: Lazy<Models> = Lazy::new(|| {
         .define::<Setting>()
         .expect("Failed to define Setting model");
     models
+        .define::<Transaction>()
+        .expect("Failed to define Transaction model");
+    models
+        .define::<RepoPackage>()
+        .expect("Failed to define RepoPackage model");
+    models
+        .define::<SearchTerm>()
+        .expect("Failed to define SearchTerm model");
+    models
+        .define::<ApprovedScript>()
+        .expect("Failed to define ApprovedScript model");
+    models
+        .define::<PackageFiles>()
+        .expect("Failed to define PackageFiles model");
+    models
 });
 
+/// Setting holding the search index statistics
+const SEARCH_INDEX_SETTING: &str = "search_index";
+
+/// Setting holding the pinned packages
+const PINS_SETTING: &str = "pins";
+
+/// Setting holding the preferred native backends, most preferred first
+const BACKEND_PREFERENCE_SETTING: &str = "backend_preference";
+
+/// Setting enabling the review of new and changed package scripts
+const SCRIPT_REVIEW_SETTING: &str = "script_review";
+
+/// How long to wait for another xpm process to release the database
+const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
 /// Global database instance
 static DB_INSTANCE: OnceLock<Database> = OnceLock::new();
 
 /// Database wrapper for XPM
 pub struct Database {
-    db: native_db::Database<'static>,
+    /// Open database handle (None while released for another xpm process)
+    db: RwLock<Option<native_db::Database<'static>>>,
+    /// Database file, used to reopen the handle after a release
+    path: PathBuf,
     /// In-memory cache for settings
     settings_cache: RwLock<HashMap<String, String>>,
 }
@@ -49,24 +108,104 @@ impl Database {
         Ok(DB_INSTANCE.get_or_init(|| db))
     }
 
+    /// Close the global database file if it is open
+    ///
+    /// The database is locked by whichever process has it open, so it must be
+    /// released before running package scripts that call back into xpm. It is
+    /// reopened on the next access.
+    pub fn release() {
+        if let Some(db) = DB_INSTANCE.get() {
+            *db.db.write() = None;
+        }
+    }
+
     /// Create a new database instance
     fn new() -> Result<Self> {
-        let db_path = Self::db_path()?;
+        Self::open(Self::db_path()?)
+    }
+
+    /// Open the database at a path
+    fn open(path: PathBuf) -> Result<Self> {
+        let db = Self::open_native(&path)?;
 
+        Ok(Self {
+            db: RwLock::new(Some(db)),
+            path,
+            settings_cache: RwLock::new(HashMap::new()),
+        })
+    }
+
+    fn open_native(path: &Path) -> Result<native_db::Database<'static>> {
         // Ensure parent directory exists
-        if let Some(parent) = db_path.parent() {
+        if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent).context("Failed to create database directory")?;
         }
 
-        // Create database using static models
-        let db = Builder::new()
-            .create(&MODELS, &db_path)
-            .context("Failed to create database")?;
+        // Create database using static models, waiting while another xpm
+        // process (such as a package script calling `$XPM`) holds it
+        let db = match Builder::new().create(&MODELS, path) {
+            Err(db_type::Error::RedbDatabaseError(redb::DatabaseError::DatabaseAlreadyOpen)) => {
+                Self::wait_for_lock(path)?
+            }
+            result => result.context("Failed to create database")?,
+        };
 
-        Ok(Self {
-            db,
-            settings_cache: RwLock::new(HashMap::new()),
-        })
+        // Upgrade rows stored by older versions of xpm
+        let rw = db.rw_transaction()?;
+        rw.migrate::<Package>()
+            .context("Failed to migrate package data")?;
+        rw.migrate::<Repo>()
+            .context("Failed to migrate repository data")?;
+        rw.commit()?;
+
+        Ok(db)
+    }
+
+    /// Open the database once the xpm process holding it releases it
+    ///
+    /// The wait blocks the thread, so on a multi-threaded runtime the worker
+    /// hands its other tasks over first.
+    fn wait_for_lock(path: &Path) -> Result<native_db::Database<'static>> {
+        let wait = || {
+            let started = Instant::now();
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                match Builder::new().create(&MODELS, path) {
+                    Err(db_type::Error::RedbDatabaseError(
+                        redb::DatabaseError::DatabaseAlreadyOpen,
+                    )) if started.elapsed() < LOCK_TIMEOUT => {}
+                    Err(db_type::Error::RedbDatabaseError(
+                        redb::DatabaseError::DatabaseAlreadyOpen,
+                    )) => anyhow::bail!(
+                        "The XPM database is locked by another xpm process, try again once it finishes"
+                    ),
+                    result => return result.context("Failed to create database"),
+                }
+            }
+        };
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(wait)
+            }
+            _ => wait(),
+        }
+    }
+
+    /// Get the database handle, reopening it if it was released
+    fn handle(&self) -> Result<MappedRwLockReadGuard<'_, native_db::Database<'static>>> {
+        let guard = self.db.upgradable_read();
+        let guard = if guard.is_some() {
+            RwLockUpgradableReadGuard::downgrade(guard)
+        } else {
+            let mut guard = RwLockUpgradableReadGuard::upgrade(guard);
+            *guard = Some(Self::open_native(&self.path)?);
+            RwLockWriteGuard::downgrade(guard)
+        };
+
+        Ok(RwLockReadGuard::map(guard, |db| {
+            db.as_ref().expect("database handle is open")
+        }))
     }
 
     /// Get database file path
@@ -79,14 +218,16 @@ impl Database {
 
     /// Get all packages
     pub fn get_all_packages(&self) -> Result<Vec<Package>> {
-        let r = self.db.r_transaction()?;
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
         let packages: Vec<Package> = r.scan().primary()?.all()?.filter_map(|p| p.ok()).collect();
         Ok(packages)
     }
 
     /// Get packages with a limit
     pub fn get_packages_limited(&self, limit: usize) -> Result<Vec<Package>> {
-        let r = self.db.r_transaction()?;
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
         let packages: Vec<Package> = r
             .scan()
             .primary()?
@@ -99,47 +240,88 @@ impl Database {
 
     /// Find package by name
     pub fn find_package_by_name(&self, name: &str) -> Result<Option<Package>> {
-        let r = self.db.r_transaction()?;
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
         let pkg = r.get().secondary(PackageKey::name, name.to_string())?;
         Ok(pkg)
     }
 
-    /// Search packages by term (searches name, desc, title)
-    pub fn search_packages(&self, terms: &[String], limit: usize) -> Result<Vec<Package>> {
-        let r = self.db.r_transaction()?;
-        let terms_lower: Vec<String> = terms.iter().map(|t| t.to_lowercase()).collect();
+    /// Search packages by term (searches name, title and desc)
+    ///
+    /// Results are ranked by the full-text index, which is built on the first
+    /// search if `refresh` has not built it yet.
+    pub fn search_packages(&self, terms: &[String], limit: usize) -> Result<SearchResults> {
+        let stats: Option<IndexStats> = self.get_setting(SEARCH_INDEX_SETTING)?;
+        let index = match stats {
+            Some(stats) => {
+                let db = self.handle()?;
+                let r = db.r_transaction()?;
+                let terms: Vec<SearchTerm> =
+                    r.scan().primary()?.all()?.filter_map(|t| t.ok()).collect();
+                SearchIndex::from_parts(terms, stats)
+            }
+            None => self.rebuild_search_index()?,
+        };
 
-        let mut results: Vec<Package> = r
-            .scan()
-            .primary()?
-            .all()?
-            .filter_map(|p| p.ok())
-            .filter(|pkg: &Package| {
-                terms_lower.iter().any(|term| {
-                    pkg.name.to_lowercase().contains(term)
-                        || pkg
-                            .desc
-                            .as_ref()
-                            .map(|d| d.to_lowercase().contains(term))
-                            .unwrap_or(false)
-                        || pkg
-                            .title
-                            .as_ref()
-                            .map(|t| t.to_lowercase().contains(term))
-                            .unwrap_or(false)
-                })
-            })
-            .take(limit)
-            .collect();
+        let matches = index.search(terms);
+
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let mut packages = Vec::new();
+        for (id, _) in matches.scores {
+            if packages.len() >= limit {
+                break;
+            }
+            // Packages removed since the last refresh are skipped
+            if let Some(pkg) = r.get().primary::<Package>(id)? {
+                packages.push(pkg);
+            }
+        }
 
-        // Sort by name
-        results.sort_by(|a, b| a.name.cmp(&b.name));
-        Ok(results)
+        Ok(SearchResults {
+            packages,
+            suggestion: matches.suggestion,
+        })
+    }
+
+    /// Rebuild the full-text search index from the stored packages
+    pub fn rebuild_search_index(&self) -> Result<SearchIndex> {
+        let index = SearchIndex::build(&self.get_all_packages()?);
+        let (terms, stats) = index.clone().into_parts();
+
+        {
+            let db = self.handle()?;
+            let rw = db.rw_transaction()?;
+            let stale: Vec<SearchTerm> =
+                rw.scan().primary()?.all()?.filter_map(|t| t.ok()).collect();
+            for term in stale {
+                rw.remove(term)?;
+            }
+            for term in terms {
+                rw.insert(term)?;
+            }
+            rw.commit()?;
+        }
+
+        self.set_setting(SEARCH_INDEX_SETTING, &stats)?;
+        Ok(index)
+    }
+
+    /// Find the packages that provide a command
+    pub fn find_providers(&self, command: &str) -> Result<Vec<Package>> {
+        let mut providers: Vec<Package> = self
+            .get_all_packages()?
+            .into_iter()
+            .filter(|pkg| pkg.provides_command(command))
+            .collect();
+        providers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(providers)
     }
 
     /// Get installed packages
     pub fn get_installed_packages(&self) -> Result<Vec<Package>> {
-        let r = self.db.r_transaction()?;
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
         let installed: Vec<Package> = r
             .scan()
             .primary()?
@@ -151,9 +333,54 @@ impl Database {
         Ok(installed)
     }
 
+    /// Get installed packages that depend on a package
+    ///
+    /// A package is a dependent if it declares the package in `xDEPENDS` or
+    /// installed it from its own script.
+    pub fn find_dependents(&self, name: &str) -> Result<Vec<Package>> {
+        let installed = self.get_installed_packages()?;
+        let Some(package) = installed.iter().find(|p| p.name == name).cloned() else {
+            return Ok(Vec::new());
+        };
+
+        let dependents = installed
+            .into_iter()
+            .filter(|p| depends_on(p, &package))
+            .collect();
+
+        Ok(dependents)
+    }
+
+    /// Get automatically installed packages that nothing depends on anymore
+    ///
+    /// Removing a package can orphan its own dependencies, so the result covers
+    /// the whole chain in an order that is safe to remove.
+    pub fn find_orphans(&self) -> Result<Vec<Package>> {
+        let mut installed = self.get_installed_packages()?;
+        let mut orphans = Vec::new();
+
+        loop {
+            let (found, remaining): (Vec<Package>, Vec<Package>) =
+                installed.iter().cloned().partition(|pkg| {
+                    pkg.reason == InstallReason::Dependency
+                        && !installed.iter().any(|other| depends_on(other, pkg))
+                });
+
+            if found.is_empty() {
+                break;
+            }
+
+            orphans.extend(found);
+            installed = remaining;
+        }
+
+        Ok(orphans)
+    }
+
     /// Insert or update a package
     pub fn upsert_package(&self, package: Package) -> Result<Package> {
-        let rw = self.db.rw_transaction()?;
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
 
         // Check if package exists
         let existing: Option<Package> =
@@ -169,7 +396,7 @@ impl Database {
             rw.insert(package.clone())?;
             rw.commit()?;
             // Get the inserted package with ID
-            let r = self.db.r_transaction()?;
+            let r = db.r_transaction()?;
             let inserted: Option<Package> =
                 r.get().secondary(PackageKey::name, package.name.clone())?;
             Ok(inserted.unwrap_or(package))
@@ -178,7 +405,8 @@ impl Database {
 
     /// Delete packages that are not installed
     pub fn delete_uninstalled_packages(&self) -> Result<usize> {
-        let rw = self.db.rw_transaction()?;
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
         let uninstalled_packages: Vec<Package> = rw
             .scan()
             .primary()?
@@ -201,21 +429,32 @@ impl Database {
 
     /// Get all repositories
     pub fn get_all_repos(&self) -> Result<Vec<Repo>> {
-        let r = self.db.r_transaction()?;
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
         let repos: Vec<Repo> = r.scan().primary()?.all()?.filter_map(|p| p.ok()).collect();
         Ok(repos)
     }
 
     /// Find repo by URL
     pub fn find_repo_by_url(&self, url: &str) -> Result<Option<Repo>> {
-        let r = self.db.r_transaction()?;
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
         let repo = r.get().secondary(RepoKey::url, url.to_string())?;
         Ok(repo)
     }
 
+    /// Find a repository by ID
+    pub fn find_repo(&self, id: u64) -> Result<Option<Repo>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let repo = r.get().primary(id)?;
+        Ok(repo)
+    }
+
     /// Insert or update a repository
     pub fn upsert_repo(&self, repo: Repo) -> Result<Repo> {
-        let rw = self.db.rw_transaction()?;
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
 
         let existing: Option<Repo> = rw.get().secondary(RepoKey::url, repo.url.clone())?;
 
@@ -226,21 +465,39 @@ impl Database {
             rw.commit()?;
             Ok(updated)
         } else {
-            rw.insert(repo.clone())?;
+            // New repositories take the next free ID
+            let last: Option<Repo> = rw
+                .scan()
+                .primary()?
+                .all()?
+                .filter_map(|r| r.ok())
+                .next_back();
+            let mut inserted = repo;
+            inserted.id = last.map(|r| r.id + 1).unwrap_or(1);
+            rw.insert(inserted.clone())?;
             rw.commit()?;
-            let r = self.db.r_transaction()?;
-            let inserted: Option<Repo> = r.get().secondary(RepoKey::url, repo.url.clone())?;
-            Ok(inserted.unwrap_or(repo))
+            Ok(inserted)
         }
     }
 
     /// Delete a repository by URL
     pub fn delete_repo(&self, url: &str) -> Result<bool> {
-        let rw = self.db.rw_transaction()?;
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
 
         let repo: Option<Repo> = rw.get().secondary(RepoKey::url, url.to_string())?;
 
         if let Some(repo) = repo {
+            let shipped: Vec<RepoPackage> = rw
+                .scan()
+                .primary()?
+                .all()?
+                .filter_map(|p| p.ok())
+                .filter(|p: &RepoPackage| p.repo_id == repo.id)
+                .collect();
+            for pkg in shipped {
+                rw.remove(pkg)?;
+            }
             rw.remove(repo)?;
             rw.commit()?;
             Ok(true)
@@ -249,6 +506,188 @@ impl Database {
         }
     }
 
+    // ==================== Repo Package Operations ====================
+
+    /// Get the packages every repository ships
+    pub fn get_repo_packages(&self) -> Result<Vec<RepoPackage>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let packages: Vec<RepoPackage> =
+            r.scan().primary()?.all()?.filter_map(|p| p.ok()).collect();
+        Ok(packages)
+    }
+
+    /// Find the repositories that ship a package
+    pub fn find_repo_packages(&self, name: &str) -> Result<Vec<RepoPackage>> {
+        Ok(self
+            .get_repo_packages()?
+            .into_iter()
+            .filter(|p| p.name == name)
+            .collect())
+    }
+
+    /// Replace the packages a repository ships
+    pub fn replace_repo_packages(&self, repo_id: u64, packages: Vec<Package>) -> Result<usize> {
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
+
+        let stale: Vec<RepoPackage> = rw
+            .scan()
+            .primary()?
+            .all()?
+            .filter_map(|p| p.ok())
+            .filter(|p: &RepoPackage| p.repo_id == repo_id)
+            .collect();
+        for pkg in stale {
+            rw.remove(pkg)?;
+        }
+
+        let count = packages.len();
+        for pkg in packages {
+            rw.insert(RepoPackage::new(repo_id, pkg))?;
+        }
+
+        rw.commit()?;
+        Ok(count)
+    }
+
+    // ==================== Script Review Operations ====================
+
+    /// Check whether new and changed scripts must be approved before they run
+    pub fn script_review_enabled(&self) -> Result<bool> {
+        self.get_setting_or(SCRIPT_REVIEW_SETTING, false)
+    }
+
+    /// Enable or disable script review
+    pub fn set_script_review(&self, enabled: bool) -> Result<()> {
+        self.set_setting(SCRIPT_REVIEW_SETTING, &enabled)
+    }
+
+    /// Get the script version last approved for a package
+    pub fn get_approved_script(&self, name: &str) -> Result<Option<ApprovedScript>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        Ok(r.get().primary(name.to_string())?)
+    }
+
+    /// Get every approved script, sorted by package name
+    pub fn get_approved_scripts(&self) -> Result<Vec<ApprovedScript>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let scripts: Vec<ApprovedScript> =
+            r.scan().primary()?.all()?.filter_map(|s| s.ok()).collect();
+        Ok(scripts)
+    }
+
+    /// Record the approval of a script, replacing the previous one
+    pub fn approve_script(&self, approved: ApprovedScript) -> Result<()> {
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
+        rw.upsert(approved)?;
+        rw.commit()?;
+        Ok(())
+    }
+
+    // ==================== File Ownership Operations ====================
+
+    /// Get the files recorded for a package
+    pub fn get_package_files(&self, name: &str) -> Result<Option<PackageFiles>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        Ok(r.get().primary(name.to_string())?)
+    }
+
+    /// Get the files recorded for every package
+    pub fn get_all_package_files(&self) -> Result<Vec<PackageFiles>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let files: Vec<PackageFiles> = r.scan().primary()?.all()?.filter_map(|f| f.ok()).collect();
+        Ok(files)
+    }
+
+    /// Record the files of a package, replacing the previous record
+    pub fn record_package_files(&self, files: PackageFiles) -> Result<()> {
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
+        rw.upsert(files)?;
+        rw.commit()?;
+        Ok(())
+    }
+
+    /// Forget the files of a package
+    pub fn delete_package_files(&self, name: &str) -> Result<()> {
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
+        let recorded: Option<PackageFiles> = rw.get().primary(name.to_string())?;
+        if let Some(recorded) = recorded {
+            rw.remove(recorded)?;
+        }
+        rw.commit()?;
+        Ok(())
+    }
+
+    /// Find the packages that recorded a file, or files under a directory,
+    /// sorted by name
+    ///
+    /// Files created while several scripts ran at once are recorded for each
+    /// of them, so a file can have more than one owner.
+    pub fn find_file_owners(&self, path: &Path) -> Result<Vec<String>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let owners = r
+            .scan()
+            .primary::<PackageFiles>()?
+            .all()?
+            .filter_map(|f| f.ok())
+            .filter(|f| f.files.iter().any(|file| Path::new(file).starts_with(path)))
+            .map(|f| f.name)
+            .collect();
+        Ok(owners)
+    }
+
+    // ==================== Transaction Operations ====================
+
+    /// Record a transaction, assigning the next sequential ID
+    pub fn record_transaction(&self, transaction: Transaction) -> Result<Transaction> {
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
+
+        let last: Option<Transaction> = rw
+            .scan()
+            .primary()?
+            .all()?
+            .filter_map(|t| t.ok())
+            .next_back();
+
+        let mut recorded = transaction;
+        recorded.id = last.map(|t| t.id + 1).unwrap_or(1);
+        rw.insert(recorded.clone())?;
+        rw.commit()?;
+        Ok(recorded)
+    }
+
+    /// Get all transactions, newest first
+    pub fn get_transactions(&self) -> Result<Vec<Transaction>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let transactions: Vec<Transaction> = r
+            .scan()
+            .primary()?
+            .all()?
+            .rev()
+            .filter_map(|t| t.ok())
+            .collect();
+        Ok(transactions)
+    }
+
+    /// Find transaction by ID
+    pub fn find_transaction(&self, id: u64) -> Result<Option<Transaction>> {
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
+        let transaction = r.get().primary(id)?;
+        Ok(transaction)
+    }
+
     // ==================== Settings Operations ====================
 
     /// Get a setting value
@@ -265,7 +704,8 @@ impl Database {
         }
 
         // Query database
-        let r = self.db.r_transaction()?;
+        let db = self.handle()?;
+        let r = db.r_transaction()?;
         let setting: Option<Setting> = r.get().secondary(SettingKey::key, key_lower.clone())?;
 
         if let Some(setting) = setting {
@@ -311,7 +751,8 @@ impl Database {
         let value_json = serde_json::to_string(value)?;
         let expires_at = expires_in.map(|d| Utc::now() + d);
 
-        let rw = self.db.rw_transaction()?;
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
 
         // Remove existing if present
         let existing: Option<Setting> = rw.get().secondary(SettingKey::key, key_lower.clone())?;
@@ -345,7 +786,8 @@ impl Database {
             cache.remove(&key_lower);
         }
 
-        let rw = self.db.rw_transaction()?;
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
         let setting: Option<Setting> = rw.get().secondary(SettingKey::key, key_lower)?;
 
         if let Some(setting) = setting {
@@ -359,7 +801,8 @@ impl Database {
 
     /// Delete all expired settings
     pub fn delete_expired_settings(&self) -> Result<usize> {
-        let rw = self.db.rw_transaction()?;
+        let db = self.handle()?;
+        let rw = db.rw_transaction()?;
         let expired_settings: Vec<Setting> = rw
             .scan()
             .primary()?
@@ -382,11 +825,64 @@ impl Database {
         rw.commit()?;
         Ok(deleted)
     }
+
+    // ==================== Pin Operations ====================
+
+    /// Get all pinned packages with the version each is held at
+    pub fn get_pins(&self) -> Result<BTreeMap<String, String>> {
+        self.get_setting_or(PINS_SETTING, BTreeMap::new())
+    }
+
+    /// Get the version a package is pinned at, if any
+    pub fn pinned_version(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.get_pins()?.remove(name))
+    }
+
+    /// Pin a package at a version, replacing any previous pin
+    pub fn pin_package(&self, name: &str, version: &str) -> Result<()> {
+        let mut pins = self.get_pins()?;
+        pins.insert(name.to_string(), version.to_string());
+        self.set_setting(PINS_SETTING, &pins)
+    }
+
+    /// Remove a package pin, returning whether it was pinned
+    pub fn unpin_package(&self, name: &str) -> Result<bool> {
+        let mut pins = self.get_pins()?;
+        if pins.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.set_setting(PINS_SETTING, &pins)?;
+        Ok(true)
+    }
+
+    // ==================== Backend Preference Operations ====================
+
+    /// Get the preferred native backends, most preferred first
+    pub fn get_backend_preference(&self) -> Result<Vec<String>> {
+        self.get_setting_or(BACKEND_PREFERENCE_SETTING, Vec::new())
+    }
+
+    /// Set the preferred native backends, clearing the preference when empty
+    pub fn set_backend_preference(&self, backends: &[String]) -> Result<()> {
+        if backends.is_empty() {
+            self.delete_setting(BACKEND_PREFERENCE_SETTING)?;
+            return Ok(());
+        }
+        self.set_setting(BACKEND_PREFERENCE_SETTING, &backends)
+    }
+}
+
+/// Check whether `package` needs `dependency` to stay installed
+pub(crate) fn depends_on(package: &Package, dependency: &Package) -> bool {
+    package.name != dependency.name
+        && (package.depends.contains(&dependency.name)
+            || dependency.required_by.contains(&package.name))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::models::Operation;
     use tempfile::TempDir;
 
     fn create_test_db() -> Result<native_db::Database<'static>> {
@@ -396,10 +892,48 @@ mod tests {
         // Leak the temp_dir to keep it alive
         std::mem::forget(temp_dir);
 
-        let db = Builder::new().create(&*MODELS, &db_path)?;
+        let db = Builder::new().create(&MODELS, &db_path)?;
         Ok(db)
     }
 
+    fn test_database() -> Result<Database> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test.redb");
+
+        // Leak the temp_dir to keep it alive
+        std::mem::forget(temp_dir);
+
+        Database::open(db_path)
+    }
+
+    #[test]
+    fn test_release_and_reopen() -> Result<()> {
+        let db = test_database()?;
+        db.upsert_package(Package::new("vim"))?;
+
+        *db.db.write() = None;
+        // Another process can open the file while it is released
+        drop(Database::open_native(&db.path)?);
+
+        assert!(db.find_package_by_name("vim")?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_wait_for_lock() -> Result<()> {
+        let db = test_database()?;
+        let path = db.path.clone();
+
+        // Opening waits until the holder lets go of the file
+        let holder = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            drop(db);
+        });
+        drop(Database::open_native(&path)?);
+        holder.join().unwrap();
+        Ok(())
+    }
+
     #[test]
     fn test_models_defined() {
         // Just verify models can be accessed
@@ -408,11 +942,7 @@ mod tests {
 
     #[test]
     fn test_search_packages() -> Result<()> {
-        let db_instance = create_test_db()?;
-        let db = Database {
-            db: db_instance,
-            settings_cache: RwLock::new(HashMap::new()),
-        };
+        let db = test_database()?;
 
         // Insert some packages
         let p1 = Package {
@@ -436,17 +966,281 @@ mod tests {
         };
         db.upsert_package(p3)?;
 
-        // Search for "vim"
-        let results = db.search_packages(&["vim".to_string()], 10)?;
+        // Search for "vim" (exact name match first)
+        let results = db.search_packages(&["vim".to_string()], 10)?.packages;
         assert_eq!(results.len(), 2);
-        assert_eq!(results[0].name, "neovim");
-        assert_eq!(results[1].name, "vim");
+        assert_eq!(results[0].name, "vim");
+        assert_eq!(results[1].name, "neovim");
+
+        // The limit applies after ranking
+        let results = db.search_packages(&["vim".to_string()], 1)?.packages;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "vim");
 
         // Search for "text editor"
         let results = db.search_packages(&["text".to_string(), "editor".to_string()], 10)?;
-        assert_eq!(results.len(), 2); // neovim and emacs
-        assert!(results.iter().any(|p| p.name == "neovim"));
-        assert!(results.iter().any(|p| p.name == "emacs"));
+        assert_eq!(results.packages.len(), 2); // neovim and emacs
+        assert!(results.packages.iter().any(|p| p.name == "neovim"));
+        assert!(results.packages.iter().any(|p| p.name == "emacs"));
+
+        // Typos are tolerated and corrected
+        db.rebuild_search_index()?;
+        let results = db.search_packages(&["emcas".to_string()], 10)?;
+        assert_eq!(results.packages[0].name, "emacs");
+        assert_eq!(results.suggestion.as_deref(), Some("emacs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_providers() -> Result<()> {
+        let db = test_database()?;
+        db.upsert_package(Package {
+            provides: vec!["rg".to_string()],
+            ..Package::new("ripgrep")
+        })?;
+        db.upsert_package(Package {
+            provides: vec!["rga".to_string()],
+            ..Package::new("ripgrep-all")
+        })?;
+
+        let providers = db.find_providers("rg")?;
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].name, "ripgrep");
+        assert!(db.find_providers("fd")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_v1_packages() -> Result<()> {
+        let db = create_test_db()?;
+
+        let old: migrations::v1::Package =
+            migrations::v2::Package::from(migrations::v3::Package::from(Package {
+                installed: Some("1.0.0".to_string()),
+                ..Package::new("vim")
+            }))
+            .into();
+        let rw = db.rw_transaction()?;
+        rw.insert(old)?;
+        rw.commit()?;
+
+        let rw = db.rw_transaction()?;
+        rw.migrate::<Package>()?;
+        rw.commit()?;
+
+        let r = db.r_transaction()?;
+        let migrated: Option<Package> = r.get().secondary(PackageKey::name, "vim".to_string())?;
+        let migrated = migrated.expect("package should survive migration");
+        assert_eq!(migrated.installed, Some("1.0.0".to_string()));
+        assert!(migrated.depends.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_dependents() -> Result<()> {
+        let db = test_database()?;
+
+        let installed = Some("1.0".to_string());
+        db.upsert_package(Package {
+            installed: installed.clone(),
+            required_by: vec!["tool".to_string(), "gone".to_string()],
+            ..Package::new("lib")
+        })?;
+        db.upsert_package(Package {
+            installed: installed.clone(),
+            depends: vec!["lib".to_string()],
+            ..Package::new("app")
+        })?;
+        db.upsert_package(Package {
+            installed,
+            ..Package::new("tool")
+        })?;
+        db.upsert_package(Package {
+            depends: vec!["lib".to_string()],
+            ..Package::new("uninstalled")
+        })?;
+
+        let mut names: Vec<_> = db
+            .find_dependents("lib")?
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["app", "tool"]);
+        assert!(db.find_dependents("app")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_orphans() -> Result<()> {
+        let db = test_database()?;
+
+        let installed = Some("1.0".to_string());
+        let dependency = |name: &str, required_by: &[&str]| Package {
+            installed: installed.clone(),
+            reason: InstallReason::Dependency,
+            required_by: required_by.iter().map(|s| s.to_string()).collect(),
+            ..Package::new(name)
+        };
+
+        // "app" was removed: lib is orphaned, and base only served lib
+        db.upsert_package(dependency("lib", &["app"]))?;
+        db.upsert_package(dependency("base", &["lib"]))?;
+        db.upsert_package(dependency("shared", &["tool"]))?;
+        db.upsert_package(Package {
+            installed: installed.clone(),
+            ..Package::new("tool")
+        })?;
+
+        let names: Vec<_> = db.find_orphans()?.into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["lib", "base"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_transaction() -> Result<()> {
+        let db = test_database()?;
+
+        let first =
+            db.record_transaction(Transaction::new(Operation::Install).with_package("vim"))?;
+        let second =
+            db.record_transaction(Transaction::new(Operation::Remove).with_package("vim"))?;
+        assert_eq!(first.id, 1);
+        assert_eq!(second.id, 2);
+
+        // Newest first
+        let transactions = db.get_transactions()?;
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].operation, Operation::Remove);
+
+        let found = db.find_transaction(1)?.unwrap();
+        assert_eq!(found.operation, Operation::Install);
+        assert!(db.find_transaction(3)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upsert_repos() -> Result<()> {
+        let db = test_database()?;
+
+        let first = db.upsert_repo(Repo::new("https://example.com/a.git"))?;
+        let second = db.upsert_repo(Repo::new("https://example.com/b.git"))?;
+        assert_eq!(first.id, 1);
+        assert_eq!(second.id, 2);
+
+        // Updating keeps the ID
+        let updated = db.upsert_repo(Repo::new("https://example.com/a.git").with_priority(5))?;
+        assert_eq!(updated.id, 1);
+        assert_eq!(db.get_all_repos()?.len(), 2);
+
+        db.replace_repo_packages(2, vec![Package::new("vim")])?;
+        db.replace_repo_packages(1, vec![Package::new("vim"), Package::new("fd")])?;
+        assert_eq!(db.find_repo_packages("vim")?.len(), 2);
+
+        // Deleting a repository forgets its packages
+        db.delete_repo("https://example.com/a.git")?;
+        let left = db.get_repo_packages()?;
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].repo_id, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pins() -> Result<()> {
+        let db = test_database()?;
+        assert!(db.get_pins()?.is_empty());
+
+        db.pin_package("vim", "9.0")?;
+        db.pin_package("git", "2.40")?;
+        db.pin_package("vim", "9.1")?;
+        assert_eq!(db.pinned_version("vim")?.as_deref(), Some("9.1"));
+        assert_eq!(db.get_pins()?.len(), 2);
+
+        assert!(db.unpin_package("vim")?);
+        assert!(!db.unpin_package("vim")?);
+        assert!(db.pinned_version("vim")?.is_none());
+        assert_eq!(db.pinned_version("git")?.as_deref(), Some("2.40"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_approved_scripts() -> Result<()> {
+        let db = test_database()?;
+        assert!(!db.script_review_enabled()?);
+        db.set_script_review(true)?;
+        assert!(db.script_review_enabled()?);
+
+        assert!(db.get_approved_script("vim")?.is_none());
+        db.approve_script(ApprovedScript::new("vim", "aaa", "install_any() { :; }"))?;
+        db.approve_script(ApprovedScript::new("git", "bbb", ""))?;
+        db.approve_script(ApprovedScript::new("vim", "ccc", "install_any() { true; }"))?;
+
+        let vim = db.get_approved_script("vim")?.unwrap();
+        assert_eq!(vim.sha256, "ccc");
+        assert_eq!(vim.content, "install_any() { true; }");
+        let names: Vec<_> = db
+            .get_approved_scripts()?
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["git", "vim"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_files() -> Result<()> {
+        let db = test_database()?;
+        assert!(db.get_package_files("app")?.is_none());
+
+        db.record_package_files(PackageFiles::new(
+            "app",
+            vec![
+                "/opt/app/bin/app".to_string(),
+                "/opt/app/LICENSE".to_string(),
+            ],
+        ))?;
+        db.record_package_files(PackageFiles::new(
+            "lib",
+            vec!["/opt/app/LICENSE".to_string()],
+        ))?;
+
+        let app = db.get_package_files("app")?.unwrap();
+        assert_eq!(app.files, vec!["/opt/app/LICENSE", "/opt/app/bin/app"]);
+        let owners = |path: &str| db.find_file_owners(Path::new(path));
+        assert_eq!(owners("/opt/app/LICENSE")?, vec!["app", "lib"]);
+        assert_eq!(owners("/opt/app/bin/app")?, vec!["app"]);
+        assert_eq!(owners("/opt/app/bin")?, vec!["app"]);
+        assert!(owners("/opt/app/bin/ap")?.is_empty());
+
+        assert_eq!(db.get_all_package_files()?.len(), 2);
+        db.delete_package_files("app")?;
+        db.delete_package_files("missing")?;
+        assert!(db.get_package_files("app")?.is_none());
+        assert_eq!(owners("/opt/app/LICENSE")?, vec!["lib"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backend_preference() -> Result<()> {
+        let db = test_database()?;
+        assert!(db.get_backend_preference()?.is_empty());
+
+        let preference = vec!["flatpak".to_string(), "apt".to_string()];
+        db.set_backend_preference(&preference)?;
+        assert_eq!(db.get_backend_preference()?, preference);
+
+        db.set_backend_preference(&[])?;
+        assert!(db.get_backend_preference()?.is_empty());
 
         Ok(())
     }
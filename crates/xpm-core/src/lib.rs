@@ -4,14 +4,16 @@
 //! It includes database models, OS abstractions, package manager integrations, and utilities.
 
 pub mod db;
+pub mod manifest;
 pub mod native_pm;
 pub mod os;
 pub mod repo;
+pub mod resolver;
 pub mod script;
 pub mod utils;
 
 // Re-export commonly used types
-pub use db::{Database, Package, Repo, Setting};
+pub use db::{Database, Package, Repo, Setting, Transaction};
 pub use native_pm::{NativePackage, NativePackageManager};
 pub use os::{get_architecture, get_os_info, Architecture, Executable, OsInfo, OsType, XpmDirs};
 pub use utils::logger::Logger;
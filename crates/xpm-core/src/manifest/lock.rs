@@ -0,0 +1,236 @@
+//! Lockfiles recording exactly where every installed package came from
+//!
+//! `xpm.lock` lists each installed package with its version, method and
+//! channel and, for packages installed from a script, the repository commit
+//! and the SHA-256 of the script, so another machine can install the very same
+//! scripts after the repository has moved on.
+
+use crate::db::{Package, Repo};
+use crate::native_pm::NativeMode;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Default lockfile name
+pub const LOCKFILE: &str = "xpm.lock";
+
+/// Exact sources of the installed packages
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    /// Locked packages, sorted by name
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// Where an installed package came from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedPackage {
+    pub name: String,
+
+    /// Installed version
+    pub version: String,
+
+    /// Installation method, or the native package manager for native packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+
+    /// Installed through a native package manager
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub native: bool,
+
+    /// Repository URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+
+    /// Repository commit the script was taken from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+
+    /// Script path inside the repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+
+    /// SHA-256 of the script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl LockedPackage {
+    /// Check whether the script source is known well enough to reproduce it
+    pub fn is_reproducible(&self) -> bool {
+        self.native
+            || (self.repo.is_some()
+                && self.commit.is_some()
+                && self.script.is_some()
+                && self.sha256.is_some())
+    }
+
+    /// Get the sources a native package is installed from
+    ///
+    /// The locked package manager, or any found on this machine when the
+    /// lockfile does not name one.
+    pub fn native_mode(&self) -> NativeMode {
+        match &self.method {
+            Some(method) => NativeMode::Sources(vec![method.clone()]),
+            None => NativeMode::Only,
+        }
+    }
+}
+
+impl FromStr for Lockfile {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s)
+    }
+}
+
+impl Lockfile {
+    /// Read a lockfile
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read lockfile {}", path.display()))?;
+        content
+            .parse()
+            .with_context(|| format!("Invalid lockfile {}", path.display()))
+    }
+
+    /// Serialize the lockfile to TOML
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Find the entry of a package
+    pub fn find(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+
+    /// Record every installed package, dependencies included
+    pub fn from_installed(installed: &[Package], repos: &[Repo]) -> Self {
+        let mut packages: Vec<LockedPackage> = installed
+            .iter()
+            .filter_map(|pkg| {
+                let version = pkg.installed.clone()?;
+                if pkg.is_native {
+                    return Some(LockedPackage {
+                        name: pkg.name.clone(),
+                        version,
+                        method: pkg.method.clone(),
+                        native: true,
+                        ..Default::default()
+                    });
+                }
+
+                let repo = pkg.repo_id.and_then(|id| repos.iter().find(|r| r.id == id));
+                let script = match (
+                    pkg.script.as_deref(),
+                    repo.and_then(|r| r.local_path.as_deref()),
+                ) {
+                    (Some(script), Some(root)) => Path::new(script)
+                        .strip_prefix(root)
+                        .ok()
+                        .map(|p| p.to_string_lossy().to_string()),
+                    _ => None,
+                };
+
+                Some(LockedPackage {
+                    name: pkg.name.clone(),
+                    version,
+                    method: pkg.method.clone(),
+                    channel: pkg.channel.clone(),
+                    native: false,
+                    repo: repo.map(|r| r.url.clone()),
+                    commit: pkg.repo_commit.clone(),
+                    script,
+                    sha256: pkg.script_sha256.clone(),
+                })
+            })
+            .collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self { packages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile_from_installed() {
+        let mut repo = Repo::new("https://example.com/repo.git");
+        repo.id = 7;
+        repo.local_path = Some("/repos/example".to_string());
+
+        let app = Package {
+            installed: Some("1.0".to_string()),
+            method: Some("any".to_string()),
+            script: Some("/repos/example/app/app.bash".to_string()),
+            repo_id: Some(7),
+            repo_commit: Some("abc123".to_string()),
+            script_sha256: Some("deadbeef".to_string()),
+            ..Package::new("app")
+        };
+        let old = Package {
+            installed: Some("0.1".to_string()),
+            script: Some("/repos/example/old/old.bash".to_string()),
+            repo_id: Some(7),
+            ..Package::new("old")
+        };
+        let native = Package {
+            installed: Some("14.1".to_string()),
+            method: Some("apt".to_string()),
+            is_native: true,
+            ..Package::new("ripgrep")
+        };
+        let available = Package::new("available");
+
+        let lock = Lockfile::from_installed(&[native, app, old, available], &[repo]);
+        let names: Vec<_> = lock.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["app", "old", "ripgrep"]);
+
+        let app = lock.find("app").unwrap();
+        assert_eq!(app.script.as_deref(), Some("app/app.bash"));
+        assert_eq!(app.repo.as_deref(), Some("https://example.com/repo.git"));
+        assert!(app.is_reproducible());
+        // Installed before sources were recorded
+        assert!(!lock.find("old").unwrap().is_reproducible());
+        assert!(lock.find("ripgrep").unwrap().is_reproducible());
+
+        let parsed: Lockfile = lock.to_toml().unwrap().parse().unwrap();
+        assert_eq!(parsed, lock);
+    }
+
+    #[test]
+    fn test_native_mode() {
+        let lock: Lockfile = r#"
+            [[package]]
+            name = "ripgrep"
+            version = "14.1"
+            method = "apt"
+            native = true
+
+            [[package]]
+            name = "fd"
+            version = "9.0"
+            native = true
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            lock.find("ripgrep").unwrap().native_mode(),
+            NativeMode::Sources(vec!["apt".to_string()])
+        );
+        // Without a recorded package manager, any found here will do
+        let fd = lock.find("fd").unwrap();
+        assert!(fd.is_reproducible());
+        assert_eq!(fd.native_mode(), NativeMode::Only);
+    }
+}
@@ -0,0 +1,363 @@
+//! Declarative manifests describing the packages and repositories of a machine
+//!
+//! A manifest is a TOML file such as:
+//!
+//! ```toml
+//! repos = ["https://github.com/verseles/xpm-popular.git"]
+//!
+//! [packages.neovim]
+//! method = "appimage"
+//!
+//! [packages.ripgrep]
+//! from = "apt"
+//! version = "14.1.0"
+//! ```
+//!
+//! `version` pins the package. Applying a manifest installs what is missing,
+//! reinstalls packages whose method, channel or source differ, removes
+//! explicitly installed packages it does not list and adds missing repos.
+
+pub mod lock;
+
+pub use lock::{LockedPackage, Lockfile, LOCKFILE};
+
+use crate::db::{depends_on, InstallReason, Package, Repo};
+use crate::utils::version::compare_versions;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Packages and repositories a machine should have
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Repository URLs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub repos: Vec<String>,
+
+    /// Explicitly installed packages, by name
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageSpec>,
+}
+
+/// How a manifest package should be installed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackageSpec {
+    /// Installation method of the package script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+
+    /// Release channel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+
+    /// Native package manager to install from instead of an XPM script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    /// Version to pin the package at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// Changes needed to bring a machine in line with a manifest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApplyPlan {
+    /// Repositories to add
+    pub add_repos: Vec<String>,
+    /// Packages to install or reinstall
+    pub install: Vec<(String, PackageSpec)>,
+    /// Explicitly installed packages the manifest does not list
+    pub remove: Vec<String>,
+    /// Packages the manifest does not list that installed packages still need,
+    /// kept as dependencies instead of being removed
+    pub unmark: Vec<String>,
+    /// Listed packages installed only as dependencies, to record as
+    /// explicitly installed so autoremove keeps them
+    pub mark_explicit: Vec<String>,
+    /// Pins to set, as package and version
+    pub pin: Vec<(String, String)>,
+    /// Pins to drop
+    pub unpin: Vec<String>,
+}
+
+impl ApplyPlan {
+    /// Check whether the machine already matches the manifest
+    pub fn is_empty(&self) -> bool {
+        self.add_repos.is_empty()
+            && self.install.is_empty()
+            && self.remove.is_empty()
+            && self.unmark.is_empty()
+            && self.mark_explicit.is_empty()
+            && self.pin.is_empty()
+            && self.unpin.is_empty()
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s)
+    }
+}
+
+impl Manifest {
+    /// Read a manifest file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+        content
+            .parse()
+            .with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+
+    /// Serialize the manifest to TOML
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Describe the current machine
+    ///
+    /// Only explicitly installed packages are listed, their dependencies come
+    /// back with them.
+    pub fn from_installed(
+        installed: &[Package],
+        pins: &BTreeMap<String, String>,
+        repos: &[Repo],
+    ) -> Self {
+        let packages = installed
+            .iter()
+            .filter(|p| p.is_installed() && p.reason == InstallReason::Explicit)
+            .map(|p| {
+                let spec = if p.is_native {
+                    PackageSpec {
+                        from: p.method.clone(),
+                        ..Default::default()
+                    }
+                } else {
+                    PackageSpec {
+                        method: p.method.clone(),
+                        channel: p.channel.clone(),
+                        ..Default::default()
+                    }
+                };
+                let version = pins.get(&p.name).cloned();
+                (p.name.clone(), PackageSpec { version, ..spec })
+            })
+            .collect();
+
+        Self {
+            repos: repos.iter().map(|r| r.url.clone()).collect(),
+            packages,
+        }
+    }
+
+    /// Work out what has to change for the machine to match the manifest
+    ///
+    /// Repositories are only ever added, and only explicitly installed
+    /// packages are removed; orphaned dependencies are left to autoremove.
+    pub fn plan(
+        &self,
+        installed: &[Package],
+        pins: &BTreeMap<String, String>,
+        repos: &[Repo],
+    ) -> ApplyPlan {
+        let mut plan = ApplyPlan {
+            add_repos: self
+                .repos
+                .iter()
+                .filter(|url| !repos.iter().any(|r| &r.url == *url))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+
+        for (name, spec) in &self.packages {
+            let current = installed.iter().find(|p| &p.name == name);
+            match current {
+                Some(p) if spec.matches(p) => {
+                    if p.reason == InstallReason::Dependency {
+                        plan.mark_explicit.push(name.clone());
+                    }
+                }
+                _ => plan.install.push((name.clone(), spec.clone())),
+            }
+
+            if let Some(version) = &spec.version {
+                if pins.get(name) != Some(version) {
+                    plan.pin.push((name.clone(), version.clone()));
+                }
+            }
+        }
+
+        let mut unlisted: Vec<&Package> = installed
+            .iter()
+            .filter(|p| p.is_installed() && p.reason == InstallReason::Explicit)
+            .filter(|p| !self.packages.contains_key(&p.name))
+            .collect();
+        unlisted.sort_by(|a, b| a.name.cmp(&b.name));
+        for pkg in unlisted {
+            // Still needed by an installed package, keep it as a dependency
+            if installed.iter().any(|p| depends_on(p, pkg)) {
+                plan.unmark.push(pkg.name.clone());
+            } else {
+                plan.remove.push(pkg.name.clone());
+            }
+        }
+
+        plan.unpin = pins
+            .keys()
+            .filter(|name| {
+                self.packages
+                    .get(*name)
+                    .is_none_or(|spec| spec.version.is_none())
+            })
+            .cloned()
+            .collect();
+
+        plan
+    }
+}
+
+impl PackageSpec {
+    /// Check whether an installed package satisfies this spec
+    fn matches(&self, pkg: &Package) -> bool {
+        let Some(installed) = &pkg.installed else {
+            return false;
+        };
+
+        if let Some(version) = &self.version {
+            if compare_versions(installed, version) != Ordering::Equal {
+                return false;
+            }
+        }
+
+        match &self.from {
+            Some(from) => pkg.is_native && pkg.method.as_ref() == Some(from),
+            // Any source will do, method and channel only apply to scripts
+            None if pkg.is_native => true,
+            None => {
+                let method = self.method.as_deref().unwrap_or("auto");
+                (method == "auto" || pkg.method.as_deref() == Some(method))
+                    && (self.channel.is_none() || pkg.channel == self.channel)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(name: &str, method: &str, native: bool) -> Package {
+        let mut pkg = Package::new(name);
+        pkg.installed = Some("1.0".to_string());
+        pkg.method = Some(method.to_string());
+        pkg.is_native = native;
+        pkg
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest: Manifest = r#"
+            repos = ["https://example.com/repo.git"]
+
+            [packages.neovim]
+            method = "appimage"
+
+            [packages.ripgrep]
+            from = "apt"
+            version = "14.1.0"
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(manifest.repos, vec!["https://example.com/repo.git"]);
+        assert_eq!(
+            manifest.packages["neovim"].method.as_deref(),
+            Some("appimage")
+        );
+        assert_eq!(manifest.packages["ripgrep"].from.as_deref(), Some("apt"));
+        assert_eq!(
+            manifest.packages["ripgrep"].version.as_deref(),
+            Some("14.1.0")
+        );
+
+        assert!("[packages.x]\nmethd = \"any\"".parse::<Manifest>().is_err());
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let mut dep = installed("lib", "any", false);
+        dep.reason = InstallReason::Dependency;
+        let packages = vec![
+            installed("app", "any", false),
+            dep,
+            installed("ripgrep", "apt", true),
+        ];
+        let pins = BTreeMap::from([("app".to_string(), "1.0".to_string())]);
+        let repos = vec![Repo::new("https://example.com/repo.git")];
+
+        let manifest = Manifest::from_installed(&packages, &pins, &repos);
+        assert_eq!(
+            manifest.packages.keys().collect::<Vec<_>>(),
+            vec!["app", "ripgrep"]
+        );
+        assert_eq!(manifest.packages["app"].version.as_deref(), Some("1.0"));
+        assert_eq!(manifest.packages["ripgrep"].from.as_deref(), Some("apt"));
+
+        let parsed: Manifest = manifest.to_toml().unwrap().parse().unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(parsed.plan(&packages, &pins, &repos).is_empty());
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut dep = installed("lib", "any", false);
+        dep.reason = InstallReason::Dependency;
+        let mut needed = installed("base", "any", false);
+        needed.required_by = vec!["app".to_string()];
+        let packages = vec![
+            installed("app", "any", false),
+            installed("old", "any", false),
+            needed,
+            installed("ripgrep", "apt", true),
+            dep,
+        ];
+        let pins = BTreeMap::from([("old".to_string(), "1.0".to_string())]);
+
+        let manifest: Manifest = r#"
+            repos = ["https://example.com/repo.git"]
+
+            [packages.app]
+            method = "appimage"
+
+            [packages.ripgrep]
+            from = "apt"
+            version = "1.0"
+
+            [packages.neovim]
+
+            [packages.lib]
+        "#
+        .parse()
+        .unwrap();
+
+        let plan = manifest.plan(&packages, &pins, &[]);
+        assert_eq!(plan.add_repos, vec!["https://example.com/repo.git"]);
+        let install: Vec<_> = plan.install.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(install, vec!["app", "neovim"]);
+        // Dependencies are left to autoremove
+        assert_eq!(plan.remove, vec!["old"]);
+        assert_eq!(plan.unmark, vec!["base"]);
+        // Listed dependencies become explicit, so autoremove keeps them
+        assert_eq!(plan.mark_explicit, vec!["lib"]);
+        assert_eq!(plan.pin, vec![("ripgrep".to_string(), "1.0".to_string())]);
+        assert_eq!(plan.unpin, vec!["old"]);
+    }
+}
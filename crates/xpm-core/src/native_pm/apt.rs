@@ -1,6 +1,6 @@
 //! APT package manager integration (Debian/Ubuntu)
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -113,6 +113,49 @@ impl AptPackageManager {
 
         packages
     }
+
+    /// Parse apt list --upgradable output
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            // Format: package/release version arch [upgradable from: old]
+            let Some((_, from)) = line.split_once("[upgradable from: ") else {
+                continue;
+            };
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let name = parts[0].split('/').next().unwrap_or(parts[0]);
+            let mut upgrade = NativeUpgrade::new(name).with_installed(from.trim_end_matches(']'));
+            if let Some(version) = parts.get(1) {
+                upgrade = upgrade.with_available(*version);
+            }
+
+            upgrades.push(upgrade);
+        }
+
+        upgrades
+    }
+
+    /// Parse dpkg-query output
+    fn parse_installed_output(&self, output: &str) -> Vec<NativePackage> {
+        let mut packages = Vec::new();
+
+        for line in output.lines() {
+            // Format: package	version	arch
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() < 2 || parts[0].is_empty() {
+                continue;
+            }
+
+            let mut pkg = NativePackage::new(parts[0]).with_version(parts[1]);
+            pkg.arch = parts.get(2).map(|s| s.to_string());
+            pkg.installed = true;
+            packages.push(pkg);
+        }
+
+        packages
+    }
 }
 
 #[async_trait]
@@ -142,6 +185,20 @@ impl NativePackageManager for AptPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["install", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_apt_sudo(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["remove", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_apt_sudo(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = Command::new("dpkg").args(["-s", name]).output().await?;
 
@@ -174,6 +231,32 @@ impl NativePackageManager for AptPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_apt_sudo(&["install", "--only-upgrade", "-y", name])
+            .await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = self.run_apt(&["list", "--upgradable"]).await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = Command::new("dpkg-query")
+            .args(["-W", "-f", "${Package}\t${Version}\t${Architecture}\n"])
+            .output()
+            .await
+            .context("Failed to run dpkg-query")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("dpkg-query failed: {}", stderr)
+        }
+
+        Ok(self.parse_installed_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_apt_sudo(&["update"]).await?;
         Ok(())
@@ -207,4 +290,50 @@ nano/stable,now 5.4-2 amd64 [installed]
         assert_eq!(packages[1].name, "nano");
         assert!(packages[1].installed);
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = AptPackageManager {
+            apt_path: PathBuf::from("/usr/bin/apt"),
+            sudo_path: None,
+        };
+
+        let output = r#"Listing... Done
+vim/jammy-updates 2:8.2.3995-1ubuntu2.15 amd64 [upgradable from: 2:8.2.3995-1ubuntu2.13]
+curl/jammy-security 7.81.0-1ubuntu1.16 amd64 [upgradable from: 7.81.0-1ubuntu1.15]
+"#;
+
+        let upgrades = pm.parse_upgradable_output(output);
+        assert_eq!(upgrades.len(), 2);
+        assert_eq!(upgrades[0].name, "vim");
+        assert_eq!(
+            upgrades[0].installed.as_deref(),
+            Some("2:8.2.3995-1ubuntu2.13")
+        );
+        assert_eq!(
+            upgrades[0].available.as_deref(),
+            Some("2:8.2.3995-1ubuntu2.15")
+        );
+        assert_eq!(upgrades[1].name, "curl");
+    }
+
+    #[test]
+    fn test_parse_installed_output() {
+        let pm = AptPackageManager {
+            apt_path: PathBuf::from("/usr/bin/apt"),
+            sudo_path: None,
+        };
+
+        let output = "vim\t2:8.2.3995-1ubuntu2.15\tamd64\nnano\t6.2-1\tamd64\n";
+        let packages = pm.parse_installed_output(output);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "vim");
+        assert_eq!(
+            packages[0].version.as_deref(),
+            Some("2:8.2.3995-1ubuntu2.15")
+        );
+        assert_eq!(packages[0].arch.as_deref(), Some("amd64"));
+        assert!(packages[1].installed);
+    }
 }
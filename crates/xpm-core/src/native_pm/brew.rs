@@ -1,6 +1,6 @@
 //! Homebrew package manager integration (macOS/Linux)
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -43,6 +43,56 @@ impl BrewPackageManager {
             .map(|line| NativePackage::new(line.trim()))
             .collect()
     }
+
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            // Formulae: name (old[, older]) < new
+            // Casks: name (old) != new
+            let Some((name, rest)) = line.trim().split_once(" (") else {
+                continue;
+            };
+            let Some((installed, available)) = rest.split_once(')') else {
+                continue;
+            };
+
+            let mut upgrade = NativeUpgrade::new(name);
+            if let Some(version) = installed.rsplit(", ").next() {
+                upgrade = upgrade.with_installed(version);
+            }
+            let available = available.trim().trim_start_matches(['<', '!', '=']).trim();
+            if !available.is_empty() {
+                upgrade = upgrade.with_available(available);
+            }
+
+            upgrades.push(upgrade);
+        }
+
+        upgrades
+    }
+
+    fn parse_installed_output(&self, output: &str) -> Vec<NativePackage> {
+        let mut packages = Vec::new();
+
+        for line in output.lines() {
+            // Format: name version [older versions...]
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let Some(name) = parts.first() else {
+                continue;
+            };
+
+            let mut pkg = NativePackage::new(*name);
+            pkg.version = parts
+                .last()
+                .filter(|_| parts.len() > 1)
+                .map(|s| s.to_string());
+            pkg.installed = true;
+            packages.push(pkg);
+        }
+
+        packages
+    }
 }
 
 #[async_trait]
@@ -72,6 +122,20 @@ impl NativePackageManager for BrewPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["install"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_brew(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["uninstall"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_brew(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = Command::new(&self.brew_path)
             .args(["list", name])
@@ -107,6 +171,21 @@ impl NativePackageManager for BrewPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_brew(&["upgrade", name]).await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = self.run_brew(&["outdated", "--verbose"]).await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = self.run_brew(&["list", "--versions"]).await?;
+        Ok(self.parse_installed_output(&output))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_brew(&["update"]).await?;
         Ok(())
@@ -155,4 +234,38 @@ mod tests {
         };
         assert_eq!(pm.name(), "brew");
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = BrewPackageManager {
+            brew_path: PathBuf::from("/opt/homebrew/bin/brew"),
+        };
+
+        let output = "vim (9.0.1, 9.0.2) < 9.1.0\n\
+                      firefox (118.0) != 119.0";
+        let upgrades = pm.parse_upgradable_output(output);
+
+        assert_eq!(upgrades.len(), 2);
+        assert_eq!(
+            upgrades[0],
+            NativeUpgrade::new("vim")
+                .with_installed("9.0.2")
+                .with_available("9.1.0")
+        );
+        assert_eq!(upgrades[1].available.as_deref(), Some("119.0"));
+    }
+
+    #[test]
+    fn test_parse_installed_output() {
+        let pm = BrewPackageManager {
+            brew_path: PathBuf::from("/opt/homebrew/bin/brew"),
+        };
+
+        let output = "git 2.41.0\nvim 9.0.1 9.0.2\n";
+        let packages = pm.parse_installed_output(output);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].version.as_deref(), Some("2.41.0"));
+        assert_eq!(packages[1].version.as_deref(), Some("9.0.2"));
+    }
 }
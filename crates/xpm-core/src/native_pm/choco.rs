@@ -1,6 +1,6 @@
 //! Chocolatey package manager integration (Windows)
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -77,7 +77,6 @@ impl ChocoPackageManager {
     }
 
     /// Parse choco list (local) output
-    #[allow(dead_code)]
     fn parse_list_output(&self, output: &str) -> Vec<NativePackage> {
         let mut packages = Vec::new();
 
@@ -108,6 +107,27 @@ impl ChocoPackageManager {
 
         packages
     }
+
+    /// Parse choco outdated --limit-output output
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            // Format: name|current|available|pinned
+            let parts: Vec<&str> = line.trim().split('|').collect();
+            if parts.len() < 3 || parts.get(3) == Some(&"true") {
+                continue;
+            }
+
+            upgrades.push(
+                NativeUpgrade::new(parts[0])
+                    .with_installed(parts[1])
+                    .with_available(parts[2]),
+            );
+        }
+
+        upgrades
+    }
 }
 
 #[async_trait]
@@ -143,6 +163,22 @@ impl NativePackageManager for ChocoPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["install"];
+        args.extend(names.iter().map(String::as_str));
+        args.extend(["-y", "--no-progress"]);
+        self.run_choco(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["uninstall"];
+        args.extend(names.iter().map(String::as_str));
+        args.extend(["-y", "--no-progress"]);
+        self.run_choco(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = self.run_choco(&["list", "--local-only", name]).await?;
         Ok(output.contains(name))
@@ -180,6 +216,22 @@ impl NativePackageManager for ChocoPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_choco(&["upgrade", name, "-y", "--no-progress"])
+            .await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = self.run_choco(&["outdated", "--limit-output"]).await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = self.run_choco(&["list", "--local-only"]).await?;
+        Ok(self.parse_list_output(&output))
+    }
+
     async fn update_db(&self) -> Result<()> {
         Ok(())
     }
@@ -258,6 +310,64 @@ impl ScoopPackageManager {
 
         packages
     }
+
+    /// Parse scoop status output
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        // Rows follow the dashed separator under the header
+        let mut lines = output
+            .lines()
+            .skip_while(|l| !l.trim_start().starts_with("----"));
+        lines.next();
+
+        for line in lines {
+            // Format: Name  Installed Version  Latest Version  Missing Dependencies  Info
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 3 {
+                continue;
+            }
+
+            upgrades.push(
+                NativeUpgrade::new(parts[0])
+                    .with_installed(parts[1])
+                    .with_available(parts[2]),
+            );
+        }
+
+        upgrades
+    }
+
+    /// Parse scoop list output
+    fn parse_list_output(&self, output: &str) -> Vec<NativePackage> {
+        let mut packages = Vec::new();
+
+        // Rows follow the dashed separator under the header
+        let mut lines = output
+            .lines()
+            .skip_while(|l| !l.trim_start().starts_with("----"));
+        lines.next();
+
+        for line in lines {
+            // Format: Name  Version  Source  Updated  Info
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 2 {
+                continue;
+            }
+
+            packages.push(NativePackage {
+                name: parts[0].to_string(),
+                version: Some(parts[1].to_string()),
+                description: None,
+                arch: None,
+                repo: parts.get(2).map(|s| s.to_string()),
+                popularity: None,
+                installed: true,
+            });
+        }
+
+        packages
+    }
 }
 
 #[async_trait]
@@ -287,6 +397,20 @@ impl NativePackageManager for ScoopPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["install"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_scoop(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["uninstall"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_scoop(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = self.run_scoop(&["list"]).await?;
         Ok(output.contains(name))
@@ -318,6 +442,21 @@ impl NativePackageManager for ScoopPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_scoop(&["update", name]).await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = self.run_scoop(&["status"]).await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = self.run_scoop(&["list"]).await?;
+        Ok(self.parse_list_output(&output))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_scoop(&["update"]).await?;
         Ok(())
@@ -362,4 +501,74 @@ neovim  (0.9.4)
         let packages = pm.parse_search_output(output);
         assert_eq!(packages.len(), 3);
     }
+
+    #[test]
+    fn test_choco_parse_upgradable_output() {
+        let pm = ChocoPackageManager {
+            choco_path: PathBuf::from("choco"),
+        };
+
+        let output = "micro|2.0.11|2.0.13|false\nneovim|0.9.4|0.9.5|true\n";
+        let upgrades = pm.parse_upgradable_output(output);
+
+        assert_eq!(
+            upgrades,
+            vec![NativeUpgrade::new("micro")
+                .with_installed("2.0.11")
+                .with_available("2.0.13")]
+        );
+    }
+
+    #[test]
+    fn test_scoop_parse_upgradable_output() {
+        let pm = ScoopPackageManager {
+            scoop_path: PathBuf::from("scoop"),
+        };
+
+        let output = r#"Scoop is up to date.
+
+Name   Installed Version Latest Version Missing Dependencies Info
+----   ----------------- -------------- -------------------- ----
+git    2.40.0            2.41.0
+neovim 0.9.4             0.9.5
+"#;
+
+        let upgrades = pm.parse_upgradable_output(output);
+        assert_eq!(upgrades.len(), 2);
+        assert_eq!(upgrades[0].name, "git");
+        assert_eq!(upgrades[1].available.as_deref(), Some("0.9.5"));
+    }
+
+    #[test]
+    fn test_choco_parse_list_output() {
+        let pm = ChocoPackageManager {
+            choco_path: PathBuf::from("choco"),
+        };
+
+        let output = "Chocolatey v2.0.0\nchocolatey 2.0.0\nmicro 2.0.11\n2 packages installed.\n";
+        let packages = pm.parse_list_output(output);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[1].name, "micro");
+        assert!(packages[1].installed);
+    }
+
+    #[test]
+    fn test_scoop_parse_list_output() {
+        let pm = ScoopPackageManager {
+            scoop_path: PathBuf::from("scoop"),
+        };
+
+        let output = r#"Installed apps:
+
+Name Version Source Updated             Info
+---- ------- ------ -------             ----
+git  2.41.0  main   2023-06-01 10:00:00
+"#;
+
+        let packages = pm.parse_list_output(output);
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "git");
+        assert_eq!(packages[0].repo.as_deref(), Some("main"));
+    }
 }
@@ -1,10 +1,17 @@
 use super::{
     AptPackageManager, BrewPackageManager, ChocoPackageManager, DnfPackageManager,
-    FlatpakPackageManager, NativePM, PacmanPackageManager, ScoopPackageManager, SnapPackageManager,
-    SwupdPackageManager, TermuxPackageManager, ZypperPackageManager,
+    FlatpakPackageManager, NativePM, NativePackageManager, PacmanPackageManager,
+    ScoopPackageManager, SnapPackageManager, SwupdPackageManager, TermuxPackageManager,
+    ZypperPackageManager,
 };
 use crate::os::{executable::Executable, os_info::get_os_info};
 
+/// Native backend names accepted by [`find_native_pm`]
+pub const NATIVE_BACKENDS: &[&str] = &[
+    "apt", "pacman", "paru", "yay", "dnf", "zypper", "brew", "swupd", "termux", "snap", "flatpak",
+    "choco", "scoop",
+];
+
 pub async fn detect_native_pm() -> Option<NativePM> {
     let os_info = get_os_info();
 
@@ -70,17 +77,14 @@ pub async fn detect_native_pm() -> Option<NativePM> {
     None
 }
 
-#[allow(dead_code)]
 pub fn has_snap() -> bool {
     Executable::new("snap").exists()
 }
 
-#[allow(dead_code)]
 pub fn has_flatpak() -> bool {
     Executable::new("flatpak").exists()
 }
 
-#[allow(dead_code)]
 pub async fn get_snap_pm() -> Option<NativePM> {
     if has_snap() {
         Some(NativePM::Snap(SnapPackageManager::new().await))
@@ -89,7 +93,6 @@ pub async fn get_snap_pm() -> Option<NativePM> {
     }
 }
 
-#[allow(dead_code)]
 pub async fn get_flatpak_pm() -> Option<NativePM> {
     if has_flatpak() {
         Some(NativePM::Flatpak(FlatpakPackageManager::new().await))
@@ -98,6 +101,72 @@ pub async fn get_flatpak_pm() -> Option<NativePM> {
     }
 }
 
+/// Detect every available native package manager
+///
+/// The primary package manager comes first, followed by snap and flatpak.
+pub async fn detect_native_pms() -> Vec<NativePM> {
+    let mut pms = Vec::new();
+    if let Some(pm) = detect_native_pm().await {
+        pms.push(pm);
+    }
+
+    for pm in [get_snap_pm().await, get_flatpak_pm().await]
+        .into_iter()
+        .flatten()
+    {
+        if !pms.iter().any(|p| p.name() == pm.name()) {
+            pms.push(pm);
+        }
+    }
+
+    pms
+}
+
+/// Get a native package manager by name, if it is available on this system
+pub async fn find_native_pm(name: &str) -> Option<NativePM> {
+    let available = match name {
+        "apt" => Executable::new("apt").exists() || Executable::new("apt-get").exists(),
+        "termux" => Executable::new("pkg").exists(),
+        other if NATIVE_BACKENDS.contains(&other) => Executable::new(other).exists(),
+        _ => false,
+    };
+
+    if !available {
+        return None;
+    }
+
+    let pm = match name {
+        "apt" => NativePM::Apt(AptPackageManager::new().await),
+        "pacman" | "paru" | "yay" => NativePM::Pacman(PacmanPackageManager::new().await),
+        "dnf" => NativePM::Dnf(DnfPackageManager::new().await),
+        "zypper" => NativePM::Zypper(ZypperPackageManager::new().await),
+        "brew" => NativePM::Brew(BrewPackageManager::new().await),
+        "swupd" => NativePM::Swupd(SwupdPackageManager::new().await),
+        "termux" => NativePM::Termux(TermuxPackageManager::new().await),
+        "snap" => NativePM::Snap(SnapPackageManager::new().await),
+        "flatpak" => NativePM::Flatpak(FlatpakPackageManager::new().await),
+        "choco" => NativePM::Choco(ChocoPackageManager::new().await),
+        "scoop" => NativePM::Scoop(ScoopPackageManager::new().await),
+        _ => return None,
+    };
+
+    Some(pm)
+}
+
+/// Get the package manager a tracked package was installed with
+///
+/// Falls back to the primary package manager when `method` is not a known
+/// backend or is no longer available.
+pub async fn native_pm_for(method: Option<&str>) -> Option<NativePM> {
+    match method {
+        Some(method) => match find_native_pm(method).await {
+            Some(pm) => Some(pm),
+            None => detect_native_pm().await,
+        },
+        None => detect_native_pm().await,
+    }
+}
+
 #[allow(dead_code)]
 pub fn has_native_pm() -> bool {
     Executable::new("apt").exists()
@@ -1,6 +1,6 @@
 //! DNF package manager integration (Fedora/RHEL)
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -93,6 +93,48 @@ impl DnfPackageManager {
 
         packages
     }
+
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            if line.starts_with("Obsoleting") {
+                break;
+            }
+            if line.trim().is_empty() || line.starts_with(' ') || line.starts_with("Last metadata")
+            {
+                continue;
+            }
+
+            // Format: name.arch  version  repo
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() == 3 {
+                let name = parts[0].rsplit_once('.').map_or(parts[0], |(n, _)| n);
+                upgrades.push(NativeUpgrade::new(name).with_available(parts[1]));
+            }
+        }
+
+        upgrades
+    }
+
+    fn parse_installed_output(&self, output: &str) -> Vec<NativePackage> {
+        let mut packages = Vec::new();
+
+        for line in output.lines() {
+            // Format: name	version-release	arch
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() < 2 || parts[0].is_empty() {
+                continue;
+            }
+
+            let mut pkg = NativePackage::new(parts[0]).with_version(parts[1]);
+            pkg.arch = parts.get(2).map(|s| s.to_string());
+            pkg.installed = true;
+            packages.push(pkg);
+        }
+
+        packages
+    }
 }
 
 #[async_trait]
@@ -122,6 +164,20 @@ impl NativePackageManager for DnfPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["install", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_dnf_sudo(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["remove", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_dnf_sudo(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = Command::new("rpm").args(["-q", name]).output().await?;
 
@@ -154,6 +210,49 @@ impl NativePackageManager for DnfPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_dnf_sudo(&["upgrade", "-y", name]).await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = Command::new(&self.dnf_path)
+            .args(["check-update", "-q"])
+            .output()
+            .await
+            .context("Failed to run dnf command")?;
+
+        // check-update exits with 100 when updates are available
+        match output.status.code() {
+            Some(0) | Some(100) => {
+                Ok(self.parse_upgradable_output(&String::from_utf8_lossy(&output.stdout)))
+            }
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                anyhow::bail!("dnf command failed: {}", stderr)
+            }
+        }
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = Command::new("rpm")
+            .args([
+                "-qa",
+                "--queryformat",
+                "%{NAME}\t%{VERSION}-%{RELEASE}\t%{ARCH}\n",
+            ])
+            .output()
+            .await
+            .context("Failed to run rpm")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("rpm command failed: {}", stderr)
+        }
+
+        Ok(self.parse_installed_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_dnf_sudo(&["makecache"]).await?;
         Ok(())
@@ -207,4 +306,41 @@ mod tests {
         };
         assert_eq!(pm.name(), "dnf");
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = DnfPackageManager {
+            dnf_path: PathBuf::from("/usr/bin/dnf"),
+            sudo_path: None,
+        };
+
+        let output = "\n\
+                      git.x86_64    2.41.0-1.fc38    updates\n\
+                      python3.11-libs.x86_64    3.11.5-1.fc38    updates\n\
+                      Obsoleting Packages\n\
+                      grub2-tools.x86_64    1:2.06-100.fc38    updates";
+
+        let upgrades = pm.parse_upgradable_output(output);
+
+        assert_eq!(upgrades.len(), 2);
+        assert_eq!(upgrades[0].name, "git");
+        assert_eq!(upgrades[0].available.as_deref(), Some("2.41.0-1.fc38"));
+        assert_eq!(upgrades[1].name, "python3.11-libs");
+    }
+
+    #[test]
+    fn test_parse_installed_output() {
+        let pm = DnfPackageManager {
+            dnf_path: PathBuf::from("/usr/bin/dnf"),
+            sudo_path: None,
+        };
+
+        let output = "git\t2.41.0-1.fc38\tx86_64\ngpg-pubkey\tabcd1234-5678\t(none)\n";
+        let packages = pm.parse_installed_output(output);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "git");
+        assert_eq!(packages[0].version.as_deref(), Some("2.41.0-1.fc38"));
+        assert_eq!(packages[0].arch.as_deref(), Some("x86_64"));
+    }
 }
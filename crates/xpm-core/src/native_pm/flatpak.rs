@@ -1,6 +1,6 @@
 //! Flatpak package manager integration
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -118,7 +118,6 @@ impl FlatpakPackageManager {
     }
 
     /// Parse flatpak list output
-    #[allow(dead_code)]
     fn parse_list_output(&self, output: &str) -> Vec<NativePackage> {
         let mut packages = Vec::new();
 
@@ -157,6 +156,28 @@ impl FlatpakPackageManager {
 
         packages
     }
+
+    /// Parse flatpak remote-ls --updates output
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            // Format: Application ID	Version
+            let parts: Vec<&str> = line.trim().split('\t').collect();
+            if parts[0].is_empty() || parts[0] == "Application ID" {
+                continue;
+            }
+
+            let mut upgrade = NativeUpgrade::new(parts[0]);
+            if let Some(version) = parts.get(1).filter(|v| !v.is_empty()) {
+                upgrade = upgrade.with_available(*version);
+            }
+
+            upgrades.push(upgrade);
+        }
+
+        upgrades
+    }
 }
 
 #[async_trait]
@@ -239,6 +260,33 @@ impl NativePackageManager for FlatpakPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        let result = self.run_flatpak(&["update", "--user", "-y", name]).await;
+        if result.is_err() {
+            self.run_flatpak_sudo(&["update", "--system", "-y", name])
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = self
+            .run_flatpak(&["remote-ls", "--updates", "--columns=application,version"])
+            .await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = self
+            .run_flatpak(&[
+                "list",
+                "--app",
+                "--columns=name,application,version,branch,installation",
+            ])
+            .await?;
+        Ok(self.parse_list_output(&output))
+    }
+
     async fn update_db(&self) -> Result<()> {
         let _ = self.run_flatpak(&["update", "--appstream"]).await;
         Ok(())
@@ -263,4 +311,38 @@ mod tests {
         assert_eq!(packages[0].name, "io.github.nickvergessen.micro");
         assert_eq!(packages[1].name, "com.visualstudio.code");
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = FlatpakPackageManager {
+            flatpak_path: PathBuf::from("/usr/bin/flatpak"),
+            sudo_path: None,
+        };
+
+        let output = "org.mozilla.firefox\t120.0\norg.gnome.Platform\t\n";
+        let upgrades = pm.parse_upgradable_output(output);
+
+        assert_eq!(upgrades.len(), 2);
+        assert_eq!(
+            upgrades[0],
+            NativeUpgrade::new("org.mozilla.firefox").with_available("120.0")
+        );
+        assert!(upgrades[1].available.is_none());
+    }
+
+    #[test]
+    fn test_parse_list_output() {
+        let pm = FlatpakPackageManager {
+            flatpak_path: PathBuf::from("/usr/bin/flatpak"),
+            sudo_path: None,
+        };
+
+        let output = "Firefox\torg.mozilla.firefox\t120.0\tstable\tsystem\n";
+        let packages = pm.parse_list_output(output);
+
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "org.mozilla.firefox");
+        assert_eq!(packages[0].version.as_deref(), Some("120.0"));
+        assert!(packages[0].installed);
+    }
 }
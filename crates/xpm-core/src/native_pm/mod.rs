@@ -7,6 +7,7 @@ mod detector;
 mod dnf;
 mod flatpak;
 mod pacman;
+mod search;
 mod snap;
 mod swupd;
 mod termux;
@@ -15,17 +16,138 @@ mod zypper;
 pub use apt::AptPackageManager;
 pub use brew::BrewPackageManager;
 pub use choco::{ChocoPackageManager, ScoopPackageManager};
-pub use detector::detect_native_pm;
+pub use detector::{
+    detect_native_pm, detect_native_pms, find_native_pm, native_pm_for, NATIVE_BACKENDS,
+};
 pub use dnf::DnfPackageManager;
 pub use flatpak::FlatpakPackageManager;
 pub use pacman::PacmanPackageManager;
+pub use search::{search_native, NativeSearchHit};
 pub use snap::SnapPackageManager;
 pub use swupd::SwupdPackageManager;
 pub use termux::TermuxPackageManager;
 pub use zypper::ZypperPackageManager;
 
+use crate::utils::logger::Logger;
 use anyhow::Result;
 use async_trait::async_trait;
+use std::str::FromStr;
+
+/// Which package sources a command may use
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum NativeMode {
+    /// XPM first, then the native package managers
+    #[default]
+    Auto,
+    /// Native package managers only
+    Only,
+    /// XPM only
+    Off,
+    /// Only the named sources, in order (`xpm` selects the XPM index)
+    Sources(Vec<String>),
+}
+
+impl NativeMode {
+    /// Check whether the XPM index may be used
+    pub fn includes_xpm(&self) -> bool {
+        match self {
+            NativeMode::Auto | NativeMode::Off => true,
+            NativeMode::Only => false,
+            NativeMode::Sources(sources) => sources.iter().any(|s| s == "xpm"),
+        }
+    }
+
+    /// Get the native package managers to use, in order
+    pub async fn native_pms(&self) -> Vec<NativePM> {
+        match self {
+            NativeMode::Auto | NativeMode::Only => detect_native_pms().await,
+            NativeMode::Off => Vec::new(),
+            NativeMode::Sources(sources) => {
+                let mut pms = Vec::new();
+                for name in sources.iter().filter(|s| *s != "xpm") {
+                    match find_native_pm(name).await {
+                        Some(pm) => pms.push(pm),
+                        None => {
+                            Logger::warning(&format!("{} is not available on this system", name))
+                        }
+                    }
+                }
+                pms
+            }
+        }
+    }
+
+    /// Get the native package managers to install from, in order
+    ///
+    /// Explicit sources keep the order they were given in. Otherwise the
+    /// backends named in `preference` come first, followed by the remaining
+    /// detected ones.
+    pub async fn preferred_pms(&self, preference: &[String]) -> Vec<NativePM> {
+        let mut pms = self.native_pms().await;
+        if !matches!(self, NativeMode::Auto | NativeMode::Only) {
+            return pms;
+        }
+
+        for name in preference {
+            if pms.iter().any(|p| p.name() == name) {
+                continue;
+            }
+            if let Some(pm) = find_native_pm(name).await {
+                if !pms.iter().any(|p| p.name() == pm.name()) {
+                    pms.push(pm);
+                }
+            }
+        }
+
+        sort_by_preference(&mut pms, preference, |pm| pm.name());
+        pms
+    }
+}
+
+/// Stable-sort items so those named in `preference` come first, in its order
+pub fn sort_by_preference<T>(items: &mut [T], preference: &[String], name: impl Fn(&T) -> &str) {
+    items.sort_by_key(|item| {
+        preference
+            .iter()
+            .position(|p| p == name(item))
+            .unwrap_or(preference.len())
+    });
+}
+
+impl FromStr for NativeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(NativeMode::Auto),
+            "only" => Ok(NativeMode::Only),
+            "off" => Ok(NativeMode::Off),
+            list => {
+                let sources: Vec<String> = list
+                    .split(',')
+                    .map(|s| s.trim().to_lowercase())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+
+                if let Some(unknown) = sources
+                    .iter()
+                    .find(|s| *s != "xpm" && !NATIVE_BACKENDS.contains(&s.as_str()))
+                {
+                    return Err(format!(
+                        "unknown source '{}' (expected auto, only, off or a list of: xpm, {})",
+                        unknown,
+                        NATIVE_BACKENDS.join(", ")
+                    ));
+                }
+                if sources.is_empty() {
+                    return Err("no sources given".to_string());
+                }
+
+                Ok(NativeMode::Sources(sources))
+            }
+        }
+    }
+}
 
 /// Native package representation
 #[derive(Debug, Clone)]
@@ -90,6 +212,40 @@ impl NativePackage {
     }
 }
 
+/// Native package with a newer version available
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeUpgrade {
+    /// Package name
+    pub name: String,
+    /// Installed version, when the package manager reports it
+    pub installed: Option<String>,
+    /// Version the package would be upgraded to
+    pub available: Option<String>,
+}
+
+impl NativeUpgrade {
+    /// Create a new upgrade entry
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            installed: None,
+            available: None,
+        }
+    }
+
+    /// Builder pattern for the installed version
+    pub fn with_installed(mut self, version: impl Into<String>) -> Self {
+        self.installed = Some(version.into());
+        self
+    }
+
+    /// Builder pattern for the available version
+    pub fn with_available(mut self, version: impl Into<String>) -> Self {
+        self.available = Some(version.into());
+        self
+    }
+}
+
 /// Trait for native package managers
 #[async_trait]
 pub trait NativePackageManager: Send + Sync {
@@ -105,12 +261,37 @@ pub trait NativePackageManager: Send + Sync {
     /// Remove a package
     async fn remove(&self, name: &str) -> Result<()>;
 
+    /// Install several packages, in a single call where the package manager allows it
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        for name in names {
+            self.install(name).await?;
+        }
+        Ok(())
+    }
+
+    /// Remove several packages, in a single call where the package manager allows it
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        for name in names {
+            self.remove(name).await?;
+        }
+        Ok(())
+    }
+
     /// Check if package is installed
     async fn is_installed(&self, name: &str) -> Result<bool>;
 
     /// Get package info
     async fn get(&self, name: &str) -> Result<Option<NativePackage>>;
 
+    /// Upgrade an installed package to the latest available version
+    async fn upgrade(&self, name: &str) -> Result<()>;
+
+    /// List every package installed through this package manager
+    async fn list_installed(&self) -> Result<Vec<NativePackage>>;
+
+    /// List installed packages that have a newer version available
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>>;
+
     /// Update package database
     async fn update_db(&self) -> Result<()>;
 
@@ -200,6 +381,38 @@ impl NativePackageManager for NativePM {
         }
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        match self {
+            NativePM::Apt(pm) => pm.install_many(names).await,
+            NativePM::Pacman(pm) => pm.install_many(names).await,
+            NativePM::Dnf(pm) => pm.install_many(names).await,
+            NativePM::Zypper(pm) => pm.install_many(names).await,
+            NativePM::Brew(pm) => pm.install_many(names).await,
+            NativePM::Swupd(pm) => pm.install_many(names).await,
+            NativePM::Termux(pm) => pm.install_many(names).await,
+            NativePM::Snap(pm) => pm.install_many(names).await,
+            NativePM::Flatpak(pm) => pm.install_many(names).await,
+            NativePM::Choco(pm) => pm.install_many(names).await,
+            NativePM::Scoop(pm) => pm.install_many(names).await,
+        }
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        match self {
+            NativePM::Apt(pm) => pm.remove_many(names).await,
+            NativePM::Pacman(pm) => pm.remove_many(names).await,
+            NativePM::Dnf(pm) => pm.remove_many(names).await,
+            NativePM::Zypper(pm) => pm.remove_many(names).await,
+            NativePM::Brew(pm) => pm.remove_many(names).await,
+            NativePM::Swupd(pm) => pm.remove_many(names).await,
+            NativePM::Termux(pm) => pm.remove_many(names).await,
+            NativePM::Snap(pm) => pm.remove_many(names).await,
+            NativePM::Flatpak(pm) => pm.remove_many(names).await,
+            NativePM::Choco(pm) => pm.remove_many(names).await,
+            NativePM::Scoop(pm) => pm.remove_many(names).await,
+        }
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         match self {
             NativePM::Apt(pm) => pm.is_installed(name).await,
@@ -232,6 +445,54 @@ impl NativePackageManager for NativePM {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        match self {
+            NativePM::Apt(pm) => pm.upgrade(name).await,
+            NativePM::Pacman(pm) => pm.upgrade(name).await,
+            NativePM::Dnf(pm) => pm.upgrade(name).await,
+            NativePM::Zypper(pm) => pm.upgrade(name).await,
+            NativePM::Brew(pm) => pm.upgrade(name).await,
+            NativePM::Swupd(pm) => pm.upgrade(name).await,
+            NativePM::Termux(pm) => pm.upgrade(name).await,
+            NativePM::Snap(pm) => pm.upgrade(name).await,
+            NativePM::Flatpak(pm) => pm.upgrade(name).await,
+            NativePM::Choco(pm) => pm.upgrade(name).await,
+            NativePM::Scoop(pm) => pm.upgrade(name).await,
+        }
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        match self {
+            NativePM::Apt(pm) => pm.list_installed().await,
+            NativePM::Pacman(pm) => pm.list_installed().await,
+            NativePM::Dnf(pm) => pm.list_installed().await,
+            NativePM::Zypper(pm) => pm.list_installed().await,
+            NativePM::Brew(pm) => pm.list_installed().await,
+            NativePM::Swupd(pm) => pm.list_installed().await,
+            NativePM::Termux(pm) => pm.list_installed().await,
+            NativePM::Snap(pm) => pm.list_installed().await,
+            NativePM::Flatpak(pm) => pm.list_installed().await,
+            NativePM::Choco(pm) => pm.list_installed().await,
+            NativePM::Scoop(pm) => pm.list_installed().await,
+        }
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        match self {
+            NativePM::Apt(pm) => pm.list_upgradable().await,
+            NativePM::Pacman(pm) => pm.list_upgradable().await,
+            NativePM::Dnf(pm) => pm.list_upgradable().await,
+            NativePM::Zypper(pm) => pm.list_upgradable().await,
+            NativePM::Brew(pm) => pm.list_upgradable().await,
+            NativePM::Swupd(pm) => pm.list_upgradable().await,
+            NativePM::Termux(pm) => pm.list_upgradable().await,
+            NativePM::Snap(pm) => pm.list_upgradable().await,
+            NativePM::Flatpak(pm) => pm.list_upgradable().await,
+            NativePM::Choco(pm) => pm.list_upgradable().await,
+            NativePM::Scoop(pm) => pm.list_upgradable().await,
+        }
+    }
+
     async fn update_db(&self) -> Result<()> {
         match self {
             NativePM::Apt(pm) => pm.update_db().await,
@@ -260,6 +521,39 @@ impl NativePackageManager for NativePM {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_sort_by_preference() {
+        let preference = vec!["flatpak".to_string(), "snap".to_string()];
+
+        let mut backends = vec!["apt", "snap", "brew", "flatpak"];
+        sort_by_preference(&mut backends, &preference, |b| b);
+        assert_eq!(backends, vec!["flatpak", "snap", "apt", "brew"]);
+
+        let mut backends = vec!["apt", "brew"];
+        sort_by_preference(&mut backends, &[], |b| b);
+        assert_eq!(backends, vec!["apt", "brew"]);
+    }
+
+    #[test]
+    fn test_native_mode_parse() {
+        assert_eq!("auto".parse(), Ok(NativeMode::Auto));
+        assert_eq!("off".parse(), Ok(NativeMode::Off));
+        assert_eq!(
+            "apt, Flatpak".parse(),
+            Ok(NativeMode::Sources(vec![
+                "apt".to_string(),
+                "flatpak".to_string()
+            ]))
+        );
+        assert!("apt,nope".parse::<NativeMode>().is_err());
+        assert!(",".parse::<NativeMode>().is_err());
+
+        assert!(NativeMode::Auto.includes_xpm());
+        assert!(!NativeMode::Only.includes_xpm());
+        assert!(!"snap".parse::<NativeMode>().unwrap().includes_xpm());
+        assert!("xpm,snap".parse::<NativeMode>().unwrap().includes_xpm());
+    }
+
     #[test]
     fn test_native_package_is_aur() {
         let aur_pkg = NativePackage::new("test").with_repo("aur");
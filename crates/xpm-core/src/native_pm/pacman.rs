@@ -1,7 +1,7 @@
 //! Pacman package manager integration (Arch Linux)
 //! Supports paru, yay, and pacman
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -227,6 +227,46 @@ impl PacmanPackageManager {
         packages.extend(aur);
         packages.extend(official);
     }
+
+    /// Parse -Qu output
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            // Format: package old -> new [ignored]
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 4 || parts[2] != "->" || line.ends_with("[ignored]") {
+                continue;
+            }
+
+            upgrades.push(
+                NativeUpgrade::new(parts[0])
+                    .with_installed(parts[1])
+                    .with_available(parts[3]),
+            );
+        }
+
+        upgrades
+    }
+
+    /// Parse -Q output
+    fn parse_installed_output(&self, output: &str) -> Vec<NativePackage> {
+        let mut packages = Vec::new();
+
+        for line in output.lines() {
+            // Format: package version
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 2 {
+                continue;
+            }
+
+            let mut pkg = NativePackage::new(parts[0]).with_version(parts[1]);
+            pkg.installed = true;
+            packages.push(pkg);
+        }
+
+        packages
+    }
 }
 
 #[async_trait]
@@ -260,6 +300,20 @@ impl NativePackageManager for PacmanPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["-S", "--noconfirm", "--needed"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_helper_sudo(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["-R", "--noconfirm"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_helper_sudo(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = Command::new(&self.helper_path)
             .args(["-Q", name])
@@ -298,6 +352,22 @@ impl NativePackageManager for PacmanPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_helper_sudo(&["-S", "--noconfirm", name]).await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        // AUR helpers include AUR packages in -Qu
+        let output = self.run_helper(&["-Qu"]).await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = self.run_helper(&["-Q"]).await?;
+        Ok(self.parse_installed_output(&output))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_helper_sudo(&["-Sy"]).await?;
         Ok(())
@@ -454,4 +524,37 @@ aur/neovim-nightly-bin 0.10.0-1 (+89 2.34)
         let pm = create_test_pm();
         assert!(pm.results_pre_sorted());
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = create_test_pm();
+
+        let output = r#"jq 1.7-1 -> 1.7.1-1
+linux 6.6.1.arch1-1 -> 6.6.2.arch1-1 [ignored]
+neovim-git 0.10.0.r1-1 -> 0.10.0.r5-1
+"#;
+
+        let upgrades = pm.parse_upgradable_output(output);
+        assert_eq!(upgrades.len(), 2);
+        assert_eq!(
+            upgrades[0],
+            NativeUpgrade::new("jq")
+                .with_installed("1.7-1")
+                .with_available("1.7.1-1")
+        );
+        assert_eq!(upgrades[1].name, "neovim-git");
+    }
+
+    #[test]
+    fn test_parse_installed_output() {
+        let pm = create_test_pm();
+
+        let output = "base 3-2\njq 1.7.1-1\nparu-bin 2.0.1-1\n";
+        let packages = pm.parse_installed_output(output);
+
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[1].name, "jq");
+        assert_eq!(packages[1].version.as_deref(), Some("1.7.1-1"));
+        assert!(packages[2].installed);
+    }
 }
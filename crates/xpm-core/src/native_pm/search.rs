@@ -0,0 +1,113 @@
+//! Concurrent search across several native package managers
+
+use super::{NativePM, NativePackage, NativePackageManager};
+use futures::future::join_all;
+use std::cmp::Reverse;
+
+/// Search result merged across native package managers
+#[derive(Debug, Clone)]
+pub struct NativeSearchHit {
+    /// Package as reported by the first source that has it
+    pub package: NativePackage,
+    /// Package managers offering this package, in priority order
+    pub sources: Vec<String>,
+}
+
+/// Search every package manager concurrently and merge the results
+///
+/// Package managers are given in priority order. Within the merged list the
+/// most relevant results come last, and the first package manager's results
+/// end up at the bottom.
+pub async fn search_native(
+    pms: &[NativePM],
+    query: &str,
+    limit: Option<usize>,
+) -> Vec<NativeSearchHit> {
+    let searches = pms.iter().map(|pm| async move {
+        // A failing backend should not hide results from the others
+        let mut packages = pm.search(query, limit).await.unwrap_or_default();
+        if !pm.results_pre_sorted() {
+            packages.reverse();
+        }
+        (pm.name().to_string(), packages)
+    });
+
+    merge_results(join_all(searches).await)
+}
+
+/// Merge per-source results, folding packages with the same name into one hit
+fn merge_results(results: Vec<(String, Vec<NativePackage>)>) -> Vec<NativeSearchHit> {
+    let mut hits: Vec<(usize, NativeSearchHit)> = Vec::new();
+
+    for (priority, (source, packages)) in results.into_iter().enumerate() {
+        for package in packages {
+            match hits
+                .iter_mut()
+                .find(|(_, h)| h.package.name == package.name)
+            {
+                Some((_, hit)) => {
+                    hit.package.installed |= package.installed;
+                    if !hit.sources.contains(&source) {
+                        hit.sources.push(source.clone());
+                    }
+                }
+                None => hits.push((
+                    priority,
+                    NativeSearchHit {
+                        package,
+                        sources: vec![source.clone()],
+                    },
+                )),
+            }
+        }
+    }
+
+    // Stable, so each source keeps its own relevance order
+    hits.sort_by_key(|(priority, _)| Reverse(*priority));
+    hits.into_iter().map(|(_, hit)| hit).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(hits: &[NativeSearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.package.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_merge_results() {
+        let results = vec![
+            (
+                "apt".to_string(),
+                vec![NativePackage::new("vim-gtk"), NativePackage::new("vim")],
+            ),
+            (
+                "snap".to_string(),
+                vec![NativePackage::new("vim"), NativePackage::new("vim-editor")],
+            ),
+        ];
+
+        let hits = merge_results(results);
+
+        // Primary source last, closest to the prompt
+        assert_eq!(names(&hits), vec!["vim-editor", "vim-gtk", "vim"]);
+        assert_eq!(hits[2].sources, vec!["apt", "snap"]);
+        assert_eq!(hits[0].sources, vec!["snap"]);
+    }
+
+    #[test]
+    fn test_merge_keeps_installed() {
+        let mut installed = NativePackage::new("firefox");
+        installed.installed = true;
+
+        let results = vec![
+            ("apt".to_string(), vec![NativePackage::new("firefox")]),
+            ("flatpak".to_string(), vec![installed]),
+        ];
+
+        let hits = merge_results(results);
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].package.installed);
+    }
+}
@@ -1,6 +1,6 @@
 //! Snap package manager integration
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -103,7 +103,6 @@ impl SnapPackageManager {
     }
 
     /// Parse snap list output for installed packages
-    #[allow(dead_code)]
     fn parse_list_output(&self, output: &str) -> Vec<NativePackage> {
         let mut packages = Vec::new();
 
@@ -128,6 +127,27 @@ impl SnapPackageManager {
 
         packages
     }
+
+    /// Parse snap refresh --list output
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        // "All snaps up to date." has no header, so nothing is parsed
+        let mut lines = output.lines().skip_while(|l| !l.starts_with("Name"));
+        lines.next();
+
+        for line in lines {
+            // Format: Name  Version  Rev  Size  Publisher  Notes
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 2 {
+                continue;
+            }
+
+            upgrades.push(NativeUpgrade::new(parts[0]).with_available(parts[1]));
+        }
+
+        upgrades
+    }
 }
 
 #[async_trait]
@@ -192,6 +212,21 @@ impl NativePackageManager for SnapPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_snap_sudo(&["refresh", name]).await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = self.run_snap(&["refresh", "--list"]).await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = self.run_snap(&["list"]).await?;
+        Ok(self.parse_list_output(&output))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_snap_sudo(&["refresh", "--list"]).await?;
         Ok(())
@@ -238,4 +273,24 @@ firefox   120.0      3358   latest/stable  mozilla✓    -
         assert_eq!(packages[0].name, "core22");
         assert!(packages[0].installed);
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = SnapPackageManager {
+            snap_path: PathBuf::from("/usr/bin/snap"),
+            sudo_path: None,
+        };
+
+        let output = "Name     Version  Rev   Size   Publisher   Notes\n\
+                      firefox  120.0    3358  267MB  mozilla✓    -";
+        let upgrades = pm.parse_upgradable_output(output);
+
+        assert_eq!(upgrades.len(), 1);
+        assert_eq!(upgrades[0].name, "firefox");
+        assert_eq!(upgrades[0].available.as_deref(), Some("120.0"));
+
+        assert!(pm
+            .parse_upgradable_output("All snaps up to date.")
+            .is_empty());
+    }
 }
@@ -1,6 +1,6 @@
 //! swupd package manager integration (Clear Linux)
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -83,6 +83,38 @@ impl SwupdPackageManager {
 
         packages
     }
+
+    /// Parse check-update output into the current and latest OS versions
+    fn parse_check_update_output(&self, output: &str) -> Option<(Option<String>, String)> {
+        let mut current = None;
+        let mut latest = None;
+
+        for line in output.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim().to_string();
+                match key.trim() {
+                    "Current OS version" => current = Some(value),
+                    "There is a new OS version available" => latest = Some(value),
+                    _ => {}
+                }
+            }
+        }
+
+        latest.map(|latest| (current, latest))
+    }
+
+    fn parse_installed_output(&self, output: &str) -> Vec<NativePackage> {
+        output
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with("Installed bundles"))
+            .map(|name| {
+                let mut pkg = NativePackage::new(name);
+                pkg.installed = true;
+                pkg
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -112,6 +144,20 @@ impl NativePackageManager for SwupdPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["bundle-add"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_swupd_sudo(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["bundle-remove"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_swupd_sudo(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = self.run_swupd(&["bundle-list"]).await?;
         Ok(output.lines().any(|line| line.trim() == name))
@@ -137,6 +183,41 @@ impl NativePackageManager for SwupdPackageManager {
         }
     }
 
+    /// Bundles are versioned with the OS, so this updates the whole system
+    async fn upgrade(&self, _name: &str) -> Result<()> {
+        self.run_swupd_sudo(&["update"]).await?;
+        Ok(())
+    }
+
+    /// Every installed bundle is upgradable when a new OS version is available
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        // check-update exits non-zero when the system is up to date
+        let Ok(output) = self.run_swupd(&["check-update"]).await else {
+            return Ok(Vec::new());
+        };
+        let Some((current, latest)) = self.parse_check_update_output(&output) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(self
+            .list_installed()
+            .await?
+            .into_iter()
+            .map(|bundle| {
+                let upgrade = NativeUpgrade::new(bundle.name).with_available(&latest);
+                match &current {
+                    Some(current) => upgrade.with_installed(current),
+                    None => upgrade,
+                }
+            })
+            .collect())
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = self.run_swupd(&["bundle-list"]).await?;
+        Ok(self.parse_installed_output(&output))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_swupd_sudo(&["update"]).await?;
         Ok(())
@@ -174,4 +255,40 @@ mod tests {
         };
         assert_eq!(pm.name(), "swupd");
     }
+
+    #[test]
+    fn test_parse_check_update_output() {
+        let pm = SwupdPackageManager {
+            swupd_path: PathBuf::from("/usr/bin/swupd"),
+            sudo_path: None,
+        };
+
+        let output = "Current OS version: 39000\n\
+                      Latest server version: 39010\n\
+                      There is a new OS version available: 39010";
+
+        assert_eq!(
+            pm.parse_check_update_output(output),
+            Some((Some("39000".to_string()), "39010".to_string()))
+        );
+        assert_eq!(
+            pm.parse_check_update_output("Current OS version: 39010"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_installed_output() {
+        let pm = SwupdPackageManager {
+            swupd_path: PathBuf::from("/usr/bin/swupd"),
+            sudo_path: None,
+        };
+
+        let output = "Installed bundles:\n os-core\n vim\n";
+        let packages = pm.parse_installed_output(output);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "os-core");
+        assert!(packages[1].installed);
+    }
 }
@@ -1,6 +1,6 @@
 //! Termux (pkg) package manager integration (Android)
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -81,6 +81,49 @@ impl TermuxPackageManager {
 
         packages
     }
+
+    /// Parse apt list --upgradable output
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            // Format: package/release version arch [upgradable from: old]
+            let Some((_, from)) = line.split_once("[upgradable from: ") else {
+                continue;
+            };
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let name = parts[0].split('/').next().unwrap_or(parts[0]);
+            let mut upgrade = NativeUpgrade::new(name).with_installed(from.trim_end_matches(']'));
+            if let Some(version) = parts.get(1) {
+                upgrade = upgrade.with_available(*version);
+            }
+
+            upgrades.push(upgrade);
+        }
+
+        upgrades
+    }
+
+    /// Parse dpkg-query output
+    fn parse_installed_output(&self, output: &str) -> Vec<NativePackage> {
+        let mut packages = Vec::new();
+
+        for line in output.lines() {
+            // Format: package	version	arch
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() < 2 || parts[0].is_empty() {
+                continue;
+            }
+
+            let mut pkg = NativePackage::new(parts[0]).with_version(parts[1]);
+            pkg.arch = parts.get(2).map(|s| s.to_string());
+            pkg.installed = true;
+            packages.push(pkg);
+        }
+
+        packages
+    }
 }
 
 #[async_trait]
@@ -110,6 +153,20 @@ impl NativePackageManager for TermuxPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["install", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_pkg(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["uninstall", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_pkg(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = Command::new("dpkg").args(["-s", name]).output().await?;
         Ok(output.status.success())
@@ -142,6 +199,46 @@ impl NativePackageManager for TermuxPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        // pkg only upgrades everything at once, apt can target one package
+        let output = Command::new("apt")
+            .args(["install", "--only-upgrade", "-y", name])
+            .output()
+            .await
+            .context("Failed to run apt command")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("apt command failed: {}", stderr)
+        }
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = Command::new("apt")
+            .args(["list", "--upgradable"])
+            .output()
+            .await
+            .context("Failed to run apt command")?;
+
+        Ok(self.parse_upgradable_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = Command::new("dpkg-query")
+            .args(["-W", "-f", "${Package}\t${Version}\t${Architecture}\n"])
+            .output()
+            .await
+            .context("Failed to run dpkg-query")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("dpkg-query failed: {}", stderr)
+        }
+
+        Ok(self.parse_installed_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_pkg(&["update"]).await?;
         Ok(())
@@ -174,4 +271,35 @@ nano/stable 7.2-1 aarch64 [installed]
         assert_eq!(packages[1].name, "nano");
         assert!(packages[1].installed);
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = TermuxPackageManager {
+            pkg_path: PathBuf::from("/data/data/com.termux/files/usr/bin/pkg"),
+        };
+
+        let output = r#"Listing... Done
+micro/stable 2.0.13 aarch64 [upgradable from: 2.0.11]
+"#;
+
+        let upgrades = pm.parse_upgradable_output(output);
+        assert_eq!(
+            upgrades,
+            vec![NativeUpgrade::new("micro")
+                .with_installed("2.0.11")
+                .with_available("2.0.13")]
+        );
+    }
+
+    #[test]
+    fn test_parse_installed_output() {
+        let pm = TermuxPackageManager {
+            pkg_path: PathBuf::from("/data/data/com.termux/files/usr/bin/pkg"),
+        };
+
+        let packages = pm.parse_installed_output("micro\t2.0.11\taarch64\n");
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "micro");
+        assert_eq!(packages[0].version.as_deref(), Some("2.0.11"));
+    }
 }
@@ -1,6 +1,6 @@
 //! Zypper package manager integration (openSUSE)
 
-use super::{NativePackage, NativePackageManager};
+use super::{NativePackage, NativePackageManager, NativeUpgrade};
 use crate::os::executable::Executable;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -100,6 +100,45 @@ impl ZypperPackageManager {
 
         packages
     }
+
+    fn parse_upgradable_output(&self, output: &str) -> Vec<NativeUpgrade> {
+        let mut upgrades = Vec::new();
+
+        for line in output.lines() {
+            // Format: S | Repository | Name | Current Version | Available Version | Arch
+            let parts: Vec<&str> = line.split('|').map(|s| s.trim()).collect();
+            if parts.len() < 5 || parts[0] == "S" || parts[2].is_empty() {
+                continue;
+            }
+
+            upgrades.push(
+                NativeUpgrade::new(parts[2])
+                    .with_installed(parts[3])
+                    .with_available(parts[4]),
+            );
+        }
+
+        upgrades
+    }
+
+    fn parse_installed_output(&self, output: &str) -> Vec<NativePackage> {
+        let mut packages = Vec::new();
+
+        for line in output.lines() {
+            // Format: name	version-release	arch
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() < 2 || parts[0].is_empty() {
+                continue;
+            }
+
+            let mut pkg = NativePackage::new(parts[0]).with_version(parts[1]);
+            pkg.arch = parts.get(2).map(|s| s.to_string());
+            pkg.installed = true;
+            packages.push(pkg);
+        }
+
+        packages
+    }
 }
 
 #[async_trait]
@@ -129,6 +168,20 @@ impl NativePackageManager for ZypperPackageManager {
         Ok(())
     }
 
+    async fn install_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["install", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_zypper_sudo(&args).await?;
+        Ok(())
+    }
+
+    async fn remove_many(&self, names: &[String]) -> Result<()> {
+        let mut args = vec!["remove", "-y"];
+        args.extend(names.iter().map(String::as_str));
+        self.run_zypper_sudo(&args).await?;
+        Ok(())
+    }
+
     async fn is_installed(&self, name: &str) -> Result<bool> {
         let output = Command::new("rpm").args(["-q", name]).output().await?;
 
@@ -161,6 +214,35 @@ impl NativePackageManager for ZypperPackageManager {
         }
     }
 
+    async fn upgrade(&self, name: &str) -> Result<()> {
+        self.run_zypper_sudo(&["update", "-y", name]).await?;
+        Ok(())
+    }
+
+    async fn list_upgradable(&self) -> Result<Vec<NativeUpgrade>> {
+        let output = self.run_zypper(&["--quiet", "list-updates"]).await?;
+        Ok(self.parse_upgradable_output(&output))
+    }
+
+    async fn list_installed(&self) -> Result<Vec<NativePackage>> {
+        let output = Command::new("rpm")
+            .args([
+                "-qa",
+                "--queryformat",
+                "%{NAME}\t%{VERSION}-%{RELEASE}\t%{ARCH}\n",
+            ])
+            .output()
+            .await
+            .context("Failed to run rpm")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("rpm command failed: {}", stderr)
+        }
+
+        Ok(self.parse_installed_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+
     async fn update_db(&self) -> Result<()> {
         self.run_zypper_sudo(&["refresh"]).await?;
         Ok(())
@@ -200,4 +282,39 @@ mod tests {
         };
         assert_eq!(pm.name(), "zypper");
     }
+
+    #[test]
+    fn test_parse_upgradable_output() {
+        let pm = ZypperPackageManager {
+            zypper_path: PathBuf::from("/usr/bin/zypper"),
+            sudo_path: None,
+        };
+
+        let output = "S | Repository | Name | Current Version | Available Version | Arch\n\
+                      --+------------+------+-----------------+-------------------+-------\n\
+                      v | repo-oss   | vim  | 9.0.1-1.1       | 9.0.2-1.1         | x86_64";
+
+        let upgrades = pm.parse_upgradable_output(output);
+
+        assert_eq!(upgrades.len(), 1);
+        assert_eq!(
+            upgrades[0],
+            NativeUpgrade::new("vim")
+                .with_installed("9.0.1-1.1")
+                .with_available("9.0.2-1.1")
+        );
+    }
+
+    #[test]
+    fn test_parse_installed_output() {
+        let pm = ZypperPackageManager {
+            zypper_path: PathBuf::from("/usr/bin/zypper"),
+            sudo_path: None,
+        };
+
+        let packages = pm.parse_installed_output("vim\t9.0.2-1.1\tx86_64\n");
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "vim");
+        assert!(packages[0].installed);
+    }
 }
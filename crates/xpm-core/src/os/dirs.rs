@@ -50,6 +50,14 @@ impl XpmDirs {
         Ok(path)
     }
 
+    /// Get the script output logs directory (~/.local/share/xpm/logs)
+    pub fn logs_dir() -> Result<PathBuf> {
+        let data = Self::data_dir()?;
+        let path = data.join("logs");
+        std::fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+
     /// Get the downloads cache directory
     pub fn downloads_dir() -> Result<PathBuf> {
         let cache = Self::cache_dir()?;
@@ -109,6 +117,37 @@ impl XpmDirs {
         Ok(local_bin)
     }
 
+    /// Get the directories package scripts usually install files to
+    ///
+    /// `xBIN`, `~/.local/share` and `~/.config` (and the XDG data and config
+    /// directories, if set elsewhere), and on Unix `/opt` and `/usr/local`.
+    /// Directories inside another one are left out.
+    pub fn watched_dirs() -> Result<Vec<PathBuf>> {
+        let home = Self::home_dir()?;
+        let mut candidates = vec![
+            Self::bin_dir()?,
+            home.join(".local/share"),
+            home.join(".config"),
+        ];
+        candidates.extend(dirs::data_dir());
+        candidates.extend(dirs::config_dir());
+        if cfg!(unix) {
+            candidates.push(PathBuf::from("/opt"));
+            candidates.push(PathBuf::from("/usr/local"));
+        }
+
+        let mut watched: Vec<PathBuf> = Vec::new();
+        for dir in &candidates {
+            let nested = candidates
+                .iter()
+                .any(|other| other != dir && dir.starts_with(other));
+            if !nested && !watched.contains(dir) {
+                watched.push(dir.clone());
+            }
+        }
+        Ok(watched)
+    }
+
     /// Get the Windows Program Files directory (Windows only)
     #[cfg(windows)]
     pub fn program_files() -> Result<PathBuf> {
@@ -225,4 +264,16 @@ mod tests {
         let dir = XpmDirs::home_dir().unwrap();
         assert!(dir.exists());
     }
+
+    #[test]
+    fn test_watched_dirs() {
+        let watched = XpmDirs::watched_dirs().unwrap();
+        let bin = XpmDirs::bin_dir().unwrap();
+        assert!(watched.iter().any(|dir| bin.starts_with(dir)));
+
+        for dir in &watched {
+            let others = watched.iter().filter(|other| *other != dir);
+            assert!(others.clone().all(|other| !dir.starts_with(other)));
+        }
+    }
 }
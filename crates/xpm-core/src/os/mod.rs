@@ -8,6 +8,7 @@ pub mod dirs;
 pub mod executable;
 pub mod file_ops;
 pub mod os_info;
+pub mod snapshot;
 
 pub use arch::{get_architecture, Architecture};
 pub use dirs::XpmDirs;
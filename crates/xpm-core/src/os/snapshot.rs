@@ -0,0 +1,210 @@
+//! Filesystem snapshots for finding the files a script changed
+//!
+//! A snapshot records the size and modification time of every file under a
+//! set of roots. Comparing the snapshots taken before and after a script ran
+//! gives the files it created, modified or removed there.
+
+use super::XpmDirs;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// How a file changed between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    /// Get the change as a word for display
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+        }
+    }
+}
+
+/// A file that changed between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// Size and modification time of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// The files under a set of roots at one point in time
+///
+/// Symlinks are recorded without being followed, and directories only
+/// through the files they contain.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    files: BTreeMap<PathBuf, FileState>,
+}
+
+impl Snapshot {
+    /// Record the files under the given roots, skipping the missing ones
+    pub fn take<P: AsRef<Path>>(roots: &[P]) -> Self {
+        Self::take_excluding(roots, &[] as &[&Path])
+    }
+
+    /// Record the files under the given roots, except those under `excluded`
+    pub fn take_excluding<P: AsRef<Path>, Q: AsRef<Path>>(roots: &[P], excluded: &[Q]) -> Self {
+        let mut files = BTreeMap::new();
+
+        for root in roots {
+            for entry in WalkDir::new(root.as_ref())
+                .follow_links(false)
+                .into_iter()
+                .filter_entry(|e| !excluded.iter().any(|dir| e.path() == dir.as_ref()))
+                .filter_map(|e| e.ok())
+                .filter(|e| !e.file_type().is_dir())
+            {
+                let Ok(metadata) = entry.path().symlink_metadata() else {
+                    continue;
+                };
+                files.insert(
+                    entry.path().to_path_buf(),
+                    FileState {
+                        len: metadata.len(),
+                        modified: metadata.modified().ok(),
+                    },
+                );
+            }
+        }
+
+        Self { files }
+    }
+
+    /// Record the files under the given roots on the blocking thread pool
+    pub async fn take_async(roots: &[PathBuf]) -> Result<Self> {
+        let roots = roots.to_vec();
+        tokio::task::spawn_blocking(move || Self::take(&roots))
+            .await
+            .context("Snapshot task panicked")
+    }
+
+    /// Record the files under [`XpmDirs::watched_dirs`] on the blocking thread pool
+    ///
+    /// XPM's own data, config and cache directories are left out, so its
+    /// database, repositories and logs are not taken for files a script
+    /// created.
+    pub async fn watched() -> Result<Self> {
+        let roots = XpmDirs::watched_dirs()?;
+        let excluded = [
+            XpmDirs::data_dir()?,
+            XpmDirs::config_dir()?,
+            XpmDirs::cache_dir()?,
+        ];
+        tokio::task::spawn_blocking(move || Self::take_excluding(&roots, &excluded))
+            .await
+            .context("Snapshot task panicked")
+    }
+
+    /// Number of files recorded
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check whether no file was recorded
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Get the files that differ in a later snapshot, sorted by path
+    pub fn changes(&self, after: &Snapshot) -> Vec<FileChange> {
+        let mut changes: Vec<FileChange> = after
+            .files
+            .iter()
+            .filter_map(|(path, state)| {
+                let kind = match self.files.get(path) {
+                    None => ChangeKind::Created,
+                    Some(before) if before != state => ChangeKind::Modified,
+                    Some(_) => return None,
+                };
+                Some(FileChange {
+                    path: path.clone(),
+                    kind,
+                })
+            })
+            .collect();
+
+        changes.extend(
+            self.files
+                .keys()
+                .filter(|path| !after.files.contains_key(*path))
+                .map(|path| FileChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Removed,
+                }),
+        );
+
+        changes.sort();
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_snapshot_changes() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("kept"), "same").unwrap();
+        std::fs::write(root.join("changed"), "old").unwrap();
+        std::fs::write(root.join("removed"), "gone").unwrap();
+
+        let before = Snapshot::take(&[root, &root.join("missing")]);
+        assert_eq!(before.len(), 3);
+
+        std::fs::write(root.join("changed"), "longer content").unwrap();
+        std::fs::remove_file(root.join("removed")).unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/new"), "new").unwrap();
+
+        let changes = before.changes(&Snapshot::take(&[root]));
+        assert_eq!(
+            changes,
+            vec![
+                FileChange {
+                    path: root.join("changed"),
+                    kind: ChangeKind::Modified
+                },
+                FileChange {
+                    path: root.join("removed"),
+                    kind: ChangeKind::Removed
+                },
+                FileChange {
+                    path: root.join("sub/new"),
+                    kind: ChangeKind::Created
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_excluding() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir(root.join("own")).unwrap();
+        std::fs::write(root.join("own/db"), "db").unwrap();
+        std::fs::write(root.join("app"), "app").unwrap();
+
+        let snapshot = Snapshot::take_excluding(&[root], &[root.join("own")]);
+        assert_eq!(snapshot.len(), 1);
+    }
+}
@@ -1,13 +1,17 @@
 //! Repository management module
 
-use crate::db::{Database, Package, Repo};
+pub mod signing;
+
+use crate::db::{Database, Operation, Package, Repo, RepoPackage, RepoRef, Transaction};
 use crate::os::dirs::XpmDirs;
 use crate::script::{BashScript, ScriptMetadata};
+use crate::utils::checksum::{Checksum, ChecksumAlgorithm};
 use crate::utils::slugify::slugify;
 use crate::DEFAULT_REPO;
 use anyhow::{Context, Result};
 use chrono::Utc;
 use git2::Repository;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
@@ -27,29 +31,83 @@ impl Repositories {
     }
 
     /// Add or update a repository
-    pub async fn add_repo(url: &str) -> Result<Repo> {
-        let slug = slugify(url);
+    ///
+    /// The ref and priority of `repo` replace those of an existing entry.
+    /// Without a ref the clone follows the default branch of the repository.
+    pub async fn add_repo(repo: Repo) -> Result<Repo> {
+        let txn = Transaction::new(Operation::RepoAdd).with_repo(&repo.url);
+        let result = Self::sync_repo(repo).await;
+        Self::journal(txn, &result)?;
+        result
+    }
+
+    /// Clone or pull a repository and save it to the database
+    async fn sync_repo(mut repo: Repo) -> Result<Repo> {
+        let url = repo.url.clone();
+        let git_ref = repo.git_ref.take();
+        let slug = slugify(&url);
         let local_path = Self::dir(&slug, None)?;
 
         // Clone or pull
         if Self::is_git(&local_path) {
-            Self::pull(&local_path).await?;
+            Self::pull(&local_path, git_ref.as_ref()).await?;
         } else {
-            Self::clone(url, &local_path).await?;
+            Self::clone(&url, &local_path, git_ref.as_ref()).await?;
         }
 
+        // Abbreviated commits are stored in full
+        repo.git_ref = match git_ref {
+            Some(RepoRef::Commit(_)) => Some(RepoRef::Commit(Self::head_commit(&local_path)?)),
+            git_ref => git_ref,
+        };
+
         // Save to database
         let db = Database::instance()?;
-        let mut repo = Repo::new(url);
+        // Trust is managed with `xpm repo trust` and survives adding the repository again
+        if repo.trusted_key.is_none() {
+            repo.trusted_key = db.find_repo_by_url(&url)?.and_then(|r| r.trusted_key);
+        }
         repo.local_path = Some(local_path.to_string_lossy().to_string());
         repo.last_sync = Some(Utc::now());
 
         db.upsert_repo(repo)
     }
 
+    /// Work out whether a name is a branch, tag or commit of a remote repository
+    ///
+    /// Branches win over tags, like `git checkout` does. Names that are
+    /// neither but look like a commit id are taken as one.
+    pub async fn resolve_ref(url: &str, name: &str) -> Result<RepoRef> {
+        let url = url.to_string();
+        let name = name.to_string();
+
+        tokio::task::spawn_blocking(move || -> Result<RepoRef> {
+            let mut remote = git2::Remote::create_detached(url.as_str())?;
+            remote
+                .connect(git2::Direction::Fetch)
+                .with_context(|| format!("Failed to reach repository {}", url))?;
+            let refs: Vec<String> = remote
+                .list()?
+                .iter()
+                .map(|head| head.name().to_string())
+                .collect();
+
+            if refs.contains(&format!("refs/heads/{}", name)) {
+                Ok(RepoRef::Branch(name))
+            } else if refs.contains(&format!("refs/tags/{}", name)) {
+                Ok(RepoRef::Tag(name))
+            } else if name.len() >= 4 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+                Ok(RepoRef::Commit(name))
+            } else {
+                anyhow::bail!("{} has no branch, tag or commit named {}", url, name)
+            }
+        })
+        .await?
+    }
+
     /// Add the default popular repository
     pub async fn add_default() -> Result<Repo> {
-        Self::add_repo(DEFAULT_REPO).await
+        Self::add_repo(Repo::new(DEFAULT_REPO)).await
     }
 
     /// Get all repositories
@@ -60,6 +118,17 @@ impl Repositories {
 
     /// Remove a repository
     pub async fn remove_repo(url: &str) -> Result<bool> {
+        let txn = Transaction::new(Operation::RepoRemove).with_repo(url);
+        let result = Self::delete_repo(url).await;
+        Self::journal(txn, &result)?;
+        result
+    }
+
+    /// Delete a repository's local clone and database entry
+    ///
+    /// Its packages are taken from the remaining repositories right away, or
+    /// dropped when no other repository ships them and they are not installed.
+    async fn delete_repo(url: &str) -> Result<bool> {
         let db = Database::instance()?;
 
         // Delete local directory
@@ -69,7 +138,14 @@ impl Repositories {
             tokio::fs::remove_dir_all(&local_path).await?;
         }
 
-        db.delete_repo(url)
+        if !db.delete_repo(url)? {
+            return Ok(false);
+        }
+
+        db.delete_uninstalled_packages()?;
+        Self::resolve_packages()?;
+        db.rebuild_search_index()?;
+        Ok(true)
     }
 
     /// Pull all repositories
@@ -87,9 +163,9 @@ impl Repositories {
             let local_path = Self::dir(&slug, None)?;
 
             if Self::is_git(&local_path) {
-                Self::pull(&local_path).await?;
+                Self::pull(&local_path, repo.git_ref.as_ref()).await?;
             } else {
-                Self::clone(&repo.url, &local_path).await?;
+                Self::clone(&repo.url, &local_path, repo.git_ref.as_ref()).await?;
             }
         }
 
@@ -98,6 +174,14 @@ impl Repositories {
 
     /// Index all packages from repositories
     pub async fn index() -> Result<usize> {
+        let txn = Transaction::new(Operation::Refresh);
+        let result = Self::index_packages().await;
+        Self::journal(txn, &result)?;
+        result
+    }
+
+    /// Pull repositories and rebuild the package index
+    async fn index_packages() -> Result<usize> {
         let db = Database::instance()?;
 
         // Delete non-installed packages
@@ -111,60 +195,242 @@ impl Repositories {
         let mut indexed = 0;
 
         for repo in repos {
-            let slug = slugify(&repo.url);
-            let local_path = Self::dir(&slug, None)?;
+            indexed += Self::index_repo(&repo)?;
+        }
 
-            // Find all package directories
-            for entry in WalkDir::new(&local_path)
-                .min_depth(1)
-                .max_depth(1)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if !entry.file_type().is_dir() {
-                    continue;
-                }
+        Self::resolve_packages()?;
+        db.rebuild_search_index()?;
 
-                let pkg_name = entry.file_name().to_string_lossy().to_string();
+        Ok(indexed)
+    }
 
-                // Skip hidden directories
-                if pkg_name.starts_with('.') {
-                    continue;
-                }
+    /// Read every package script of a repository clone
+    ///
+    /// The scripts are stored per repository; [`Self::resolve_packages`]
+    /// decides which repository each package is taken from.
+    pub fn index_repo(repo: &Repo) -> Result<usize> {
+        let mut packages = Vec::new();
 
-                // Find package script
-                let script_path = entry.path().join(format!("{}.bash", pkg_name));
-                if !script_path.exists() {
-                    continue;
-                }
+        let slug = slugify(&repo.url);
+        let local_path = Self::dir(&slug, None)?;
 
-                // Parse script metadata
-                let script = BashScript::new(&script_path);
-                if !script.exists() {
-                    continue;
-                }
+        // Find all package directories
+        for entry in WalkDir::new(&local_path)
+            .min_depth(1)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if !entry.file_type().is_dir() {
+                continue;
+            }
 
-                let metadata = ScriptMetadata::from_script(&script);
-
-                // Create package
-                let mut package = Package::new(&pkg_name);
-                package.script = Some(script_path.to_string_lossy().to_string());
-                package.desc = metadata.desc;
-                package.version = metadata.version;
-                package.title = metadata.title;
-                package.url = metadata.url;
-                package.arch = metadata.archs;
-                package.methods = metadata.methods;
-                package.defaults = metadata.defaults;
-                package.repo_id = Some(repo.id);
-
-                // Upsert to database
-                db.upsert_package(package)?;
-                indexed += 1;
+            let pkg_name = entry.file_name().to_string_lossy().to_string();
+
+            // Skip hidden directories
+            if pkg_name.starts_with('.') {
+                continue;
+            }
+
+            // Find package script
+            let script_path = entry.path().join(format!("{}.bash", pkg_name));
+            if let Some(package) = Self::read_script(repo, &pkg_name, &script_path) {
+                packages.push(package);
             }
         }
 
-        Ok(indexed)
+        Database::instance()?.replace_repo_packages(repo.id, packages)
+    }
+
+    /// Read a package script
+    ///
+    /// Returns None when the script does not exist.
+    fn read_script(repo: &Repo, name: &str, script_path: &Path) -> Option<Package> {
+        // Parse script metadata
+        let script = BashScript::new(script_path);
+        if !script.exists() {
+            return None;
+        }
+
+        let metadata = ScriptMetadata::from_script(&script);
+
+        // Create package
+        let mut package = Package::new(name);
+        package.script = Some(script_path.to_string_lossy().to_string());
+        package.desc = metadata.desc;
+        package.version = metadata.version;
+        package.title = metadata.title;
+        package.url = metadata.url;
+        package.arch = metadata.archs;
+        package.methods = metadata.methods;
+        package.defaults = metadata.defaults;
+        package.depends = metadata.depends;
+        package.recommends = metadata.recommends;
+        package.conflicts = metadata.conflicts;
+        package.provides = metadata.provides;
+        package.repo_id = Some(repo.id);
+
+        Some(package)
+    }
+
+    /// Decide which repository every package is taken from
+    ///
+    /// Installed packages stay with the repository they were installed from
+    /// while it still ships them. Everything else comes from the repository
+    /// with the highest priority.
+    pub fn resolve_packages() -> Result<()> {
+        let db = Database::instance()?;
+        let mut repos = Self::all_repos()?;
+        repos.sort_by(Repo::precedence);
+
+        let mut shipped: BTreeMap<String, Vec<RepoPackage>> = BTreeMap::new();
+        for pkg in db.get_repo_packages()? {
+            shipped.entry(pkg.name.clone()).or_default().push(pkg);
+        }
+
+        for (name, candidates) in shipped {
+            let current = db.find_package_by_name(&name)?;
+            if let Some(chosen) = pick_candidate(&candidates, &repos, current.as_ref()) {
+                Self::store_candidate(chosen, current.as_ref())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take a package from a given repository, whatever its priority
+    ///
+    /// Used when installing `repo/package`; the package keeps coming from
+    /// that repository once it is installed.
+    pub fn use_repo_package(repo: &Repo, name: &str) -> Result<()> {
+        let db = Database::instance()?;
+        let candidate = db
+            .find_repo_packages(name)?
+            .into_iter()
+            .find(|p| p.repo_id == repo.id)
+            .with_context(|| {
+                format!(
+                    "Repository {} does not have package '{}'",
+                    Self::repo_name(&repo.url),
+                    name
+                )
+            })?;
+
+        Self::store_candidate(&candidate, db.find_package_by_name(name)?.as_ref())
+    }
+
+    /// Store a repository package as the package of its name
+    fn store_candidate(candidate: &RepoPackage, current: Option<&Package>) -> Result<()> {
+        let mut package = candidate.package.clone();
+        // Reindexing must not forget what is installed
+        if let Some(existing) = current {
+            package.keep_install_state(existing);
+        }
+
+        Database::instance()?.upsert_package(package)?;
+        Ok(())
+    }
+
+    /// Trust a minisign public key for a repository
+    ///
+    /// Scripts from the repository are then only installed when its signed
+    /// index verifies with this key.
+    pub fn trust(repo: &Repo, public_key: &str) -> Result<Repo> {
+        let mut repo = repo.clone();
+        repo.trusted_key = Some(signing::parse_public_key(public_key)?);
+        Database::instance()?.upsert_repo(repo)
+    }
+
+    /// Check a package script against the signed index of its repository
+    ///
+    /// `content` is the script as it will run, read once by the caller so the
+    /// file cannot change between the check and the run. Fails with a
+    /// [`signing::TrustError`] when the repository has no trusted key, is not
+    /// signed, or does not vouch for `content`.
+    pub fn verify_script(pkg: &Package, content: &str) -> Result<()> {
+        let script = pkg
+            .script
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Package has no installation script"))?;
+        let repo = pkg
+            .repo_id
+            .map(|id| Database::instance()?.find_repo(id))
+            .transpose()?
+            .flatten()
+            .ok_or_else(|| anyhow::anyhow!("Repository of {} not found", pkg.name))?;
+
+        let name = Self::repo_name(&repo.url);
+        let Some(public_key) = &repo.trusted_key else {
+            return Err(signing::TrustError::NoTrustedKey(name).into());
+        };
+        let root = repo
+            .local_path
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Repository {} has not been cloned", name))?;
+
+        let index = signing::SignedIndex::load(Path::new(root), public_key, &name)?;
+        let relative = Path::new(script)
+            .strip_prefix(root)
+            .with_context(|| format!("Script of {} is outside its repository", pkg.name))?;
+        let sha256 = Checksum::hash_bytes(content.as_bytes(), ChecksumAlgorithm::Sha256);
+        index.check(&relative.to_string_lossy(), &sha256)?;
+
+        Ok(())
+    }
+
+    /// Find packages shipped by more than one repository
+    ///
+    /// Maps each package to the repositories shipping it, by precedence.
+    pub fn conflicts() -> Result<BTreeMap<String, Vec<Repo>>> {
+        let mut repos = Self::all_repos()?;
+        repos.sort_by(Repo::precedence);
+
+        let mut shipped: BTreeMap<String, Vec<Repo>> = BTreeMap::new();
+        for pkg in Database::instance()?.get_repo_packages()? {
+            if let Some(repo) = repos.iter().find(|r| r.id == pkg.repo_id) {
+                shipped.entry(pkg.name).or_default().push(repo.clone());
+            }
+        }
+
+        shipped.retain(|_, repos| repos.len() > 1);
+        for repos in shipped.values_mut() {
+            repos.sort_by(Repo::precedence);
+        }
+
+        Ok(shipped)
+    }
+
+    /// Find a repository by URL, slug or name
+    ///
+    /// Accepts the full URL, the `owner/name` shown by `xpm repo list`, just
+    /// the name, or the directory slug of the clone.
+    pub fn find_repo(spec: &str) -> Result<Option<Repo>> {
+        let matching: Vec<Repo> = Self::all_repos()?
+            .into_iter()
+            .filter(|r| {
+                let name = Self::repo_name(&r.url);
+                r.url == spec
+                    || slugify(&r.url) == spec
+                    || name == spec
+                    || name.rsplit('/').next() == Some(spec)
+            })
+            .collect();
+
+        match matching.len() {
+            0 => Ok(None),
+            1 => Ok(matching.into_iter().next()),
+            _ => anyhow::bail!(
+                "'{}' matches several repositories, use its URL or slug instead",
+                spec
+            ),
+        }
+    }
+
+    /// Record a finished repository operation in the journal
+    fn journal<T>(mut txn: Transaction, result: &Result<T>) -> Result<()> {
+        txn.finish(if result.is_ok() { Some(0) } else { None });
+        Database::instance()?.record_transaction(txn)?;
+        Ok(())
     }
 
     /// Check if path is a git repository
@@ -172,59 +438,208 @@ impl Repositories {
         path.join(".git").exists()
     }
 
-    /// Clone a repository
-    async fn clone(url: &str, path: &Path) -> Result<()> {
+    /// Clone a repository, checking out a ref when one is given
+    async fn clone(url: &str, path: &Path, git_ref: Option<&RepoRef>) -> Result<()> {
         let url = url.to_string();
         let path = path.to_path_buf();
+        let git_ref = git_ref.cloned();
 
-        tokio::task::spawn_blocking(move || {
-            Repository::clone(&url, &path)
-                .context("Failed to clone repository")
-                .map(|_| ())
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let repo = Repository::clone(&url, &path).context("Failed to clone repository")?;
+            if let Some(git_ref) = &git_ref {
+                Self::update(&repo, git_ref)?;
+            }
+            Ok(())
         })
         .await?
     }
 
     /// Pull a repository
-    async fn pull(path: &Path) -> Result<()> {
+    async fn pull(path: &Path, git_ref: Option<&RepoRef>) -> Result<()> {
         let path = path.to_path_buf();
+        let git_ref = git_ref.cloned();
 
         tokio::task::spawn_blocking(move || -> Result<()> {
             let repo = Repository::open(&path).context("Failed to open repository")?;
+            match &git_ref {
+                Some(git_ref) => Self::update(&repo, git_ref),
+                None => Self::follow_default_branch(&repo),
+            }
+        })
+        .await?
+    }
 
-            // Reset hard first
-            let head = repo.head()?.peel_to_commit()?;
-            repo.reset(head.as_object(), git2::ResetType::Hard, None)?;
+    /// Fast-forward the default branch of a clone
+    fn follow_default_branch(repo: &Repository) -> Result<()> {
+        // Locked installs and repo refs move HEAD elsewhere, go back to the branch
+        let on_default = repo
+            .head()
+            .is_ok_and(|head| matches!(head.name(), Some("refs/heads/main" | "refs/heads/master")));
+        if !on_default {
+            let branch = ["refs/heads/main", "refs/heads/master"]
+                .into_iter()
+                .find(|r| repo.find_reference(r).is_ok());
+            if let Some(branch) = branch {
+                repo.set_head(branch)?;
+            }
+        }
+
+        // Reset hard first
+        let head = repo.head()?.peel_to_commit()?;
+        repo.reset(head.as_object(), git2::ResetType::Hard, None)?;
 
-            // Fetch
-            let mut remote = repo.find_remote("origin")?;
-            remote.fetch(&["main", "master"], None, None)?;
+        // Fetch
+        let mut remote = repo.find_remote("origin")?;
+        remote.fetch(&["main", "master"], None, None)?;
 
-            // Get the fetch head
-            let fetch_head = repo.find_reference("FETCH_HEAD")?;
-            let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
+        // Get the fetch head
+        let fetch_head = repo.find_reference("FETCH_HEAD")?;
+        let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
 
-            // Merge
-            let (analysis, _) = repo.merge_analysis(&[&fetch_commit])?;
+        // Merge
+        let (analysis, _) = repo.merge_analysis(&[&fetch_commit])?;
 
-            if analysis.is_fast_forward() {
-                let refname = format!("refs/heads/{}", "main");
+        if analysis.is_fast_forward() {
+            let refname = format!("refs/heads/{}", "main");
+            if let Ok(mut reference) = repo.find_reference(&refname) {
+                reference.set_target(fetch_commit.id(), "Fast-Forward")?;
+            } else {
+                let refname = format!("refs/heads/{}", "master");
                 if let Ok(mut reference) = repo.find_reference(&refname) {
                     reference.set_target(fetch_commit.id(), "Fast-Forward")?;
-                } else {
-                    let refname = format!("refs/heads/{}", "master");
-                    if let Ok(mut reference) = repo.find_reference(&refname) {
-                        reference.set_target(fetch_commit.id(), "Fast-Forward")?;
-                    }
                 }
+            }
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Fetch a ref and move the clone to it
+    ///
+    /// Branches are followed, tags and commits leave HEAD detached. Local
+    /// changes are discarded, as on the default branch.
+    fn update(repo: &Repository, git_ref: &RepoRef) -> Result<()> {
+        let mut remote = repo.find_remote("origin")?;
+
+        let target = match git_ref {
+            RepoRef::Branch(branch) => {
+                let tracking = format!("refs/remotes/origin/{}", branch);
+                remote
+                    .fetch(
+                        &[format!("+refs/heads/{}:{}", branch, tracking)],
+                        None,
+                        None,
+                    )
+                    .with_context(|| format!("Failed to fetch branch {}", branch))?;
+                let commit = repo.find_reference(&tracking)?.peel_to_commit()?;
+
+                let local = format!("refs/heads/{}", branch);
+                repo.reference(&local, commit.id(), true, "Follow branch")?;
+                repo.set_head(&local)?;
                 repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+                return Ok(());
+            }
+            RepoRef::Tag(tag) => {
+                let refname = format!("refs/tags/{}", tag);
+                remote
+                    .fetch(&[format!("+{0}:{0}", refname)], None, None)
+                    .with_context(|| format!("Failed to fetch tag {}", tag))?;
+                repo.find_reference(&refname)?.peel_to_commit()?
+            }
+            RepoRef::Commit(id) => {
+                let find = || repo.revparse_single(id).and_then(|o| o.peel_to_commit());
+                match find() {
+                    Ok(commit) => commit,
+                    Err(_) => {
+                        remote.fetch(
+                            &[
+                                "+refs/heads/*:refs/remotes/origin/*",
+                                "+refs/tags/*:refs/tags/*",
+                            ],
+                            None,
+                            None,
+                        )?;
+                        find().with_context(|| format!("Commit {} not found in repository", id))?
+                    }
+                }
             }
+        };
 
+        repo.set_head_detached(target.id())?;
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+        Ok(())
+    }
+
+    /// Get the commit a repository clone is at
+    pub fn head_commit(path: &Path) -> Result<String> {
+        let repo = Repository::open(path).context("Failed to open repository")?;
+        let commit = repo.head()?.peel_to_commit()?;
+        Ok(commit.id().to_string())
+    }
+
+    /// Get what HEAD of a repository clone points to
+    ///
+    /// A branch reference, or a commit when HEAD is detached, to hand back
+    /// to [`Self::restore_head`].
+    pub fn head_ref(path: &Path) -> Result<String> {
+        let repo = Repository::open(path).context("Failed to open repository")?;
+        if repo.head_detached()? {
+            return Ok(repo.head()?.peel_to_commit()?.id().to_string());
+        }
+        let head = repo.head()?;
+        head.name()
+            .map(String::from)
+            .context("HEAD is not a valid reference")
+    }
+
+    /// Put HEAD of a repository clone back where [`Self::head_ref`] found it
+    ///
+    /// Nothing is fetched, the clone goes back to what it had.
+    pub async fn restore_head(path: &Path, head: &str) -> Result<()> {
+        let path = path.to_path_buf();
+        let head = head.to_string();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let repo = Repository::open(&path).context("Failed to open repository")?;
+            if head.starts_with("refs/") {
+                repo.set_head(&head)?;
+            } else {
+                repo.set_head_detached(git2::Oid::from_str(&head)?)?;
+            }
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
             Ok(())
         })
         .await?
     }
 
+    /// Get the commit the repository clone of a package is at
+    pub fn package_commit(pkg: &Package) -> Result<Option<String>> {
+        let Some(repo_id) = pkg.repo_id else {
+            return Ok(None);
+        };
+        let repo = Database::instance()?.find_repo(repo_id)?;
+        match repo.and_then(|r| r.local_path) {
+            Some(path) => Ok(Some(Self::head_commit(Path::new(&path))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Check out a commit of a repository clone, fetching it if needed
+    ///
+    /// HEAD is left detached; the next pull moves the clone back to its ref,
+    /// or [`Self::restore_head`] to where it was.
+    pub async fn checkout(path: &Path, commit: &str) -> Result<()> {
+        let path = path.to_path_buf();
+        let commit = RepoRef::Commit(commit.to_string());
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let repo = Repository::open(&path).context("Failed to open repository")?;
+            Self::update(&repo, &commit)
+        })
+        .await?
+    }
+
     /// Get repository name from URL
     pub fn repo_name(url: &str) -> String {
         let parts: Vec<&str> = url.split('/').collect();
@@ -238,6 +653,30 @@ impl Repositories {
     }
 }
 
+/// Pick the repository package a name resolves to
+///
+/// `repos` must be sorted by precedence. Packages from unknown repositories
+/// are never picked.
+fn pick_candidate<'a>(
+    candidates: &'a [RepoPackage],
+    repos: &[Repo],
+    current: Option<&Package>,
+) -> Option<&'a RepoPackage> {
+    // Installed packages stick to their repository
+    if let Some(current) = current.filter(|p| p.is_installed() && !p.is_native) {
+        let installed_from = candidates
+            .iter()
+            .find(|c| Some(c.repo_id) == current.repo_id);
+        if installed_from.is_some() {
+            return installed_from;
+        }
+    }
+
+    repos
+        .iter()
+        .find_map(|repo| candidates.iter().find(|c| c.repo_id == repo.id))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,6 +693,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pick_candidate() {
+        let mut main = Repo::new("https://example.com/main.git");
+        main.id = 1;
+        let mut extra = Repo::new("https://example.com/extra.git").with_priority(10);
+        extra.id = 2;
+        let mut repos = vec![main, extra];
+        repos.sort_by(Repo::precedence);
+
+        let candidates = vec![
+            RepoPackage::new(1, Package::new("app")),
+            RepoPackage::new(2, Package::new("app")),
+        ];
+
+        // Highest priority wins
+        let picked = pick_candidate(&candidates, &repos, None).unwrap();
+        assert_eq!(picked.repo_id, 2);
+
+        // Installed packages keep their repository
+        let mut installed = Package::new("app");
+        installed.installed = Some("1.0".to_string());
+        installed.repo_id = Some(1);
+        let picked = pick_candidate(&candidates, &repos, Some(&installed)).unwrap();
+        assert_eq!(picked.repo_id, 1);
+
+        // Equal priorities go to the repository added first
+        repos[1].priority = 10;
+        repos.sort_by(Repo::precedence);
+        let picked = pick_candidate(&candidates, &repos, None).unwrap();
+        assert_eq!(picked.repo_id, 1);
+
+        assert!(pick_candidate(&candidates[..1], &repos[..0], None).is_none());
+    }
+
     #[test]
     fn test_slugify_url() {
         let slug = slugify("https://github.com/verseles/xpm-popular.git");
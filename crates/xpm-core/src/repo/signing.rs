@@ -0,0 +1,189 @@
+//! Signed repository indexes
+//!
+//! A signed repository ships `xpm.sum`, the SHA-256 of every package script
+//! in `sha256sum` format, and `xpm.sum.minisig`, its minisign signature:
+//!
+//! ```sh
+//! sha256sum */*.bash > xpm.sum
+//! minisign -Sm xpm.sum
+//! ```
+//!
+//! A script is only trusted when the index is signed with the key trusted for
+//! its repository and lists the script with a matching checksum.
+
+use anyhow::{Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Index of script checksums at the root of a repository
+pub const INDEX_FILE: &str = "xpm.sum";
+
+/// Minisign signature of the index
+pub const SIGNATURE_FILE: &str = "xpm.sum.minisig";
+
+/// Why a package script is not trusted
+#[derive(Debug, thiserror::Error)]
+pub enum TrustError {
+    /// No key is trusted for the repository
+    #[error("Repository {0} has no trusted key")]
+    NoTrustedKey(String),
+
+    /// The repository does not ship a signed index
+    #[error("Repository {0} is not signed ({INDEX_FILE} or {SIGNATURE_FILE} is missing)")]
+    Unsigned(String),
+
+    /// The index signature does not verify with the trusted key
+    #[error("Signature of {INDEX_FILE} in repository {0} does not match the trusted key")]
+    BadSignature(String),
+
+    /// The signed index does not list the script
+    #[error("{0} is not listed in the signed index")]
+    NotListed(String),
+
+    /// The script differs from the checksum in the signed index
+    #[error("{0} does not match the signed index, it may have been tampered with")]
+    Tampered(String),
+}
+
+/// Read a minisign public key
+///
+/// Accepts the base64 key alone or the contents of a `minisign.pub` file,
+/// and returns the base64 key.
+pub fn parse_public_key(key: &str) -> Result<String> {
+    let key = key
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .unwrap_or_default();
+
+    PublicKey::from_base64(key).map_err(|e| anyhow::anyhow!("Invalid public key: {}", e))?;
+    Ok(key.to_string())
+}
+
+/// Script checksums of a repository, read from its verified index
+#[derive(Debug, Clone, Default)]
+pub struct SignedIndex {
+    checksums: HashMap<String, String>,
+}
+
+impl SignedIndex {
+    /// Read and verify the signed index of a repository clone
+    ///
+    /// `repo` names the repository in errors.
+    pub fn load(root: &Path, public_key: &str, repo: &str) -> Result<Self> {
+        let index_path = root.join(INDEX_FILE);
+        let signature_path = root.join(SIGNATURE_FILE);
+        if !index_path.exists() || !signature_path.exists() {
+            return Err(TrustError::Unsigned(repo.to_string()).into());
+        }
+
+        let content = std::fs::read(&index_path)
+            .with_context(|| format!("Failed to read {}", index_path.display()))?;
+        let signature = std::fs::read_to_string(&signature_path)
+            .with_context(|| format!("Failed to read {}", signature_path.display()))?;
+
+        Self::verify(&content, &signature, public_key, repo)
+    }
+
+    /// Verify an index against its signature
+    pub fn verify(content: &[u8], signature: &str, public_key: &str, repo: &str) -> Result<Self> {
+        let public_key = PublicKey::from_base64(public_key)
+            .map_err(|e| anyhow::anyhow!("Invalid trusted key for {}: {}", repo, e))?;
+        let signature =
+            Signature::decode(signature).map_err(|_| TrustError::BadSignature(repo.to_string()))?;
+
+        // Only prehashed signatures, as made by current minisign versions
+        public_key
+            .verify(content, &signature, false)
+            .map_err(|_| TrustError::BadSignature(repo.to_string()))?;
+
+        Ok(Self::parse(&String::from_utf8_lossy(content)))
+    }
+
+    /// Parse `sha256sum` output, ignoring lines it cannot read
+    pub fn parse(content: &str) -> Self {
+        let checksums = content
+            .lines()
+            .filter_map(|line| {
+                let (checksum, path) = line.trim().split_once(char::is_whitespace)?;
+                // sha256sum marks binary mode with a leading '*'
+                let path = path.trim_start().trim_start_matches('*');
+                let path = path.strip_prefix("./").unwrap_or(path);
+                Some((path.to_string(), checksum.to_lowercase()))
+            })
+            .collect();
+
+        Self { checksums }
+    }
+
+    /// Check a script, by its path inside the repository, against the index
+    pub fn check(&self, script: &str, sha256: &str) -> Result<(), TrustError> {
+        match self.checksums.get(script) {
+            None => Err(TrustError::NotListed(script.to_string())),
+            Some(expected) if !expected.eq_ignore_ascii_case(sha256) => {
+                Err(TrustError::Tampered(script.to_string()))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Made with a throwaway minisign key
+    const PUBLIC_KEY: &str = "RWT/SHA3Zp1HKLIPLUW3x0P+XnlUFfuODDgwTn45ZqubCwd/9G1Xs1/n";
+    const INDEX: &str = "
+e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  app/app.bash
+";
+    const SIGNATURE: &str = "untrusted comment: signature
+RUT/SHA3Zp1HKMZcMdyzGdJIpBmJLl58C/sRLtsrTwQtJzRsS0BOc7C5W39t08IfkCj1dJuv/2Xi1Tos1x4RPu2kr7+wdUYNlQE=
+trusted comment: timestamp:0
+cQeROCLpWEnL5Qwz32VRxZJVWppiRcihsPFhSRe7NlsC+KsMpPcP2L2/6Po9rbkMCj9g7dTCBc69V8U+i8UkBQ==
+";
+
+    #[test]
+    fn test_parse_public_key() {
+        assert_eq!(parse_public_key(PUBLIC_KEY).unwrap(), PUBLIC_KEY);
+
+        let file = format!("untrusted comment: minisign public key\n{}\n", PUBLIC_KEY);
+        assert_eq!(parse_public_key(&file).unwrap(), PUBLIC_KEY);
+
+        assert!(parse_public_key("not a key").is_err());
+    }
+
+    #[test]
+    fn test_verify_index() {
+        let index = SignedIndex::verify(INDEX.as_bytes(), SIGNATURE, PUBLIC_KEY, "test").unwrap();
+        let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert!(index.check("app/app.bash", empty).is_ok());
+
+        let tampered = INDEX.replace("app/app.bash", "app/other.bash");
+        let err =
+            SignedIndex::verify(tampered.as_bytes(), SIGNATURE, PUBLIC_KEY, "test").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TrustError>(),
+            Some(TrustError::BadSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_script() {
+        let index = SignedIndex::parse(
+            "abc123  ./app/app.bash\nDEF456 *lib/lib.bash\nnot a checksum line\n",
+        );
+
+        assert!(index.check("app/app.bash", "ABC123").is_ok());
+        assert!(index.check("lib/lib.bash", "def456").is_ok());
+        assert!(matches!(
+            index.check("app/app.bash", "000"),
+            Err(TrustError::Tampered(_))
+        ));
+        assert!(matches!(
+            index.check("other/other.bash", "abc123"),
+            Err(TrustError::NotListed(_))
+        ));
+    }
+}
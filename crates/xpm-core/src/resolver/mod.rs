@@ -0,0 +1,308 @@
+//! Dependency resolution for XPM packages
+//!
+//! Walks `xDEPENDS` declarations depth-first to produce an install order in
+//! which every package comes after its dependencies. Dependencies that are not
+//! in the XPM index are left to the native package manager.
+
+use crate::db::{Database, Package};
+use anyhow::Result;
+use std::collections::HashSet;
+
+/// A single step of an install plan
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstallStep {
+    /// Package with an XPM script
+    Xpm(Box<Package>),
+    /// Package unknown to XPM, installed via the native package manager
+    Native(String),
+}
+
+impl InstallStep {
+    /// Get the package name
+    pub fn name(&self) -> &str {
+        match self {
+            InstallStep::Xpm(pkg) => &pkg.name,
+            InstallStep::Native(name) => name,
+        }
+    }
+}
+
+/// Ordered list of packages to install
+#[derive(Debug, Clone, Default)]
+pub struct InstallPlan {
+    /// Steps in install order; the requested package is always last
+    pub steps: Vec<InstallStep>,
+
+    /// Recommended packages that are not installed
+    pub recommends: Vec<String>,
+}
+
+impl InstallPlan {
+    /// Get the dependencies to install before the requested package
+    pub fn dependencies(&self) -> &[InstallStep] {
+        &self.steps[..self.steps.len().saturating_sub(1)]
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.steps.iter().any(|s| s.name() == name)
+    }
+}
+
+/// Errors that make an install plan impossible
+#[derive(Debug, thiserror::Error)]
+pub enum ResolveError {
+    #[error("Dependency cycle detected: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    #[error("Cannot install {package}: it conflicts with installed package {other}")]
+    ConflictsInstalled { package: String, other: String },
+
+    #[error("Cannot install {package}: it conflicts with {other}, which is also being installed")]
+    Conflict { package: String, other: String },
+}
+
+/// Dependency resolver
+pub struct Resolver<F> {
+    lookup: F,
+    installed: Vec<Package>,
+}
+
+impl<F> Resolver<F>
+where
+    F: Fn(&str) -> Result<Option<Package>>,
+{
+    /// Create a resolver from a package lookup and the currently installed packages
+    pub fn new(lookup: F, installed: Vec<Package>) -> Self {
+        Self { lookup, installed }
+    }
+
+    /// Compute the install plan for a package
+    pub fn resolve(&self, package: &Package) -> Result<InstallPlan> {
+        let mut plan = InstallPlan::default();
+        let mut done = HashSet::new();
+        let mut path = Vec::new();
+
+        self.visit(package, &mut path, &mut done, &mut plan)?;
+        self.check_conflicts(&plan)?;
+
+        let mut recommends = Vec::new();
+        for step in &plan.steps {
+            if let InstallStep::Xpm(pkg) = step {
+                for name in &pkg.recommends {
+                    if !plan.contains(name)
+                        && !self.is_installed(name)
+                        && !recommends.contains(name)
+                    {
+                        recommends.push(name.clone());
+                    }
+                }
+            }
+        }
+        plan.recommends = recommends;
+
+        Ok(plan)
+    }
+
+    fn visit(
+        &self,
+        package: &Package,
+        path: &mut Vec<String>,
+        done: &mut HashSet<String>,
+        plan: &mut InstallPlan,
+    ) -> Result<()> {
+        path.push(package.name.clone());
+
+        for dep in &package.depends {
+            if let Some(pos) = path.iter().position(|n| n == dep) {
+                let mut cycle = path[pos..].to_vec();
+                cycle.push(dep.clone());
+                return Err(ResolveError::Cycle(cycle).into());
+            }
+
+            if done.contains(dep) {
+                continue;
+            }
+
+            match (self.lookup)(dep)? {
+                Some(pkg) if pkg.is_installed() => {
+                    done.insert(dep.clone());
+                }
+                Some(pkg) if pkg.script.is_some() => {
+                    self.visit(&pkg, path, done, plan)?;
+                }
+                _ => {
+                    done.insert(dep.clone());
+                    plan.steps.push(InstallStep::Native(dep.clone()));
+                }
+            }
+        }
+
+        path.pop();
+        done.insert(package.name.clone());
+        plan.steps.push(InstallStep::Xpm(Box::new(package.clone())));
+
+        Ok(())
+    }
+
+    fn check_conflicts(&self, plan: &InstallPlan) -> Result<()> {
+        for step in &plan.steps {
+            let InstallStep::Xpm(pkg) = step else {
+                continue;
+            };
+
+            for other in pkg.conflicts.iter().filter(|c| **c != pkg.name) {
+                if plan.contains(other) {
+                    return Err(ResolveError::Conflict {
+                        package: pkg.name.clone(),
+                        other: other.clone(),
+                    }
+                    .into());
+                }
+                if self.is_installed(other) {
+                    return Err(ResolveError::ConflictsInstalled {
+                        package: pkg.name.clone(),
+                        other: other.clone(),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        // Installed packages may declare the conflict instead
+        for installed in self.installed.iter().filter(|i| !plan.contains(&i.name)) {
+            if let Some(name) = installed.conflicts.iter().find(|c| plan.contains(c)) {
+                return Err(ResolveError::ConflictsInstalled {
+                    package: name.clone(),
+                    other: installed.name.clone(),
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_installed(&self, name: &str) -> bool {
+        self.installed.iter().any(|p| p.name == name)
+    }
+}
+
+/// Compute the install plan for a package against the XPM database
+pub fn resolve_install(package: &Package) -> Result<InstallPlan> {
+    let db = Database::instance()?;
+    let installed = db.get_installed_packages()?;
+    Resolver::new(|name| db.find_package_by_name(name), installed).resolve(package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn package(name: &str, depends: &[&str]) -> Package {
+        let mut pkg = Package::new(name);
+        pkg.script = Some(format!("/repo/{name}/{name}.bash"));
+        pkg.depends = depends.iter().map(|s| s.to_string()).collect();
+        pkg
+    }
+
+    fn resolve(index: &[Package], installed: &[Package], root: &str) -> Result<InstallPlan> {
+        let index: HashMap<String, Package> = index
+            .iter()
+            .chain(installed)
+            .map(|p| (p.name.clone(), p.clone()))
+            .collect();
+        let root = index[root].clone();
+        Resolver::new(|name| Ok(index.get(name).cloned()), installed.to_vec()).resolve(&root)
+    }
+
+    fn names(plan: &InstallPlan) -> Vec<&str> {
+        plan.steps.iter().map(|s| s.name()).collect()
+    }
+
+    #[test]
+    fn test_install_order() {
+        let index = [
+            package("app", &["lib", "tool"]),
+            package("lib", &["base"]),
+            package("tool", &["base"]),
+            package("base", &[]),
+        ];
+
+        let plan = resolve(&index, &[], "app").unwrap();
+        assert_eq!(names(&plan), vec!["base", "lib", "tool", "app"]);
+        assert_eq!(plan.dependencies().len(), 3);
+    }
+
+    #[test]
+    fn test_native_and_installed_dependencies() {
+        let index = [package("app", &["curl", "lib"])];
+        let mut lib = package("lib", &[]);
+        lib.installed = Some("1.0".to_string());
+
+        let plan = resolve(&index, &[lib], "app").unwrap();
+        assert_eq!(plan.steps[0], InstallStep::Native("curl".to_string()));
+        assert_eq!(names(&plan), vec!["curl", "app"]);
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let index = [
+            package("a", &["b"]),
+            package("b", &["c"]),
+            package("c", &["a"]),
+        ];
+
+        let err = resolve(&index, &[], "a").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Dependency cycle detected: a -> b -> c -> a"
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut app = package("app", &["lib"]);
+        app.conflicts = vec!["legacy".to_string()];
+        let mut legacy = package("legacy", &[]);
+        legacy.installed = Some("1.0".to_string());
+
+        let err = resolve(&[app.clone(), package("lib", &[])], &[legacy], "app").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ResolveError>(),
+            Some(ResolveError::ConflictsInstalled { .. })
+        ));
+
+        let mut lib = package("lib", &[]);
+        lib.conflicts = vec!["app".to_string()];
+        let err = resolve(&[app, lib], &[], "app").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ResolveError>(),
+            Some(ResolveError::Conflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_installed_package_declares_conflict() {
+        let mut old = package("old", &[]);
+        old.installed = Some("1.0".to_string());
+        old.conflicts = vec!["new".to_string()];
+
+        let err = resolve(&[package("new", &[])], &[old], "new").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot install new: it conflicts with installed package old"
+        );
+    }
+
+    #[test]
+    fn test_recommends() {
+        let mut app = package("app", &[]);
+        app.recommends = vec!["extras".to_string(), "docs".to_string()];
+        let mut docs = package("docs", &[]);
+        docs.installed = Some("1.0".to_string());
+
+        let plan = resolve(&[app], &[docs], "app").unwrap();
+        assert_eq!(plan.recommends, vec!["extras"]);
+    }
+}
@@ -0,0 +1,656 @@
+//! Static safety checks for package scripts
+//!
+//! The linter reads a script without running it and reports missing metadata,
+//! unknown architectures, install methods that cannot be removed and shell
+//! patterns that are dangerous in a script run with elevated privileges.
+//! Shell parsing is deliberately shallow: it works line by line, so it can
+//! miss constructs spread over heredocs or nested command substitutions.
+
+use super::BashScript;
+use crate::os::arch::Architecture;
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+
+/// Metadata every package script must define
+const REQUIRED_METADATA: [&str; 3] = ["xNAME", "xVERSION", "xDESC"];
+
+/// Commands that run their arguments with elevated privileges
+///
+/// Install and remove methods receive `$xSUDO` as their first argument.
+const PRIVILEGED: [&str; 6] = ["sudo", "doas", "$xSUDO", "${xSUDO}", "$1", "${1}"];
+
+/// Words that can precede a command without being one
+const PREFIXES: [&str; 12] = [
+    "{", "(", "!", "if", "elif", "then", "else", "while", "until", "do", "time", "exec",
+];
+
+/// Shells that run a script read from stdin
+const SHELLS: [&str; 5] = ["sh", "bash", "zsh", "dash", "ksh"];
+
+/// A shell running a download through command or process substitution, as in
+/// `sh -c "$(curl ...)"` and `bash <(curl ...)`
+static SUBSTITUTED_DOWNLOAD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\b(ba|z|da|k)?sh\b[^|;&]*(\$\(|<\()\s*(curl|wget)\b"#).expect("valid regex")
+});
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A check performed by the linter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    MissingMetadata,
+    UnknownArch,
+    MissingRemove,
+    PipeToShell,
+    RemoveRoot,
+    UnquotedSudoRm,
+    WriteOutsidePrefix,
+}
+
+impl Rule {
+    /// Every rule, in the order they are documented
+    pub const ALL: [Rule; 7] = [
+        Rule::MissingMetadata,
+        Rule::UnknownArch,
+        Rule::MissingRemove,
+        Rule::PipeToShell,
+        Rule::RemoveRoot,
+        Rule::UnquotedSudoRm,
+        Rule::WriteOutsidePrefix,
+    ];
+
+    /// Stable identifier of the rule
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::MissingMetadata => "missing-metadata",
+            Rule::UnknownArch => "unknown-arch",
+            Rule::MissingRemove => "missing-remove",
+            Rule::PipeToShell => "pipe-to-shell",
+            Rule::RemoveRoot => "remove-root",
+            Rule::UnquotedSudoRm => "unquoted-sudo-rm",
+            Rule::WriteOutsidePrefix => "write-outside-prefix",
+        }
+    }
+
+    /// One-line description of what the rule checks
+    pub fn description(&self) -> &'static str {
+        match self {
+            Rule::MissingMetadata => "The script defines xNAME, xVERSION and xDESC",
+            Rule::UnknownArch => "Every xARCHS entry is a known architecture",
+            Rule::MissingRemove => "Every install_* method has a matching remove_* method",
+            Rule::PipeToShell => "Downloaded content is not piped into a shell",
+            Rule::RemoveRoot => "rm -r is never pointed at /, ~ or $HOME",
+            Rule::UnquotedSudoRm => "Privileged rm only takes quoted variables",
+            Rule::WriteOutsidePrefix => {
+                "Redirections and cp, mv, install, ln, tee, touch, mkdir, truncate, chmod, chown, chgrp, dd and sed -i only write under $xBIN or $xTMP"
+            }
+        }
+    }
+
+    /// Severity of the findings of this rule
+    pub fn severity(&self) -> Severity {
+        match self {
+            Rule::MissingMetadata
+            | Rule::UnknownArch
+            | Rule::MissingRemove
+            | Rule::PipeToShell
+            | Rule::RemoveRoot => Severity::Error,
+            Rule::UnquotedSudoRm | Rule::WriteOutsidePrefix => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// A problem found in a script
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// 1-based line number, None for problems with the script as a whole
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(rule: Rule, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            severity: rule.severity(),
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Lint a package script
+///
+/// Findings are sorted by line, problems with the whole script first.
+pub fn lint(script: &BashScript) -> Result<Vec<Finding>> {
+    let Some(content) = script.contents() else {
+        anyhow::bail!("Failed to read {}", script.path().display());
+    };
+
+    let mut findings = Vec::new();
+    check_metadata(script, &mut findings);
+    check_archs(script, content, &mut findings);
+    check_remove_methods(script, content, &mut findings);
+    for (line, text) in logical_lines(content) {
+        check_line(line, &text, &mut findings);
+    }
+
+    findings.sort_by_key(|f| f.line);
+    Ok(findings)
+}
+
+fn check_metadata(script: &BashScript, findings: &mut Vec<Finding>) {
+    for name in REQUIRED_METADATA {
+        if script.get(name).is_none_or(|v| v.trim().is_empty()) {
+            findings.push(Finding::new(
+                Rule::MissingMetadata,
+                None,
+                format!("Missing readonly {}=\"...\"", name),
+            ));
+        }
+    }
+}
+
+fn check_archs(script: &BashScript, content: &str, findings: &mut Vec<Finding>) {
+    let Some(archs) = script.get_array("xARCHS") else {
+        return;
+    };
+    let line = line_of(content, r"\bxARCHS\s*=");
+
+    for arch in archs {
+        if Architecture::parse(&arch) == Architecture::Unknown {
+            findings.push(Finding::new(
+                Rule::UnknownArch,
+                line,
+                format!("Unknown architecture '{}' in xARCHS", arch),
+            ));
+        }
+    }
+}
+
+fn check_remove_methods(script: &BashScript, content: &str, findings: &mut Vec<Finding>) {
+    // remove_any is the fallback for every method
+    if script.has_function("remove_any") {
+        return;
+    }
+
+    let functions = script.functions().unwrap_or_default();
+    for method in functions.iter().filter_map(|f| f.strip_prefix("install_")) {
+        if !functions.iter().any(|f| f == &format!("remove_{}", method)) {
+            let fallback = if method == "any" {
+                ""
+            } else {
+                " or remove_any"
+            };
+            findings.push(Finding::new(
+                Rule::MissingRemove,
+                line_of(
+                    content,
+                    &format!(r"\binstall_{}\s*\(\s*\)", regex::escape(method)),
+                ),
+                format!(
+                    "install_{} has no matching remove_{}{}",
+                    method, method, fallback
+                ),
+            ));
+        }
+    }
+}
+
+/// Check the commands on one line of the script
+fn check_line(line: usize, text: &str, findings: &mut Vec<Finding>) {
+    let commands = parse_commands(text);
+
+    for (i, command) in commands.iter().enumerate() {
+        let words = command_words(&command.words);
+        let Some(program) = words.first() else {
+            continue;
+        };
+
+        // curl ... | sh
+        if matches!(program.text.as_str(), "curl" | "wget") && command.piped {
+            let next = commands.get(i + 1).map(|c| command_words(&c.words));
+            if let Some(shell) = next.and_then(|w| w.first()) {
+                if SHELLS.contains(&shell.text.as_str()) {
+                    findings.push(Finding::new(
+                        Rule::PipeToShell,
+                        Some(line),
+                        format!(
+                            "Output of {} is piped into {}; download to $xTMP and verify it first",
+                            program.text, shell.text
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if program.text == "rm" {
+            check_rm(line, command, words, findings);
+        }
+
+        for target in write_targets(words) {
+            if is_outside_prefix(target) {
+                findings.push(Finding::new(
+                    Rule::WriteOutsidePrefix,
+                    Some(line),
+                    format!("Writes to {} outside $xBIN and $xTMP", target),
+                ));
+            }
+        }
+    }
+
+    let reported = findings
+        .iter()
+        .any(|f| f.rule == Rule::PipeToShell && f.line == Some(line));
+    if SUBSTITUTED_DOWNLOAD.is_match(text) && !reported {
+        findings.push(Finding::new(
+            Rule::PipeToShell,
+            Some(line),
+            "Downloaded content is run by a shell; download to $xTMP and verify it first",
+        ));
+    }
+}
+
+fn check_rm(line: usize, command: &Command, words: &[Word], findings: &mut Vec<Finding>) {
+    let args = &words[1..];
+    let recursive = args.iter().any(|w| {
+        w.text == "--recursive"
+            || (w.text.starts_with('-') && !w.text.starts_with("--") && w.text.contains(['r', 'R']))
+    });
+
+    if args.iter().any(|w| w.text == "--no-preserve-root") {
+        findings.push(Finding::new(
+            Rule::RemoveRoot,
+            Some(line),
+            "rm --no-preserve-root can wipe the whole system",
+        ));
+    } else if recursive {
+        let operands = args.iter().filter(|w| !w.text.starts_with('-'));
+        for operand in operands {
+            let path = operand.text.trim_end_matches('/');
+            if matches!(
+                path,
+                "" | "/*" | "~" | "$HOME" | "${HOME}" | "$xHOME" | "${xHOME}"
+            ) {
+                findings.push(Finding::new(
+                    Rule::RemoveRoot,
+                    Some(line),
+                    format!("rm -r {} removes far more than the package", operand.text),
+                ));
+            }
+        }
+    }
+
+    let privileged = command
+        .words
+        .iter()
+        .take_while(|w| w.text != "rm")
+        .any(|w| PRIVILEGED.contains(&w.text.as_str()));
+    if privileged {
+        for operand in args.iter().filter(|w| w.unquoted_expansion) {
+            findings.push(Finding::new(
+                Rule::UnquotedSudoRm,
+                Some(line),
+                format!(
+                    "Privileged rm with unquoted {}; an empty or spaced value removes the wrong files",
+                    operand.text
+                ),
+            ));
+        }
+    }
+}
+
+/// Files a command writes to
+fn write_targets(words: &[Word]) -> Vec<&str> {
+    let mut targets = Vec::new();
+
+    // Redirections, as `> file` or `>file`
+    let mut iter = words.iter().peekable();
+    while let Some(word) = iter.next() {
+        if word.redirect {
+            if word.text.is_empty() {
+                if let Some(next) = iter.next() {
+                    targets.push(next.text.as_str());
+                }
+            } else {
+                targets.push(word.text.as_str());
+            }
+        }
+    }
+
+    let options: Vec<&str> = words[1..]
+        .iter()
+        .filter(|w| !w.redirect && w.text.starts_with('-'))
+        .map(|w| w.text.as_str())
+        .collect();
+    let operands: Vec<&str> = words[1..]
+        .iter()
+        .filter(|w| !w.redirect && !w.text.starts_with('-'))
+        .map(|w| w.text.as_str())
+        .collect();
+    match words[0].text.as_str() {
+        "cp" | "mv" | "install" | "ln" => {
+            targets.extend(operands.last().filter(|_| operands.len() > 1))
+        }
+        "tee" | "touch" | "mkdir" | "truncate" => targets.extend(operands),
+        // The first operand is the mode or the owner
+        "chmod" | "chown" | "chgrp" => targets.extend(operands.iter().skip(1)),
+        "dd" => targets.extend(operands.iter().filter_map(|w| w.strip_prefix("of="))),
+        "sed" => {
+            let in_place = options
+                .iter()
+                .any(|o| o.starts_with("--in-place") || (!o.starts_with("--") && o.contains('i')));
+            if in_place {
+                // Without -e or -f, the first operand is the sed script
+                let scripted = options
+                    .iter()
+                    .any(|o| matches!(*o, "-e" | "-f" | "--expression" | "--file"));
+                targets.extend(operands.iter().skip(usize::from(!scripted)));
+            }
+        }
+        _ => {}
+    }
+
+    targets
+}
+
+/// Check whether a path is known to lie outside the package prefixes
+///
+/// Paths relative to the working directory or built from other variables
+/// cannot be judged statically and are accepted.
+fn is_outside_prefix(path: &str) -> bool {
+    const ALLOWED: [&str; 6] = [
+        "$xBIN",
+        "${xBIN}",
+        "$xTMP",
+        "${xTMP}",
+        "/dev/null",
+        "/dev/std",
+    ];
+    const OUTSIDE: [&str; 6] = ["/", "~", "$HOME", "${HOME}", "$xHOME", "${xHOME}"];
+
+    !path.starts_with('&')
+        && !ALLOWED.iter().any(|p| path.starts_with(p))
+        && OUTSIDE.iter().any(|p| path.starts_with(p))
+}
+
+/// Find the 1-based line of the first match of a pattern
+fn line_of(content: &str, pattern: &str) -> Option<usize> {
+    let re = Regex::new(pattern).ok()?;
+    content
+        .lines()
+        .position(|line| re.is_match(line))
+        .map(|i| i + 1)
+}
+
+/// Join lines continued with a trailing backslash, keeping the first line number
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        let (start, mut text) = current.take().unwrap_or((i + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(continued) => {
+                text.push_str(continued);
+                text.push(' ');
+                current = Some((start, text));
+            }
+            None => {
+                text.push_str(line);
+                lines.push((start, text));
+            }
+        }
+    }
+    lines.extend(current);
+
+    lines
+}
+
+/// A shell word with its quotes removed
+#[derive(Debug, Default)]
+struct Word {
+    text: String,
+    /// Contains a `$` expansion outside quotes
+    unquoted_expansion: bool,
+    /// Output redirection; `text` is its target when written as `>file`
+    redirect: bool,
+}
+
+/// A simple command
+#[derive(Debug, Default)]
+struct Command {
+    words: Vec<Word>,
+    /// Its output is piped into the next command
+    piped: bool,
+}
+
+/// Skip keywords, function definitions and privilege wrappers before a command
+fn command_words(words: &[Word]) -> &[Word] {
+    let start = words
+        .iter()
+        .position(|w| {
+            !PREFIXES.contains(&w.text.as_str())
+                && !PRIVILEGED.contains(&w.text.as_str())
+                && !w.text.ends_with("()")
+        })
+        .unwrap_or(words.len());
+    &words[start..]
+}
+
+/// Split a line into simple commands, dropping comments
+fn parse_commands(line: &str) -> Vec<Command> {
+    let mut commands = Vec::new();
+    let mut command = Command::default();
+    let mut word = Word::default();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    fn finish(word: &mut Word, in_word: &mut bool, command: &mut Command) {
+        if *in_word || word.redirect {
+            command.words.push(std::mem::take(word));
+        }
+        *in_word = false;
+    }
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => word.text.extend(chars.next()),
+            (Some(_), c) => word.text.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                word.text.extend(chars.next());
+                in_word = true;
+            }
+            (None, '#') if !in_word => break,
+            (None, ' ' | '\t') => finish(&mut word, &mut in_word, &mut command),
+            (None, '>') => {
+                // A file descriptor number belongs to the redirection
+                if in_word && !word.text.chars().all(|c| c.is_ascii_digit()) {
+                    finish(&mut word, &mut in_word, &mut command);
+                }
+                word = Word {
+                    redirect: true,
+                    ..Default::default()
+                };
+                in_word = false;
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                }
+                // Duplications such as 2>&1 write no file
+                if chars.peek() == Some(&'&') {
+                    chars.next();
+                    word.text.push('&');
+                    in_word = true;
+                }
+            }
+            (None, ';' | '|' | '&') => {
+                finish(&mut word, &mut in_word, &mut command);
+                let doubled = chars.peek() == Some(&c);
+                if doubled {
+                    chars.next();
+                }
+                command.piped = c == '|' && !doubled;
+                commands.push(std::mem::take(&mut command));
+            }
+            (None, c) => {
+                if c == '$' {
+                    word.unquoted_expansion = true;
+                }
+                word.text.push(c);
+                in_word = true;
+            }
+        }
+    }
+    finish(&mut word, &mut in_word, &mut command);
+    if !command.words.is_empty() {
+        commands.push(command);
+    }
+
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn lint_str(content: &str) -> Vec<Finding> {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", content).unwrap();
+        lint(&BashScript::new(file.path())).unwrap()
+    }
+
+    fn rules(findings: &[Finding]) -> Vec<(Rule, Option<usize>)> {
+        findings.iter().map(|f| (f.rule, f.line)).collect()
+    }
+
+    const HEADER: &str = r#"readonly xNAME="app"
+readonly xVERSION="1.0"
+readonly xDESC="An app"
+"#;
+
+    #[test]
+    fn test_clean_script() {
+        let script = format!(
+            r#"{HEADER}xARCHS=(x86_64 arm64)
+
+install_any() {{
+    curl -fsSL "$xURL" -o "$xTMP/app"   # never | sh
+    install -m 755 "$xTMP/app" "$xBIN/app"
+    echo done > /dev/null 2>&1
+}}
+
+remove_any() {{
+    $xSUDO rm -f "$xBIN/app"
+}}
+
+install_apt() {{
+    $1 apt install -y app
+}}
+"#
+        );
+        assert_eq!(lint_str(&script), vec![]);
+    }
+
+    #[test]
+    fn test_metadata_and_methods() {
+        let findings = lint_str(
+            r#"readonly xNAME="app"
+readonly xDESC=""
+xARCHS=(x86_64 sparc)
+install_apt() { $1 apt install app; }
+install_brew() { brew install app; }
+remove_brew() { brew uninstall app; }
+"#,
+        );
+
+        assert_eq!(
+            rules(&findings),
+            vec![
+                (Rule::MissingMetadata, None),
+                (Rule::MissingMetadata, None),
+                (Rule::UnknownArch, Some(3)),
+                (Rule::MissingRemove, Some(4)),
+            ]
+        );
+        assert!(findings[0].message.contains("xVERSION"));
+        assert!(findings[2].message.contains("sparc"));
+        assert_eq!(findings[3].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_dangerous_patterns() {
+        let script = format!(
+            r#"{HEADER}install_any() {{
+    curl -fsSL https://example.com/install.sh | sudo bash
+    sh -c "$(wget -qO- https://example.com/install.sh)"
+    $xSUDO rm -rf /
+    rm -r --no-preserve-root "$dir"
+    $xSUDO rm -rf $xBIN/app
+    echo "PATH=$xBIN:$PATH" >> ~/.bashrc
+    cp app.conf /etc/app.conf
+    echo ok | $xSUDO tee "$xTMP/log" > "$xBIN/log"
+    $xSUDO sed -i 's/a/b/' /etc/app.conf
+    $xSUDO chmod 755 "$xBIN/app" /usr/bin/app
+    dd if=app.img of=/dev/sda bs=4M
+    sed -e 's/a/b/' -i "$xTMP/app.conf"
+    # rm -rf / in a comment is fine
+    echo "rm -rf / in a string is fine"
+}}
+remove_any() {{ rm -rf \
+    ~; }}
+"#
+        );
+
+        assert_eq!(
+            rules(&lint_str(&script)),
+            vec![
+                (Rule::PipeToShell, Some(5)),
+                (Rule::PipeToShell, Some(6)),
+                (Rule::RemoveRoot, Some(7)),
+                (Rule::RemoveRoot, Some(8)),
+                (Rule::UnquotedSudoRm, Some(9)),
+                (Rule::WriteOutsidePrefix, Some(10)),
+                (Rule::WriteOutsidePrefix, Some(11)),
+                (Rule::WriteOutsidePrefix, Some(13)),
+                (Rule::WriteOutsidePrefix, Some(14)),
+                (Rule::WriteOutsidePrefix, Some(15)),
+                (Rule::RemoveRoot, Some(20)),
+            ]
+        );
+    }
+}
@@ -6,6 +6,12 @@ use regex::Regex;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+pub mod lint;
+mod runner;
+pub mod sandbox;
+
+pub use runner::{tail, OutputStream, ScriptRunner};
+
 /// Bash script parser for XPM package scripts
 #[derive(Debug)]
 pub struct BashScript {
@@ -155,6 +161,24 @@ impl BashScript {
     }
 }
 
+/// Error returned when a package script exits unsuccessfully
+#[derive(Debug, thiserror::Error)]
+#[error("Script failed with exit code: {code:?}")]
+pub struct ScriptError {
+    /// Exit code (None if the script was terminated by a signal)
+    pub code: Option<i32>,
+}
+
+impl ScriptError {
+    /// Get the exit code of a script run result (Some(0) on success)
+    pub fn exit_code_of(result: &Result<()>) -> Option<i32> {
+        match result {
+            Ok(()) => Some(0),
+            Err(e) => e.downcast_ref::<ScriptError>().and_then(|e| e.code),
+        }
+    }
+}
+
 /// Script metadata extracted from bash script
 #[derive(Debug, Clone, Default)]
 pub struct ScriptMetadata {
@@ -167,6 +191,9 @@ pub struct ScriptMetadata {
     pub provides: Vec<String>,
     pub defaults: Vec<String>,
     pub methods: Vec<String>,
+    pub depends: Vec<String>,
+    pub recommends: Vec<String>,
+    pub conflicts: Vec<String>,
 }
 
 impl ScriptMetadata {
@@ -182,6 +209,9 @@ impl ScriptMetadata {
             provides: script.get_array("xPROVIDES").unwrap_or_default(),
             defaults: script.get_array("xDEFAULT").unwrap_or_default(),
             methods: script.available_install_methods(),
+            depends: script.get_array("xDEPENDS").unwrap_or_default(),
+            recommends: script.get_array("xRECOMMENDS").unwrap_or_default(),
+            conflicts: script.get_array("xCONFLICTS").unwrap_or_default(),
         }
     }
 
@@ -211,6 +241,9 @@ mod tests {
         writeln!(file, r#"xARCHS=(x86_64 aarch64)"#)?;
         writeln!(file, r#"xPROVIDES=('test-bin' 'test-cli')"#)?;
         writeln!(file, r#"xDEFAULT=(apt)"#)?;
+        writeln!(file, r#"xDEPENDS=(curl 'test-lib')"#)?;
+        writeln!(file, r#"xRECOMMENDS=(test-extras)"#)?;
+        writeln!(file, r#"xCONFLICTS=(test-legacy)"#)?;
         writeln!(file)?;
         writeln!(file, r#"install_apt() {{"#)?;
         writeln!(file, r#"    $1 install test-package"#)?;
@@ -274,6 +307,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_script_error_exit_code() {
+        assert_eq!(ScriptError::exit_code_of(&Ok(())), Some(0));
+
+        let failed: Result<()> = Err(ScriptError { code: Some(3) }.into());
+        assert_eq!(ScriptError::exit_code_of(&failed), Some(3));
+
+        let other: Result<()> = Err(anyhow::anyhow!("spawn failed"));
+        assert_eq!(ScriptError::exit_code_of(&other), None);
+    }
+
     #[test]
     fn test_script_metadata() -> Result<()> {
         let file = create_test_script()?;
@@ -282,6 +326,9 @@ mod tests {
         assert_eq!(metadata.name, Some("test-package".to_string()));
         assert_eq!(metadata.version, Some("1.0.0".to_string()));
         assert_eq!(metadata.archs, vec!["x86_64", "aarch64"]);
+        assert_eq!(metadata.depends, vec!["curl", "test-lib"]);
+        assert_eq!(metadata.recommends, vec!["test-extras"]);
+        assert_eq!(metadata.conflicts, vec!["test-legacy"]);
         Ok(())
     }
 }
@@ -0,0 +1,290 @@
+//! Script execution with output capture
+
+use super::sandbox::{Sandbox, SandboxReport};
+use super::ScriptError;
+use crate::db::Database;
+use crate::os::snapshot::Snapshot;
+use crate::os::XpmDirs;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Output stream a script line was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    /// Get the tag written in front of each logged line
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputStream::Stdout => "out",
+            OutputStream::Stderr => "err",
+        }
+    }
+}
+
+/// Runs bash scripts, teeing their output into an optional log file
+///
+/// Each logged line is prefixed with an RFC 3339 timestamp and the stream it
+/// came from, so stdout and stderr stay interleaved in the order they arrived.
+#[derive(Debug, Default)]
+pub struct ScriptRunner {
+    log_path: Option<PathBuf>,
+}
+
+impl ScriptRunner {
+    /// Create a runner that does not capture output
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder pattern for the log file (appended to if it already exists)
+    pub fn with_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.log_path = Some(path.into());
+        self
+    }
+
+    /// Get the log file path
+    pub fn log_path(&self) -> Option<&Path> {
+        self.log_path.as_deref()
+    }
+
+    /// Create a runner logging to a new file for an operation on a package
+    pub fn for_operation(operation: &str, package: &str) -> Result<Self> {
+        let name = format!(
+            "{}-{}-{}.log",
+            Utc::now().format("%Y%m%d-%H%M%S%3f"),
+            operation,
+            package.replace(['/', '\\'], "_")
+        );
+        Ok(Self::new().with_log(XpmDirs::logs_dir()?.join(name)))
+    }
+
+    /// Run a bash script, calling `on_line` for every line of output
+    ///
+    /// Returns a [`ScriptError`] if the script exits unsuccessfully.
+    pub async fn run<F>(&self, script: &str, on_line: F) -> Result<()>
+    where
+        F: FnMut(OutputStream, &str),
+    {
+        let mut command = Command::new("bash");
+        command.arg("-c").arg(script);
+        self.run_command(command, on_line).await
+    }
+
+    /// Run a bash script inside a sandbox, like [`ScriptRunner::run`]
+    ///
+    /// Also returns what the script did to the filesystem: the files it
+    /// changed in the writable paths and the writes the sandbox blocked.
+    pub async fn run_sandboxed<F>(
+        &self,
+        script: &str,
+        sandbox: &Sandbox,
+        mut on_line: F,
+    ) -> (Result<()>, SandboxReport)
+    where
+        F: FnMut(OutputStream, &str),
+    {
+        let mut report = SandboxReport::default();
+        let command = match sandbox.command(script) {
+            Ok(command) => command,
+            Err(e) => return (Err(e), report),
+        };
+
+        let before = Snapshot::take_async(sandbox.writable()).await;
+        let result = self
+            .run_command(command, |stream, line| {
+                if stream == OutputStream::Stderr {
+                    report.record_denied(line);
+                }
+                on_line(stream, line)
+            })
+            .await;
+        if let (Ok(before), Ok(after)) = (before, Snapshot::take_async(sandbox.writable()).await) {
+            report.changes = before.changes(&after);
+        }
+
+        (result, report)
+    }
+
+    async fn run_command<F>(&self, mut command: Command, mut on_line: F) -> Result<()>
+    where
+        F: FnMut(OutputStream, &str),
+    {
+        let mut log = match &self.log_path {
+            Some(path) => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .with_context(|| format!("Failed to open log file {}", path.display()))?,
+            ),
+            None => None,
+        };
+
+        // Scripts may run `$XPM install ...`, which needs the database lock
+        Database::release();
+
+        let program = command.as_std().get_program().to_string_lossy().to_string();
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", program))?;
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        if let Some(stdout) = child.stdout.take() {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let _ = tx.send((OutputStream::Stdout, line));
+                }
+            });
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let _ = tx.send((OutputStream::Stderr, line));
+                }
+            });
+        }
+
+        // Close our sender so the loop ends once both readers are done
+        drop(tx);
+
+        while let Some((stream, line)) = rx.recv().await {
+            if let Some(file) = log.as_mut() {
+                let entry = format!(
+                    "{} [{}] {}\n",
+                    Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                    stream.as_str(),
+                    line
+                );
+                file.write_all(entry.as_bytes()).await?;
+            }
+            on_line(stream, &line);
+        }
+
+        let status = child.wait().await?;
+
+        if let Some(file) = log.as_mut() {
+            file.flush().await?;
+        }
+
+        if !status.success() {
+            return Err(ScriptError {
+                code: status.code(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the last `count` lines of a captured log
+pub fn tail(path: impl AsRef<Path>, count: usize) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path.as_ref())
+        .with_context(|| format!("Failed to read log file {}", path.as_ref().display()))?;
+    let lines: Vec<String> = content.lines().map(String::from).collect();
+    let skip = lines.len().saturating_sub(count);
+    Ok(lines.into_iter().skip(skip).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_run_captures_output() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("test.log");
+        let runner = ScriptRunner::new().with_log(&log);
+
+        let mut seen = Vec::new();
+        runner
+            .run("echo hello; echo oops >&2", |stream, line| {
+                seen.push((stream, line.to_string()))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(seen.len(), 2);
+        assert!(seen.contains(&(OutputStream::Stdout, "hello".to_string())));
+        assert!(seen.contains(&(OutputStream::Stderr, "oops".to_string())));
+
+        let lines = tail(&log, 10).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().any(|l| l.ends_with("[out] hello")));
+        assert!(lines.iter().any(|l| l.ends_with("[err] oops")));
+    }
+
+    #[tokio::test]
+    async fn test_run_failure_appends() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("test.log");
+        let runner = ScriptRunner::new().with_log(&log);
+
+        runner.run("echo first", |_, _| {}).await.unwrap();
+        let result = runner.run("echo second; exit 3", |_, _| {}).await;
+
+        assert_eq!(ScriptError::exit_code_of(&result), Some(3));
+        assert_eq!(tail(&log, 1).unwrap().len(), 1);
+        assert!(tail(&log, 1).unwrap()[0].ends_with("second"));
+        assert_eq!(tail(&log, 10).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_sandboxed() {
+        use crate::os::snapshot::ChangeKind;
+        use crate::script::sandbox::SandboxBackend;
+
+        let dir = TempDir::new().unwrap();
+        let writable = dir.path().join("bin");
+        let Some(backend) = SandboxBackend::detect() else {
+            return;
+        };
+        let sandbox = Sandbox::new(backend).with_writable(&writable);
+
+        // User namespaces may be disabled where the tests run
+        let runner = ScriptRunner::new();
+        if runner
+            .run_sandboxed("true", &sandbox, |_, _| {})
+            .await
+            .0
+            .is_err()
+        {
+            return;
+        }
+
+        let outside = dir.path().join("outside");
+        let script = format!(
+            "echo app > '{}/app'; touch '{}'",
+            writable.display(),
+            outside.display()
+        );
+        let (result, report) = runner.run_sandboxed(&script, &sandbox, |_, _| {}).await;
+
+        assert!(result.is_err());
+        assert!(!outside.exists());
+        assert_eq!(report.denied, vec![outside]);
+        assert_eq!(report.changes.len(), 1);
+        assert_eq!(report.changes[0].path, writable.join("app"));
+        assert_eq!(report.changes[0].kind, ChangeKind::Created);
+    }
+}
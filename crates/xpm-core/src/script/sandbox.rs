@@ -0,0 +1,507 @@
+//! Sandboxed script execution
+//!
+//! A sandboxed script sees the whole filesystem read-only, except for the
+//! package's `xBIN` and `xTMP` and the directories its script declares in
+//! `xWRITES`. It runs in its own user, mount and PID namespaces, without
+//! network access unless allowed, through bubblewrap (`bwrap`) when it is
+//! installed or `unshare` from util-linux otherwise.
+//!
+//! Scripts cannot gain privileges inside the sandbox, so `xSUDO` is empty and
+//! installs through system package managers fail.
+
+use super::BashScript;
+use crate::os::snapshot::{ChangeKind, FileChange};
+use crate::os::{Executable, XpmDirs};
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::{Component, Path, PathBuf};
+use tokio::process::Command;
+
+/// Sets up the mounts inside the namespace created by `unshare`, then runs
+/// the script given as `$1` with the writable paths as the other arguments
+///
+/// Fails instead of running the script when a mount point cannot be made
+/// read-only, or when `/` or `$HOME` are still writable afterwards.
+const UNSHARE_SETUP: &str = r#"set -e
+script="$1"
+shift
+# Writable paths become mount points of their own, left out of the remount
+for path in "$@"; do
+    mount --bind "$path" "$path"
+done
+while read -r _ _ _ _ mount_point options _; do
+    mount_point=$(printf '%b' "$mount_point")
+    case "$mount_point" in
+        /proc | /proc/* | /sys | /sys/* | /dev | /dev/*) continue ;;
+    esac
+    for path in "$@"; do
+        case "$mount_point" in "$path" | "$path"/*) continue 2 ;; esac
+    done
+    # Flags locked by the parent namespace have to be kept for the remount
+    flags=remount,bind,ro
+    for option in ${options//,/ }; do
+        case "$option" in
+            nosuid | nodev | noexec | noatime | nodiratime | relatime | strictatime)
+                flags="$flags,$option" ;;
+        esac
+    done
+    if ! mount -o "$flags" "$mount_point"; then
+        echo "xpm sandbox: cannot make $mount_point read-only" >&2
+        exit 1
+    fi
+done < /proc/self/mountinfo
+for path in / "$HOME"; do
+    if [ -w "$path" ]; then
+        echo "xpm sandbox: $path is still writable" >&2
+        exit 1
+    fi
+done
+exec bash -c "$script"
+"#;
+
+/// System directories no script may make writable, `/usr/local` aside
+const SYSTEM_PATHS: &[&str] = &[
+    "/etc", "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/boot",
+];
+
+/// Paths in the home directory no script may make writable: credentials and
+/// files that run code at login
+const HOME_PATHS: &[&str] = &[
+    ".ssh",
+    ".gnupg",
+    ".profile",
+    ".bashrc",
+    ".bash_profile",
+    ".bash_login",
+    ".bash_logout",
+    ".zshrc",
+    ".zshenv",
+    ".zprofile",
+    ".zlogin",
+    ".config/autostart",
+    ".config/systemd",
+    ".config/fish",
+    ".config/environment.d",
+];
+
+/// The first absolute path on a line, after a space, quote or parenthesis
+static ABSOLUTE_PATH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?:^|[\s'"‘`(])(/[^\s'"‘’`:]+)"#).expect("valid regex"));
+
+/// Program used to build the sandbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SandboxBackend {
+    /// bubblewrap
+    Bubblewrap(PathBuf),
+    /// `unshare` from util-linux, in an unprivileged user namespace
+    Unshare(PathBuf),
+}
+
+impl SandboxBackend {
+    /// Find an available backend, preferring bubblewrap
+    pub fn detect() -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        Executable::new("bwrap")
+            .find()
+            .map(SandboxBackend::Bubblewrap)
+            .or_else(|| {
+                Executable::new("unshare")
+                    .find()
+                    .map(SandboxBackend::Unshare)
+            })
+    }
+
+    /// Get the backend name for display
+    pub fn name(&self) -> &'static str {
+        match self {
+            SandboxBackend::Bubblewrap(_) => "bubblewrap",
+            SandboxBackend::Unshare(_) => "unshare",
+        }
+    }
+}
+
+/// Restrictions a script runs under
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    backend: SandboxBackend,
+    writable: Vec<PathBuf>,
+    network: bool,
+}
+
+impl Sandbox {
+    /// Create a sandbox with nothing writable and no network
+    pub fn new(backend: SandboxBackend) -> Self {
+        Self {
+            backend,
+            writable: Vec::new(),
+            network: false,
+        }
+    }
+
+    /// Create the sandbox for a package script with the first available backend
+    ///
+    /// `xBIN`, the package's `xTMP` and `declared`, the paths from
+    /// [`declared_writes`], are writable.
+    pub fn for_package(name: &str, declared: &[PathBuf]) -> Result<Self> {
+        let backend = SandboxBackend::detect()
+            .context("Sandboxing needs bubblewrap (bwrap) or unshare from util-linux on Linux")?;
+
+        let mut sandbox = Self::new(backend)
+            .with_writable(XpmDirs::bin_dir()?)
+            .with_writable(XpmDirs::temp_dir(Some(name))?);
+        for path in declared {
+            sandbox = sandbox.with_writable(path);
+        }
+
+        Ok(sandbox)
+    }
+
+    /// Builder pattern for a path the script may write to
+    pub fn with_writable(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if !self.writable.contains(&path) {
+            self.writable.push(path);
+        }
+        self
+    }
+
+    /// Builder pattern for network access
+    pub fn with_network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn backend(&self) -> &SandboxBackend {
+        &self.backend
+    }
+
+    /// Paths the script may write to
+    pub fn writable(&self) -> &[PathBuf] {
+        &self.writable
+    }
+
+    /// Check whether the script may use the network
+    pub fn allows_network(&self) -> bool {
+        self.network
+    }
+
+    /// Build the command running a bash script inside the sandbox
+    ///
+    /// Missing writable directories are created first, so they can be
+    /// mounted.
+    pub fn command(&self, script: &str) -> Result<Command> {
+        for path in &self.writable {
+            if !path.exists() {
+                std::fs::create_dir_all(path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+            }
+        }
+
+        let command = match &self.backend {
+            SandboxBackend::Bubblewrap(bwrap) => {
+                let mut command = Command::new(bwrap);
+                command.args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"]);
+                for path in &self.writable {
+                    command.arg("--bind").arg(path).arg(path);
+                }
+                if !self.network {
+                    command.arg("--unshare-net");
+                }
+                command
+                    .args([
+                        "--unshare-user-try",
+                        "--unshare-pid",
+                        "--unshare-ipc",
+                        "--unshare-uts",
+                        "--die-with-parent",
+                        "--new-session",
+                        "--",
+                        "bash",
+                        "-c",
+                    ])
+                    .arg(script);
+                command
+            }
+            SandboxBackend::Unshare(unshare) => {
+                let mut command = Command::new(unshare);
+                command.args([
+                    "--user",
+                    "--map-root-user",
+                    "--mount",
+                    "--pid",
+                    "--fork",
+                    "--mount-proc",
+                    "--kill-child",
+                ]);
+                if !self.network {
+                    command.arg("--net");
+                }
+                command
+                    .args(["bash", "-c", UNSHARE_SETUP, "sandbox", script])
+                    .args(&self.writable);
+                command
+            }
+        };
+
+        Ok(command)
+    }
+}
+
+/// What a sandboxed script did to the filesystem
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SandboxReport {
+    /// Files created, modified or removed in the writable paths
+    pub changes: Vec<FileChange>,
+    /// Paths outside the writable ones the script tried to write to
+    pub denied: Vec<PathBuf>,
+}
+
+impl SandboxReport {
+    /// Record a path from a line of script output, if it reports a blocked write
+    pub fn record_denied(&mut self, line: &str) {
+        if let Some(path) = denied_path(line) {
+            if !self.denied.contains(&path) {
+                self.denied.push(path);
+            }
+        }
+    }
+
+    /// Add the report of another script run in the same sandbox
+    pub fn merge(&mut self, other: SandboxReport) {
+        for change in other.changes {
+            let Some(i) = self.changes.iter().position(|c| c.path == change.path) else {
+                self.changes.push(change);
+                continue;
+            };
+            match (self.changes[i].kind, change.kind) {
+                (ChangeKind::Created, ChangeKind::Removed) => {
+                    self.changes.remove(i);
+                }
+                (ChangeKind::Created, _) => {}
+                (ChangeKind::Removed, _) => self.changes[i].kind = ChangeKind::Modified,
+                (_, kind) => self.changes[i].kind = kind,
+            }
+        }
+        self.changes.sort();
+        for path in other.denied {
+            if !self.denied.contains(&path) {
+                self.denied.push(path);
+            }
+        }
+    }
+}
+
+/// Extract the path from an error about writing to the read-only filesystem
+///
+/// Tools word these errors differently, so this takes the first absolute
+/// path on a line mentioning `Read-only file system`; paths containing
+/// spaces, quotes or colons are cut short.
+pub fn denied_path(line: &str) -> Option<PathBuf> {
+    if !line.contains("Read-only file system") {
+        return None;
+    }
+
+    ABSOLUTE_PATH
+        .captures(line)
+        .and_then(|cap| cap.get(1))
+        .map(|m| PathBuf::from(m.as_str()))
+}
+
+/// Read the paths a package script declares in `xWRITES`
+///
+/// `~` and `$HOME` in them stand for the home directory. The script is not
+/// trusted, so relative paths, paths with `..`, top-level directories, the
+/// home directory or any of its parents and anything inside or around
+/// [`SYSTEM_PATHS`] and [`HOME_PATHS`] are refused.
+pub fn declared_writes(script: &BashScript) -> Result<Vec<PathBuf>> {
+    let home = XpmDirs::home_dir()?;
+    script
+        .get_array("xWRITES")
+        .unwrap_or_default()
+        .iter()
+        .map(|path| check_writable(path, &home))
+        .collect()
+}
+
+/// Expand a path from `xWRITES`, refusing those that open up too much
+fn check_writable(path: &str, home: &Path) -> Result<PathBuf> {
+    let expanded = expand_home(path, home);
+    if !expanded.is_absolute() {
+        anyhow::bail!("xWRITES path {} is not absolute", path);
+    }
+    if expanded
+        .components()
+        .any(|c| matches!(c, Component::ParentDir))
+    {
+        anyhow::bail!("xWRITES path {} must not contain '..'", path);
+    }
+    // The root and one directory below it
+    if expanded.components().count() <= 2 || home.starts_with(&expanded) {
+        anyhow::bail!("xWRITES path {} would make too much writable", path);
+    }
+
+    let sensitive = SYSTEM_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .chain(HOME_PATHS.iter().map(|p| home.join(p)));
+    for sensitive in sensitive {
+        let inside = expanded.starts_with(&sensitive) && !expanded.starts_with("/usr/local");
+        if inside || sensitive.starts_with(&expanded) {
+            anyhow::bail!(
+                "xWRITES path {} would make {} writable",
+                path,
+                sensitive.display()
+            );
+        }
+    }
+    Ok(expanded)
+}
+
+/// Expand a leading `~`, `$HOME` or `$xHOME` to the home directory
+fn expand_home(path: &str, home: &Path) -> PathBuf {
+    for prefix in ["~", "$HOME", "${HOME}", "$xHOME", "${xHOME}"] {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            if rest.is_empty() {
+                return home.to_path_buf();
+            }
+            if let Some(rest) = rest.strip_prefix('/') {
+                return home.join(rest);
+            }
+        }
+    }
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denied_path() {
+        let lines = [
+            (
+                "touch: cannot touch '/etc/app.conf': Read-only file system",
+                Some("/etc/app.conf"),
+            ),
+            (
+                "mkdir: cannot create directory ‘/opt/app’: Read-only file system",
+                Some("/opt/app"),
+            ),
+            (
+                "bash: line 3: /usr/share/app: Read-only file system",
+                Some("/usr/share/app"),
+            ),
+            (
+                "E: Could not open lock file /var/lib/dpkg/lock-frontend - open (30: Read-only file system)",
+                Some("/var/lib/dpkg/lock-frontend"),
+            ),
+            ("cp: cannot stat '/tmp/x': No such file or directory", None),
+        ];
+
+        for (line, expected) in lines {
+            assert_eq!(denied_path(line), expected.map(PathBuf::from), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = Path::new("/home/user");
+        assert_eq!(expand_home("~/.config/app", home), home.join(".config/app"));
+        assert_eq!(expand_home("$HOME", home), home);
+        assert_eq!(
+            expand_home("${xHOME}/.local/share", home),
+            home.join(".local/share")
+        );
+        assert_eq!(expand_home("/opt/app", home), PathBuf::from("/opt/app"));
+        assert_eq!(expand_home("~other", home), PathBuf::from("~other"));
+    }
+
+    #[test]
+    fn test_check_writable() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            check_writable("~/.config/app", home).unwrap(),
+            home.join(".config/app")
+        );
+        assert_eq!(
+            check_writable("/opt/app", home).unwrap(),
+            PathBuf::from("/opt/app")
+        );
+        assert!(check_writable("/usr/local/share/app", home).is_ok());
+
+        for path in [
+            "/",
+            "~",
+            "$HOME",
+            "~/",
+            "/home",
+            "/etc",
+            "/home/",
+            "app",
+            "~other",
+            "/opt/../etc",
+            "/usr/bin",
+            "/etc/ssh",
+            "/lib64/app",
+            "~/.ssh",
+            "~/.bashrc",
+            "~/.config",
+            "~/.config/autostart",
+            "~/.gnupg/private",
+        ] {
+            assert!(check_writable(path, home).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_sandbox_builder() {
+        let sandbox = Sandbox::new(SandboxBackend::Unshare(PathBuf::from("unshare")))
+            .with_writable("/tmp/a")
+            .with_writable("/tmp/a")
+            .with_network(true);
+
+        assert_eq!(sandbox.writable(), [PathBuf::from("/tmp/a")]);
+        assert!(sandbox.allows_network());
+    }
+
+    #[test]
+    fn test_report_merge() {
+        let change = |path: &str, kind| FileChange {
+            path: PathBuf::from(path),
+            kind,
+        };
+        let mut report = SandboxReport {
+            changes: vec![
+                change("/bin/app", ChangeKind::Created),
+                change("/bin/old", ChangeKind::Modified),
+                change("/tmp/app/src", ChangeKind::Created),
+            ],
+            denied: vec![PathBuf::from("/etc/app")],
+        };
+
+        report.merge(SandboxReport {
+            changes: vec![
+                change("/bin/app", ChangeKind::Modified),
+                change("/bin/old", ChangeKind::Removed),
+                change("/tmp/app/src", ChangeKind::Removed),
+            ],
+            denied: vec![PathBuf::from("/etc/app"), PathBuf::from("/opt/app")],
+        });
+
+        assert_eq!(
+            report.changes,
+            vec![
+                change("/bin/app", ChangeKind::Created),
+                change("/bin/old", ChangeKind::Removed),
+            ]
+        );
+        assert_eq!(
+            report.denied,
+            vec![PathBuf::from("/etc/app"), PathBuf::from("/opt/app")]
+        );
+    }
+}
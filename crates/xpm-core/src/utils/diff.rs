@@ -0,0 +1,219 @@
+//! Line diffs in the unified format
+
+use std::fmt;
+
+/// A line of a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    /// Present in both versions
+    Context(&'a str),
+    /// Only in the old version
+    Removed(&'a str),
+    /// Only in the new version
+    Added(&'a str),
+}
+
+impl fmt::Display for DiffLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffLine::Context(line) => write!(f, " {}", line),
+            DiffLine::Removed(line) => write!(f, "-{}", line),
+            DiffLine::Added(line) => write!(f, "+{}", line),
+        }
+    }
+}
+
+/// Nearby changes with the lines around them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    /// First old line, 1-based
+    pub old_start: usize,
+    pub old_len: usize,
+    /// First new line, 1-based
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<DiffLine<'a>>,
+}
+
+impl fmt::Display for Hunk<'_> {
+    /// Format the hunk header, such as `@@ -1,4 +1,5 @@`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        )
+    }
+}
+
+/// Diff two texts line by line
+///
+/// Uses a longest common subsequence, which is quadratic in the number of
+/// changed lines; fine for scripts, not for large files.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Only the middle, after the common prefix and suffix, needs the table
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j]: length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().map(|l| DiffLine::Context(l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(DiffLine::Context(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            // Removals first, as diff shows them
+            lines.push(DiffLine::Removed(a[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(b[j]));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|l| DiffLine::Context(l)),
+    );
+
+    lines
+}
+
+/// Diff two texts, keeping `context` unchanged lines around each change
+pub fn unified_diff<'a>(old: &'a str, new: &'a str, context: usize) -> Vec<Hunk<'a>> {
+    let lines = diff_lines(old, new);
+
+    // Old and new line numbers before each diff line
+    let mut positions = Vec::with_capacity(lines.len());
+    let (mut old_no, mut new_no) = (0, 0);
+    for line in &lines {
+        positions.push((old_no, new_no));
+        match line {
+            DiffLine::Context(_) => {
+                old_no += 1;
+                new_no += 1;
+            }
+            DiffLine::Removed(_) => old_no += 1,
+            DiffLine::Added(_) => new_no += 1,
+        }
+    }
+
+    let changes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Changes closer than twice the context share a hunk
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &change in &changes {
+        match groups.last_mut() {
+            Some((_, end)) if change - *end <= 2 * context + 1 => *end = change,
+            _ => groups.push((change, change)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(first, last)| {
+            let from = first.saturating_sub(context);
+            let to = (last + context).min(lines.len() - 1);
+            let hunk_lines = lines[from..=to].to_vec();
+
+            let old_len = hunk_lines
+                .iter()
+                .filter(|l| !matches!(l, DiffLine::Added(_)))
+                .count();
+            let new_len = hunk_lines
+                .iter()
+                .filter(|l| !matches!(l, DiffLine::Removed(_)))
+                .count();
+            // An empty side points at the line before it, as diff does
+            let (old_pos, new_pos) = positions[from];
+            Hunk {
+                old_start: if old_len == 0 { old_pos } else { old_pos + 1 },
+                old_len,
+                new_start: if new_len == 0 { new_pos } else { new_pos + 1 },
+                new_len,
+                lines: hunk_lines,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nc\nd\ne\n";
+
+        assert_eq!(
+            diff_lines(old, new),
+            vec![
+                DiffLine::Context("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Context("c"),
+                DiffLine::Context("d"),
+                DiffLine::Added("e"),
+            ]
+        );
+        assert!(diff_lines(old, old)
+            .iter()
+            .all(|l| matches!(l, DiffLine::Context(_))));
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 17\n", "")
+            .replace("line 20\n", "line 20\nline 21\n");
+
+        let hunks = unified_diff(&old, &new, 2);
+        assert_eq!(hunks.len(), 2);
+
+        assert_eq!(hunks[0].to_string(), "@@ -1,4 +1,4 @@");
+        let text: Vec<String> = hunks[0].lines.iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            text,
+            vec![" line 1", "-line 2", "+line two", " line 3", " line 4"]
+        );
+
+        // Nearby changes share a hunk
+        assert_eq!(hunks[1].to_string(), "@@ -15,6 +15,6 @@");
+
+        assert_eq!(
+            unified_diff("", "new\n", 3)[0].to_string(),
+            "@@ -0,0 +1,1 @@"
+        );
+        assert!(unified_diff(&old, &old, 3).is_empty());
+    }
+}
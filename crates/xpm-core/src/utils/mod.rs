@@ -1,6 +1,7 @@
 //! Utility modules for XPM
 
 pub mod checksum;
+pub mod diff;
 pub mod logger;
 pub mod slugify;
 pub mod startup;
@@ -3,6 +3,7 @@
 use anyhow::{Context, Result};
 use semver::Version;
 use serde::Deserialize;
+use std::cmp::Ordering;
 
 /// GitHub release response
 #[derive(Debug, Deserialize)]
@@ -123,22 +124,226 @@ pub struct ReleaseInfo {
     pub prerelease: bool,
 }
 
-/// Compare two semantic versions
-pub fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
-    let va = Version::parse(a.trim_start_matches('v')).ok();
-    let vb = Version::parse(b.trim_start_matches('v')).ok();
+/// Pre-release stage, ordered from least to most mature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PreRelease {
+    Dev,
+    Alpha,
+    Beta,
+    Rc,
+}
+
+impl PreRelease {
+    /// Parse a pre-release tag; single letters only count when followed by a number
+    fn parse(tag: &str, numbered: bool) -> Option<Self> {
+        match tag {
+            "dev" | "snapshot" => Some(PreRelease::Dev),
+            "alpha" => Some(PreRelease::Alpha),
+            "beta" => Some(PreRelease::Beta),
+            "rc" | "pre" | "preview" => Some(PreRelease::Rc),
+            "a" if numbered => Some(PreRelease::Alpha),
+            "b" if numbered => Some(PreRelease::Beta),
+            _ => None,
+        }
+    }
+}
+
+/// Numeric or alphabetic run inside a version string
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Num(u64),
+    Alpha(String),
+}
+
+impl Ord for Token {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Token::Num(a), Token::Num(b)) => a.cmp(b),
+            (Token::Alpha(a), Token::Alpha(b)) => a.cmp(b),
+            (Token::Alpha(_), Token::Num(_)) => Ordering::Less,
+            (Token::Num(_), Token::Alpha(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for Token {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-    match (va, vb) {
+/// Split a version fragment into numeric and alphabetic runs, dropping separators
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_digit() {
+            let mut run = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                run.push(d);
+                chars.next();
+            }
+            tokens.push(Token::Num(run.parse().unwrap_or(u64::MAX)));
+        } else if c.is_alphabetic() {
+            let mut run = String::new();
+            while let Some(&a) = chars.peek().filter(|a| a.is_alphabetic()) {
+                run.extend(a.to_lowercase());
+                chars.next();
+            }
+            tokens.push(Token::Alpha(run));
+        } else {
+            chars.next();
+        }
+    }
+
+    tokens
+}
+
+/// A package version that can be ordered
+///
+/// Understands semver (`1.2.3`, `1.2.3-rc.1`), calver (`2024.01.15`),
+/// Debian-style epochs and revisions (`2:1.2-3`), `v` prefixes and
+/// `-rc`/`-beta`/`-alpha`/`-dev` pre-releases. Missing components count as
+/// zero, so `v1.2` equals `1.2.0`. Build metadata after `+` is ignored.
+#[derive(Debug, Clone)]
+pub struct PackageVersion {
+    epoch: u64,
+    release: Vec<u64>,
+    pre: Option<(PreRelease, u64)>,
+    extra: Vec<Token>,
+    revision: Vec<Token>,
+}
+
+impl PackageVersion {
+    /// Parse a version string, returning None if it has no numeric release part
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut rest = version.trim();
+        rest = rest.strip_prefix(['v', 'V']).unwrap_or(rest);
+        rest = rest.split('+').next().unwrap_or(rest);
+
+        let mut epoch = 0;
+        if let Some((prefix, tail)) = rest.split_once(':') {
+            if !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_digit()) {
+                epoch = prefix.parse().unwrap_or(u64::MAX);
+                rest = tail;
+            }
+        }
+
+        // A trailing "-N" is a revision unless it starts a pre-release
+        let mut revision = Vec::new();
+        if let Some((upstream, tail)) = rest.rsplit_once('-') {
+            let tail_tokens = tokenize(tail);
+            let is_pre = match tail_tokens.as_slice() {
+                [Token::Alpha(tag), next @ ..] => {
+                    PreRelease::parse(tag, matches!(next.first(), Some(Token::Num(_)))).is_some()
+                }
+                _ => false,
+            };
+            if !is_pre && !upstream.is_empty() {
+                revision = tail_tokens;
+                rest = upstream;
+            }
+        }
+
+        let mut tokens = tokenize(rest).into_iter().peekable();
+
+        let mut release = Vec::new();
+        while let Some(Token::Num(n)) = tokens.peek() {
+            release.push(*n);
+            tokens.next();
+        }
+        if release.is_empty() {
+            return None;
+        }
+
+        let mut pre = None;
+        if let Some(Token::Alpha(tag)) = tokens.peek() {
+            let tag = tag.clone();
+            let mut lookahead = tokens.clone();
+            lookahead.next();
+            let number = match lookahead.peek() {
+                Some(Token::Num(n)) => Some(*n),
+                _ => None,
+            };
+            if let Some(stage) = PreRelease::parse(&tag, number.is_some()) {
+                tokens.next();
+                if number.is_some() {
+                    tokens.next();
+                }
+                pre = Some((stage, number.unwrap_or(0)));
+            }
+        }
+
+        Some(Self {
+            epoch,
+            release,
+            pre,
+            extra: tokens.collect(),
+            revision,
+        })
+    }
+
+    /// Check if this is a pre-release
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some()
+    }
+}
+
+impl Ord for PackageVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| {
+                let len = self.release.len().max(other.release.len());
+                let pad = |v: &[u64]| {
+                    (0..len)
+                        .map(|i| v.get(i).copied().unwrap_or(0))
+                        .collect::<Vec<_>>()
+                };
+                pad(&self.release).cmp(&pad(&other.release))
+            })
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+            .then_with(|| self.extra.cmp(&other.extra))
+            .then_with(|| self.revision.cmp(&other.revision))
+    }
+}
+
+impl PartialOrd for PackageVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for PackageVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PackageVersion {}
+
+/// Compare two version strings
+///
+/// Versions that cannot be parsed sort below parseable ones, and are compared
+/// as plain strings against each other.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (PackageVersion::parse(a), PackageVersion::parse(b)) {
         (Some(va), Some(vb)) => va.cmp(&vb),
-        (Some(_), None) => std::cmp::Ordering::Greater,
-        (None, Some(_)) => std::cmp::Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
         (None, None) => a.cmp(b),
     }
 }
 
 /// Check if version a is newer than version b
 pub fn is_newer(a: &str, b: &str) -> bool {
-    compare_versions(a, b) == std::cmp::Ordering::Greater
+    compare_versions(a, b) == Ordering::Greater
 }
 
 #[cfg(test)]
@@ -170,6 +375,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compare_reformatted_versions() {
+        assert_eq!(compare_versions("v1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2.0+build5", "1.2"), Ordering::Equal);
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_compare_prereleases() {
+        assert!(is_newer("1.0.0", "1.0.0-rc1"));
+        assert!(is_newer("1.0.0-rc.2", "1.0.0-rc.1"));
+        assert!(is_newer("1.0.0-rc1", "1.0.0-beta3"));
+        assert!(is_newer("1.0.0-beta", "1.0.0-alpha.5"));
+        assert!(is_newer("1.0.0-alpha", "1.0.0-dev"));
+        assert!(is_newer("1.0b2", "1.0a7"));
+        assert!(is_newer("1.0.1-rc1", "1.0.0"));
+        assert!(PackageVersion::parse("2.0.0-beta.1")
+            .unwrap()
+            .is_prerelease());
+    }
+
+    #[test]
+    fn test_compare_debian_versions() {
+        assert!(is_newer("1:1.0", "2.5"));
+        assert!(is_newer("1.2.3-2", "1.2.3-1"));
+        assert!(is_newer("1.2.3-1", "1.2.3"));
+        assert!(is_newer("1.2.4-1", "1.2.3-9"));
+        assert!(is_newer("1.2-0ubuntu2", "1.2-0ubuntu1"));
+        assert!(is_newer("1.0-rc1-2", "1.0-rc1-1"));
+        assert!(is_newer("1.0-1", "1.0-rc1-1"));
+    }
+
+    #[test]
+    fn test_compare_calver() {
+        assert!(is_newer("2024.01.15", "2023.12.31"));
+        assert!(is_newer("2024.1.2", "2024.01.1"));
+        assert!(is_newer("20240115", "20231231"));
+    }
+
+    #[test]
+    fn test_compare_suffixes() {
+        // Letter suffixes without a number are patch releases (openssl style)
+        assert!(is_newer("1.1.1w", "1.1.1"));
+        assert!(is_newer("1.1.1w", "1.1.1a"));
+        assert!(is_newer("1.0", "unknown"));
+        assert!(PackageVersion::parse("latest").is_none());
+    }
+
     #[test]
     fn test_is_newer() {
         assert!(is_newer("2.0.0", "1.0.0"));